@@ -12,13 +12,24 @@ declare module '1fpga:core' {
     path: string;
   }
 
+  /**
+   * A path to an MRA file of an arcade game. The core is found from the MRA
+   * file, and the game ROMs are built from the MAME ROM archives it lists.
+   */
+  export interface CoreMra {
+    type: 'Mra';
+    path: string;
+  }
+
   /**
    * The type of core to start.
    */
-  export type CoreType = CorePath;
+  export type CoreType = CorePath | CoreMra;
 
   /**
-   * A path to a game ROM.
+   * A path to a game ROM. The ROM can be a file inside a zip or 7z archive, by
+   * following the path of the archive with the path of the file in it (see
+   * `readArchive` in `1fpga:fs`).
    */
   export interface GameRomPath {
     type: 'RomPath';
@@ -44,9 +55,17 @@ declare module '1fpga:core' {
      */
     game?: GameType;
 
+    /**
+     * BIOS files to load before the game. Files named `boot.rom` (or `boot1.rom`
+     * to `boot3.rom`) are loaded in the core's boot ROM slots, other files are
+     * loaded in the core's first (index 0) file slot.
+     */
+    bios?: string[];
+
     /**
      * The save file path to load (or save to). If missing the core will
-     * not use any save file.
+     * not use any save file. An `.m3u` playlist inserts the first disk of
+     * a multi-disk game (see `OneFpgaCore.nextDisk`).
      */
     files?: (string | undefined)[];
 
@@ -54,6 +73,36 @@ declare module '1fpga:core' {
      * The savestate file to load. If savestates are not supported, this will be ignored.
      */
     savestate?: string;
+
+    /**
+     * Files to save the core options to, most specific first (e.g. a per-game file,
+     * then a per-core file). The first file that exists is used to restore the options
+     * when the core starts, and options are saved to the first file when they change.
+     * If none exist, options are imported from MiSTer's `config/<CORE>.CFG`, if any.
+     * If missing, options are not saved.
+     */
+    statusFiles?: string[];
+
+    /**
+     * The file to save the DIP switches of an arcade game to. The switches are restored
+     * from this file when the core starts, and saved to it when they change.
+     */
+    dipFile?: string;
+
+    /**
+     * The file to save the files chosen for the core's remembered entries to (e.g. a
+     * palette or a boot ROM). These files are sent to the core again when it starts.
+     * Defaults to a file per core.
+     */
+    rememberFile?: string;
+
+    /**
+     * The number of save state slots of the core, if it supports save states. The
+     * default is 4, and all slots must fit in the memory of the core. Save states
+     * saved from a different core or game are rejected with an
+     * `IncompatibleSaveStateError`.
+     */
+    saveStateSlots?: number;
   }
 
   /**
@@ -148,6 +197,40 @@ declare module '1fpga:core' {
    */
   export type QuitListener = (error?: any) => void | Promise<void>;
 
+  /**
+   * The `name` of errors thrown by core operations, depending on what went wrong.
+   * Other errors are thrown as a plain `Error`.
+   */
+  export type CoreErrorName =
+    | 'UnsupportedError'
+    | 'InvalidSlotError'
+    | 'InvalidConfigError'
+    | 'TimeoutError'
+    | 'ProtocolError'
+    | 'IoError'
+    | 'CoreClosedError'
+    | 'CancelledError'
+    | 'IncompatibleSaveStateError'
+    | 'MissingFileError'
+    | 'InvalidFileError';
+
+  /**
+   * An event fired by the core while it runs.
+   */
+  export type CoreEvent =
+    | { kind: 'sdServiced'; slot: number; write: boolean }
+    | { kind: 'statusBitsChanged' }
+    | { kind: 'videoModeChanged'; width: number; height: number; interlaced: boolean }
+    | { kind: 'info'; message: string };
+
+  /**
+   * Callback for core events.
+   * @param event The event, with its `kind` matching the event name.
+   */
+  export type CoreEventListener<K extends CoreEvent['kind']> = (
+    event: Extract<CoreEvent, { kind: K }>,
+  ) => void | Promise<void>;
+
   /**
    * The result of the OSD, whether to quit the core or not.
    */
@@ -158,6 +241,43 @@ declare module '1fpga:core' {
    */
   export type CoreOsdFunction = () => Promise<OsdResult>;
 
+  /**
+   * A slot in which a core can load or mount a file.
+   */
+  export interface FileSlot {
+    index: number;
+    label: string | null;
+    extensions: string[];
+  }
+
+  /**
+   * The features supported by a core. Features the core cannot detect are
+   * `null` (unknown).
+   */
+  export interface CoreCapabilities {
+    /**
+     * The number of save state slots (0 if save states are not supported).
+     */
+    saveStateSlots: number;
+    mountSlots: FileSlot[];
+    biosSlots: FileSlot[];
+    screenshot: boolean;
+    keyboard: boolean | null;
+    mouse: boolean | null;
+    analog: boolean | null;
+    gamepads: number;
+    rtc: boolean | null;
+  }
+
+  /**
+   * A cheat loaded for the current game.
+   */
+  export interface Cheat {
+    id: number;
+    name: string;
+    enabled: boolean;
+  }
+
   export interface OneFpgaCore {
     /**
      * Return the name of the core.
@@ -169,11 +289,29 @@ declare module '1fpga:core' {
      */
     readonly settings: CoreSettings;
 
+    /**
+     * The features supported by the core.
+     */
+    readonly capabilities: CoreCapabilities;
+
     /**
      * The core's current status bits. Only if the core supports it.
      */
     statusBits: number[];
 
+    /**
+     * The cheats available for the current game. Empty if the core does not support
+     * cheats or no cheats were found for the game.
+     */
+    readonly cheats: Cheat[];
+
+    /**
+     * Enable or disable a cheat, and send the enabled cheats to the core.
+     * @param id The ID of the cheat.
+     * @param enabled Whether the cheat should be enabled.
+     */
+    setCheat(id: number, enabled: boolean): void;
+
     /**
      * The core's main loop, sending any inputs to the core, and checking for
      * shortcuts. This function will return when the core is unloaded by the
@@ -217,6 +355,41 @@ declare module '1fpga:core' {
      */
     reset(): void;
 
+    /**
+     * Set where the UART of the core is bridged to on Linux, when a UART mode is
+     * selected in its settings. If the UART is enabled, it is bridged to the new
+     * endpoint right away.
+     * @param endpoint One of `pty` (a new pseudo-terminal), `tty:{path}` (a serial
+     *   device), `tcp:{host}:{port}` (a TCP connection) or `midi[:{client}:{port}]`
+     *   (a new ALSA sequencer port, connected to a port if given). `null` uses the
+     *   default endpoint of the mode.
+     */
+    setUartEndpoint(endpoint: string | null): void;
+
+    /**
+     * Insert a disk image in a slot. The image already in the slot, if any, is
+     * ejected first so the core sees the disk change. If the path is an `.m3u`
+     * playlist, its first disk is inserted (see `nextDisk`).
+     * @param slot The index of the slot.
+     * @param path The path of the disk image or playlist.
+     */
+    insertImage(slot: number, path: string): void;
+
+    /**
+     * Eject the disk image in a slot, leaving it empty.
+     * @param slot The index of the slot.
+     */
+    ejectImage(slot: number): void;
+
+    /**
+     * Insert the next disk of the playlist of a slot, or the first disk after
+     * the last one.
+     * @param slot The index of the slot.
+     * @returns The path of the disk inserted, or `null` if the slot has no
+     *   playlist.
+     */
+    nextDisk(slot: number): string | null;
+
     /**
      * Quit the core and returns to the main menu.
      */
@@ -245,11 +418,75 @@ declare module '1fpga:core' {
      * @param listener The event listener.
      */
     on(event: 'quit', listener: QuitListener): void;
+
+    /**
+     * Specialization of the `on` method for events produced by the core.
+     * @param event The event name.
+     * @param listener The event listener.
+     */
+    on<K extends CoreEvent['kind']>(event: K, listener: CoreEventListener<K>): void;
+  }
+
+  /**
+   * The progress of a file sent to a core while it is loaded.
+   */
+  export interface TransferProgress {
+    /**
+     * The index of the file slot the file is sent to.
+     */
+    index: number;
+
+    /**
+     * The number of bytes sent so far.
+     */
+    sent: number;
+
+    /**
+     * The size of the file, in bytes.
+     */
+    total: number;
   }
 
   /**
    * Starts a core with the given options.
    * @param options The options for the core.
+   * @param onProgress Called while files (ROMs, BIOS, etc.) are sent to the
+   *   core. Return `false` to cancel loading, which rejects with a
+   *   `CancelledError`.
+   */
+  export function load(
+    options: RunOptions,
+    onProgress?: (progress: TransferProgress) => boolean | void,
+  ): Promise<OneFpgaCore>;
+
+  /**
+   * A backup of a save file. Backups are made when a save file is first
+   * written after a core is started, and the most recent ones are kept.
+   */
+  export interface SaveBackup {
+    /**
+     * The path of the backup file.
+     */
+    path: string;
+
+    /**
+     * The time the backup was made, in milliseconds since the Unix epoch.
+     */
+    timestamp: number;
+  }
+
+  /**
+   * List the backups of a save file, newest first.
+   * @param path The path of the save file.
+   */
+  export function listSaveBackups(path: string): SaveBackup[];
+
+  /**
+   * Replace a save file with one of its backups. The current save file is
+   * backed up first, so restoring can be undone. This should not be called
+   * while a core is using the save file.
+   * @param path The path of the save file.
+   * @param backup The path of the backup, as returned by `listSaveBackups`.
    */
-  export function load(options: RunOptions): Promise<OneFpgaCore>;
+  export function restoreSaveBackup(path: string, backup: string): void;
 }
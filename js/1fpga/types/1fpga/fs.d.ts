@@ -16,6 +16,14 @@ declare module '1fpga:fs' {
 
   export function readDir(path: string): Promise<string[]>;
 
+  /**
+   * List the files inside a zip or 7z archive. The paths returned are the path
+   * of the archive followed by the path of the file in the archive (e.g.
+   * `/media/fat/games/SNES/Games.zip/Game.sfc`), and can be used as game paths.
+   * @param path The path to the archive.
+   */
+  export function readArchive(path: string): Promise<string[]>;
+
   export function isFile(path: string): Promise<boolean>;
 
   /**
@@ -39,6 +39,22 @@ export class QuitCoreCommand extends CoreCommandImpl {
   }
 }
 
+export class NextDiskCommand extends CoreCommandImpl {
+  key = 'nextDisk';
+  label = 'Insert the next disk of a multi-disk game';
+  category = 'Core';
+
+  execute(core: core.OneFpgaCore) {
+    // Cycle every slot that has a playlist.
+    for (let slot = 0; slot < 16; slot++) {
+      const path = core.nextDisk(slot);
+      if (path !== null) {
+        console.log(`Inserted disk "${path}" in slot ${slot}.`);
+      }
+    }
+  }
+}
+
 export class ShowDebugLogCommand extends CoreCommandImpl {
   key = 'showDebugLog';
   label = 'Show a debug log';
@@ -82,6 +98,7 @@ export class ScreenshotCommand extends CoreCommandImpl {
 export async function init() {
   await Commands.register(ShowCoreMenuCommand);
   await Commands.register(QuitCoreCommand);
+  await Commands.register(NextDiskCommand);
   await Commands.register(ShowDebugLogCommand);
   await Commands.register(ScreenshotCommand);
 }
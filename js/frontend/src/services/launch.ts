@@ -15,6 +15,43 @@ export function running() {
   return { game: runningGame, core: runningCore };
 }
 
+/**
+ * The files to save core options to, most specific first.
+ */
+function statusFiles(
+  core: db.cores.CoreRow | null,
+  game: db.games.ExtendedGamesRow | null,
+  perGame: boolean,
+): string[] {
+  if (core === null) {
+    return [];
+  }
+
+  const user = User.loggedInUser(true);
+  const root = `/media/fat/1fpga/options/${user.id}/${core.uniqueName}`;
+  return [...(perGame && game ? [`${root}/${game.name}.cfg`] : []), `${root}.cfg`];
+}
+
+/**
+ * The file to save the DIP switches of a game to.
+ */
+function dipFile(core: db.cores.CoreRow, game: db.games.ExtendedGamesRow): string {
+  const user = User.loggedInUser(true);
+  return `/media/fat/1fpga/options/${user.id}/${core.uniqueName}/${game.name}.dip`;
+}
+
+/**
+ * The file to save the files remembered by a core to.
+ */
+function rememberFile(core: db.cores.CoreRow | null): string | undefined {
+  if (core === null) {
+    return undefined;
+  }
+
+  const user = User.loggedInUser(true);
+  return `/media/fat/1fpga/options/${user.id}/${core.uniqueName}.files`;
+}
+
 /**
  * Options when starting a core.
  */
@@ -37,6 +74,8 @@ export async function core(coreRow: db.cores.CoreRow | string, { menu = false }:
     runningCore = typeof coreRow !== 'string' ? coreRow : null;
     const c = await oneFpgaCore.load({
       core: { type: 'Path', path },
+      statusFiles: statusFiles(runningCore, null, false),
+      rememberFile: rememberFile(runningCore),
     });
 
     const settings = await (
@@ -73,6 +112,9 @@ export async function game(gameRow: db.games.ExtendedGamesRow) {
     const c = await oneFpgaCore.load({
       core: { type: 'Path', path: gameRow.rbfPath },
       ...(gameRow.romPath ? { game: { type: 'RomPath', path: gameRow.romPath } } : {}),
+      statusFiles: statusFiles(runningCore, gameRow, await settings.perGameCoreOptions()),
+      ...(runningCore ? { dipFile: dipFile(runningCore, gameRow) } : {}),
+      rememberFile: rememberFile(runningCore),
     });
 
     c.volume = await settings.defaultVolume();
@@ -21,6 +21,7 @@ const START_ON_KEY = 'startOn';
 const DEV_TOOLS_KEY = 'devTools';
 const GAME_SORT_KEY = 'gameSort';
 const DEFAULT_VOLUME_KEY = 'defaultVolume';
+const PER_GAME_CORE_OPTIONS_KEY = 'perGameCoreOptions';
 
 export class UserSettings {
   public static async forLoggedInUser(): Promise<UserSettings> {
@@ -79,4 +80,12 @@ export class UserSettings {
   public async setDefaultVolume(value: number): Promise<void> {
     await this.storage_.set(DEFAULT_VOLUME_KEY, value);
   }
+
+  public async perGameCoreOptions(): Promise<boolean> {
+    return await getOrFail(this.storage_, PER_GAME_CORE_OPTIONS_KEY, false);
+  }
+
+  public async setPerGameCoreOptions(value: boolean): Promise<void> {
+    await this.storage_.set(PER_GAME_CORE_OPTIONS_KEY, value);
+  }
 }
@@ -163,6 +163,19 @@ impl Default for SocFpga<memory::DevMemMemoryMapper> {
     }
 }
 
+#[cfg(feature = "std")]
+impl SocFpga<memory::DevMemMemoryMapper> {
+    /// Create the SoC over anonymous memory (see
+    /// [`memory::DevMemMemoryMapper::anonymous`]), to test code that uses the
+    /// `/dev/mem` mapper without the hardware.
+    pub fn create_anonymous() -> Self {
+        let memory = memory::DevMemMemoryMapper::anonymous(sizes::BASE)
+            .expect("Could not create memory mapper");
+
+        Self::new(memory)
+    }
+}
+
 #[cfg(feature = "std")]
 impl SocFpga<memory::BufferMemoryMapper> {
     pub fn create_for_test() -> Self {
@@ -29,6 +29,33 @@ impl fmt::Debug for DevMemMemoryMapper {
     }
 }
 
+impl DevMemMemoryMapper {
+    /// Map zeroed anonymous memory instead of physical memory. Nothing is shared
+    /// with the FPGA, so this is only useful to run code that expects a
+    /// `DevMemMemoryMapper` without the hardware, e.g. in tests.
+    pub fn anonymous(size: usize) -> Result<Self, &'static str> {
+        unsafe {
+            let res = libc::mmap(
+                std::ptr::null_mut(),
+                size as libc::size_t,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+
+            if res == libc::MAP_FAILED {
+                return Err("Unable to map memory region");
+            }
+
+            Ok(Self {
+                region: core::slice::from_raw_parts_mut(res as *mut u8, size),
+                physical: (0, size),
+            })
+        }
+    }
+}
+
 impl MemoryMapper for DevMemMemoryMapper {
     fn create(address: usize, size: usize) -> Result<Self, &'static str> {
         unsafe {
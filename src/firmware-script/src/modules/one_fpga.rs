@@ -9,6 +9,7 @@ mod commands;
 mod controllers;
 mod core;
 mod db;
+mod error;
 mod fs;
 mod gui;
 mod net;
@@ -4,19 +4,25 @@ use boa_macros::boa_module;
 #[boa_module]
 #[boa(rename_all = "camelCase")]
 mod js {
+    use crate::modules::one_fpga::error::core_error;
     use crate::modules::JsCore;
     use crate::AppRef;
     use boa_engine::class::Class;
     use boa_engine::interop::ContextData;
+    use boa_engine::object::builtins::JsFunction;
     use boa_engine::value::TryFromJs;
     use boa_engine::{js_error, Context, JsError};
-    use boa_engine::{JsResult, JsValue};
+    use boa_engine::{JsResult, JsString, JsValue};
     use boa_macros::{Finalize, JsData, Trace};
-    use one_fpga::core::Rom;
+    use mister_fpga::core::save_file::{self, SaveFileOptions};
+    use mister_fpga::core::TransferProgress;
+    use one_fpga::core::{Bios, Rom};
     use one_fpga::runner::CoreLaunchInfo;
     use serde::Deserialize;
     use std::path::PathBuf;
-    use tracing::info;
+    use std::sync::Arc;
+    use std::time::UNIX_EPOCH;
+    use tracing::{info, warn};
 
     /// The core type from JavaScript.
     #[boa(skip)]
@@ -24,6 +30,7 @@ mod js {
     #[serde(tag = "type")]
     enum CoreType {
         Path { path: String },
+        Mra { path: String },
     }
 
     /// The game type for JavaScript.
@@ -40,8 +47,13 @@ mod js {
     struct RunOptions {
         core: CoreType,
         game: Option<GameType>,
+        bios: Option<Vec<String>>,
         files: Option<Vec<Option<String>>>,
         savestate: Option<String>,
+        status_files: Option<Vec<String>>,
+        dip_file: Option<String>,
+        remember_file: Option<String>,
+        save_state_slots: Option<usize>,
         show_menu: Option<bool>,
     }
 
@@ -58,16 +70,23 @@ mod js {
 
     fn load(
         options: RunOptions,
+        on_progress: Option<JsFunction>,
         ContextData(mut app): ContextData<AppRef>,
         context: &mut Context,
     ) -> JsResult<JsValue> {
         let mut core_options = match &options.core {
             CoreType::Path { path } => CoreLaunchInfo::rbf(PathBuf::from(path)),
+            CoreType::Mra { path } => CoreLaunchInfo::mra(PathBuf::from(path)),
         };
 
+        for path in options.bios.iter().flatten() {
+            let file = std::fs::File::open(path).map_err(JsError::from_rust)?;
+            core_options = core_options.with_bios(Bios::File(PathBuf::from(path), Arc::new(file)));
+        }
+
         match &options.game {
             Some(GameType::RomPath { path }) => {
-                core_options = core_options.with_rom(Rom::File(PathBuf::from(path)));
+                core_options = core_options.with_rom(Rom::from_path(path));
             }
             None => {}
         };
@@ -84,15 +103,79 @@ mod js {
             }
         }
 
+        for path in options.status_files.iter().flatten() {
+            core_options = core_options.with_status_file(PathBuf::from(path));
+        }
+
+        if let Some(path) = &options.dip_file {
+            core_options = core_options.with_dip_file(PathBuf::from(path));
+        }
+
+        if let Some(path) = &options.remember_file {
+            core_options = core_options.with_remember_file(PathBuf::from(path));
+        }
+
+        if let Some(slots) = options.save_state_slots {
+            core_options = core_options.with_save_state_slots(slots);
+        }
+
         info!("Launching core: {:?}", core_options);
+        // Returning `false` from the progress callback cancels the launch. An
+        // exception in the callback also cancels it.
+        let report = |progress: &TransferProgress| {
+            let Some(f) = &on_progress else {
+                return true;
+            };
+            let result = serde_json::to_value(progress)
+                .map_err(JsError::from_rust)
+                .and_then(|json| JsValue::from_json(&json, context))
+                .and_then(|value| f.call(&JsValue::undefined(), &[value], context));
+            match result {
+                Ok(value) => value.as_boolean() != Some(false),
+                Err(error) => {
+                    warn!(%error, "Progress callback failed, cancelling");
+                    false
+                }
+            }
+        };
         let core = app
             .platform_mut()
             .core_manager_mut()
-            .launch(core_options)
-            .unwrap();
+            .launch(core_options, report)
+            .map_err(|e| core_error(e, context))?;
 
         Ok(JsCore::from_data(JsCore::new(core), context)?.into())
     }
+
+    /// The backups of a save file, newest first.
+    fn list_save_backups(path: JsString, context: &mut Context) -> JsResult<JsValue> {
+        let path = PathBuf::from(path.to_std_string_escaped());
+        let backups = save_file::backups(&path)
+            .map_err(JsError::from_rust)?
+            .into_iter()
+            .map(|backup| {
+                let timestamp = backup
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as u64);
+                serde_json::json!({
+                    "path": backup.path.to_string_lossy(),
+                    "timestamp": timestamp,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        JsValue::from_json(&serde_json::Value::Array(backups), context)
+    }
+
+    /// Replace a save file with one of its backups.
+    fn restore_save_backup(path: JsString, backup: JsString) -> JsResult<()> {
+        let path = PathBuf::from(path.to_std_string_escaped());
+        let backup = PathBuf::from(backup.to_std_string_escaped());
+        info!(?path, ?backup, "Restoring save file");
+        save_file::restore_backup(&path, &backup, SaveFileOptions::default().backups)
+            .map_err(JsError::from_rust)
+    }
 }
 
 pub fn create_module(context: &mut Context) -> JsResult<(JsString, Module)> {
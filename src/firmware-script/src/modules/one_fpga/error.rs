@@ -0,0 +1,32 @@
+use boa_engine::{Context, JsError, JsNativeError, JsString};
+use one_fpga::core::Error;
+
+/// Convert a core error into a JavaScript error. Each kind of error has its own
+/// `name` (e.g. `UnsupportedError`), so scripts can tell them apart and show the
+/// user something meaningful.
+pub fn core_error(error: Error, context: &mut Context) -> JsError {
+    let (native, name) = match &error {
+        Error::Unsupported(_) => (JsNativeError::typ(), "UnsupportedError"),
+        Error::InvalidSlot(_) => (JsNativeError::range(), "InvalidSlotError"),
+        Error::InvalidConfig(_) => (JsNativeError::syntax(), "InvalidConfigError"),
+        Error::Timeout(_) => (JsNativeError::error(), "TimeoutError"),
+        Error::Protocol(_) => (JsNativeError::error(), "ProtocolError"),
+        Error::IoError(_) => (JsNativeError::error(), "IoError"),
+        Error::CoreClosed => (JsNativeError::error(), "CoreClosedError"),
+        Error::Cancelled => (JsNativeError::error(), "CancelledError"),
+        Error::IncompatibleSaveState(_) => (JsNativeError::error(), "IncompatibleSaveStateError"),
+        Error::MissingFile(_) => (JsNativeError::error(), "MissingFileError"),
+        Error::InvalidFile(_) => (JsNativeError::error(), "InvalidFileError"),
+        Error::Generic(_) | Error::Message(_) | Error::AnyError(_) => {
+            return JsNativeError::error()
+                .with_message(error.to_string())
+                .into();
+        }
+    };
+
+    let object = native.with_message(error.to_string()).into_opaque(context);
+    if let Err(e) = object.set(JsString::from("name"), JsString::from(name), false, context) {
+        return e;
+    }
+    JsError::from_opaque(object.into())
+}
@@ -142,6 +142,36 @@ mod js {
         )
     }
 
+    fn read_archive(file: JsString, context: &mut Context) -> JsResult<JsPromise> {
+        let path = PathBuf::from(file.to_std_string_escaped());
+
+        JsPromise::new(
+            |resolvers, context| match mister_fpga::archive::list(&path) {
+                Ok(entries) => {
+                    let entries = entries
+                        .iter()
+                        .map(|entry| {
+                            let path = path.join(&entry.name);
+                            JsString::from(path.to_string_lossy().as_ref()).into()
+                        })
+                        .collect::<Vec<JsValue>>();
+                    resolvers.resolve.call(
+                        &JsValue::undefined(),
+                        &[JsArray::from_iter(entries, context).into()],
+                        context,
+                    )
+                }
+                Err(e) => {
+                    let v: JsValue = js_error!("{}", e)
+                        .into_opaque(context)
+                        .expect("into_opaque");
+                    resolvers.reject.call(&JsValue::undefined(), &[v], context)
+                }
+            },
+            context,
+        )
+    }
+
     fn is_file(file: JsString, context: &mut Context) -> JsResult<JsPromise> {
         let path = PathBuf::from(file.to_std_string_escaped());
 
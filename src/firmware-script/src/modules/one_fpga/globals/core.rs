@@ -1,4 +1,5 @@
 use crate::commands::maybe_call_command;
+use crate::modules::one_fpga::error::core_error;
 use crate::modules::one_fpga::globals::classes::JsImage;
 use crate::modules::CommandMap;
 use crate::AppRef;
@@ -11,9 +12,11 @@ use boa_macros::{boa_class, Finalize, JsData, Trace};
 use enum_map::{Enum, EnumMap};
 use firmware_ui::application::panels::core_loop::run_core_loop;
 use mister_fpga::core::{AsMisterCore, MisterFpgaCore};
-use one_fpga::core::SettingId;
+use mister_fpga::uart::UartEndpoint;
+use one_fpga::core::{Error, SettingId};
 use one_fpga::{Core, OneFpgaCore};
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
@@ -29,6 +32,14 @@ enum Events {
     SaveState,
     /// Called when the core exits.
     Quit,
+    /// Fired when the core read from or wrote to a mounted file.
+    SdServiced,
+    /// Fired when the core changed its own status bits.
+    StatusBitsChanged,
+    /// Fired when the video mode of the core changed.
+    VideoModeChanged,
+    /// Fired when the core has an informational message for the user.
+    Info,
 }
 
 impl TryFromJs for Events {
@@ -80,11 +91,21 @@ impl JsCore {
 
     #[boa(getter)]
     fn settings(&self, context: &mut Context) -> JsResult<JsValue> {
-        let settings = self.core.settings().map_err(JsError::from_rust)?;
+        let settings = self.core.settings().map_err(|e| core_error(e, context))?;
         let json = serde_json::to_value(&settings).map_err(JsError::from_rust)?;
         JsValue::from_json(&json, context).map_err(JsError::from_rust)
     }
 
+    #[boa(getter)]
+    fn capabilities(&self, context: &mut Context) -> JsResult<JsValue> {
+        let capabilities = self
+            .core
+            .capabilities()
+            .map_err(|e| core_error(e, context))?;
+        let json = serde_json::to_value(&capabilities).map_err(JsError::from_rust)?;
+        JsValue::from_json(&json, context).map_err(JsError::from_rust)
+    }
+
     #[boa(getter)]
     fn status_bits(&self, context: &mut Context) -> Option<JsUint8Array> {
         if let Some(core) = self.core.as_mister_core() {
@@ -115,19 +136,57 @@ impl JsCore {
     }
 
     #[boa(getter)]
-    fn volume(&self) -> JsResult<f64> {
-        Ok(self.core.volume().map_err(JsError::from_rust)? as f64 / 255.0)
+    fn cheats(&self, context: &mut Context) -> JsResult<JsValue> {
+        let cheats: Vec<_> = self
+            .core
+            .as_mister_core()
+            .map(|core| {
+                core.cheats()
+                    .iter()
+                    .map(|cheat| {
+                        serde_json::json!({
+                            "id": cheat.setting_id(),
+                            "name": cheat.name,
+                            "enabled": cheat.enabled,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        JsValue::from_json(&serde_json::Value::Array(cheats), context).map_err(JsError::from_rust)
+    }
+
+    fn set_cheat(&mut self, id: u32, enabled: bool, context: &mut Context) -> JsResult<()> {
+        let Some(core) = self.core.as_mister_core_mut() else {
+            return Err(core_error(
+                Error::unsupported("Cheats are not supported by this core"),
+                context,
+            ));
+        };
+
+        match core.set_cheat_enabled(SettingId::from(id), enabled) {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(core_error(Error::InvalidSlot(id as usize), context)),
+            Err(e) => Err(core_error(e, context)),
+        }
+    }
+
+    #[boa(getter)]
+    fn volume(&self, context: &mut Context) -> JsResult<f64> {
+        Ok(self.core.volume().map_err(|e| core_error(e, context))? as f64 / 255.0)
     }
 
     #[boa(setter)]
     #[boa(rename = "volume")]
-    fn set_volume(&mut self, volume: f64) -> JsResult<()> {
+    fn set_volume(&mut self, volume: f64, context: &mut Context) -> JsResult<()> {
         let value = (volume * 255.0) as u8;
-        self.core.set_volume(value).map_err(JsError::from_rust)
+        self.core
+            .set_volume(value)
+            .map_err(|e| core_error(e, context))
     }
 
-    fn reset(&mut self) -> JsResult<()> {
-        self.core.reset().map_err(JsError::from_rust)
+    fn reset(&mut self, context: &mut Context) -> JsResult<()> {
+        self.core.reset().map_err(|e| core_error(e, context))
     }
 
     #[boa(rename = "loop")]
@@ -175,6 +234,29 @@ impl JsCore {
 
                 Ok(())
             },
+            |_app, _core, event| -> JsResult<()> {
+                let Ok(kind) = Events::from_str(event.name()) else {
+                    return Ok(());
+                };
+                let handlers = events.borrow()[kind].clone();
+                if handlers.is_empty() {
+                    return Ok(());
+                }
+
+                let json = serde_json::to_value(event).map_err(JsError::from_rust)?;
+                let value =
+                    JsValue::from_json(&json, *cx.borrow_mut()).map_err(JsError::from_rust)?;
+                for handler in handlers {
+                    let result =
+                        handler.call(&JsValue::undefined(), &[value.clone()], *cx.borrow_mut())?;
+
+                    if let Some(p) = result.as_promise() {
+                        p.await_blocking(*cx.borrow_mut())?;
+                    }
+                }
+
+                Ok(())
+            },
             |_app, _core| -> JsResult<()> {
                 let start = Instant::now();
                 (*cx.borrow_mut()).run_jobs()?;
@@ -251,32 +333,72 @@ impl JsCore {
     }
 
     fn screenshot(&self, context: &mut Context) -> JsResult<JsPromise> {
-        let screenshot = self.core.screenshot().map_err(JsError::from_rust)?;
+        let screenshot = self.core.screenshot().map_err(|e| core_error(e, context))?;
         let image = JsImage::from_data(JsImage::new(screenshot), context)?;
         JsPromise::resolve(image, context)
     }
 
-    fn file_select(&mut self, id: u32, path: JsString) -> JsResult<()> {
+    fn set_uart_endpoint(
+        &mut self,
+        endpoint: Option<JsString>,
+        context: &mut Context,
+    ) -> JsResult<()> {
+        let endpoint = endpoint
+            .map(|e| e.to_std_string_escaped().parse::<UartEndpoint>())
+            .transpose()
+            .map_err(|e| core_error(e, context))?;
+        let Some(core) = self.core.as_mister_core_mut() else {
+            return Err(core_error(
+                Error::unsupported("UART is not supported by this core"),
+                context,
+            ));
+        };
+
+        core.set_uart_endpoint(endpoint).map_err(|e| core_error(e, context))
+    }
+
+    fn file_select(&mut self, id: u32, path: JsString, context: &mut Context) -> JsResult<()> {
         self.core
             .file_select(SettingId::from(id), path.to_std_string_lossy())
-            .map_err(JsError::from_rust)
+            .map_err(|e| core_error(e, context))
+    }
+
+    fn insert_image(&mut self, slot: u32, path: JsString, context: &mut Context) -> JsResult<()> {
+        let path = PathBuf::from(path.to_std_string_escaped());
+        self.core
+            .insert_image(slot as usize, &path)
+            .map_err(|e| core_error(e, context))
+    }
+
+    fn eject_image(&mut self, slot: u32, context: &mut Context) -> JsResult<()> {
+        self.core
+            .eject_image(slot as usize)
+            .map_err(|e| core_error(e, context))
+    }
+
+    fn next_disk(&mut self, slot: u32, context: &mut Context) -> JsResult<Option<JsString>> {
+        let path = self
+            .core
+            .next_disk(slot as usize)
+            .map_err(|e| core_error(e, context))?;
+        Ok(path.map(|p| JsString::from(p.to_string_lossy().as_ref())))
     }
 
-    fn trigger(&mut self, id: u32) -> JsResult<()> {
+    fn trigger(&mut self, id: u32, context: &mut Context) -> JsResult<()> {
         self.core
             .trigger(SettingId::from(id))
-            .map_err(JsError::from_rust)
+            .map_err(|e| core_error(e, context))
     }
 
-    fn bool_select(&mut self, id: u32, value: bool) -> JsResult<bool> {
+    fn bool_select(&mut self, id: u32, value: bool, context: &mut Context) -> JsResult<bool> {
         self.core
             .bool_option(SettingId::from(id), value)
-            .map_err(JsError::from_rust)
+            .map_err(|e| core_error(e, context))
     }
 
-    fn int_select(&mut self, id: u32, value: u32) -> JsResult<u32> {
+    fn int_select(&mut self, id: u32, value: u32, context: &mut Context) -> JsResult<u32> {
         self.core
             .int_option(SettingId::from(id), value)
-            .map_err(JsError::from_rust)
+            .map_err(|e| core_error(e, context))
     }
 }
@@ -205,7 +205,7 @@ impl JsImage {
         }
 
         if let Err(error) = maybe_menu.send_to_framebuffer(image, (x, y)) {
-            error!(error, "Failed to send image to framebuffer");
+            error!(%error, "Failed to send image to framebuffer");
         }
     }
 }
@@ -148,6 +148,7 @@ impl From<liboptic_edid::Edid> for Edid {
 #[boa_module]
 mod js {
     use crate::AppRef;
+    use crate::modules::one_fpga::error::core_error;
     use boa_engine::interop::ContextData;
     use boa_engine::value::TryIntoJs;
     use boa_engine::{Context, JsError, JsResult, JsString, JsValue, js_error};
@@ -237,7 +238,10 @@ mod js {
         Ok(Some(Resolution::from(resolution).try_into_js(context)?))
     }
 
-    fn switch_to_core(ContextData(mut app): ContextData<AppRef>) -> JsResult<()> {
+    fn switch_to_core(
+        ContextData(mut app): ContextData<AppRef>,
+        context: &mut Context,
+    ) -> JsResult<()> {
         if let Some(mut core) = app.platform_mut().core_manager_mut().get_current_core() {
             if let Some(menu) = core.as_menu_core_mut() {
                 let mister_core = menu.inner();
@@ -252,18 +256,25 @@ mod js {
                     x_offset: 0,
                     y_offset: 0,
                 })
-                .expect("Uh...");
+                .map_err(JsError::from_rust)?;
 
-                let mut bits = *mister_core.read_status_bits();
+                let mut bits = *mister_core
+                    .read_status_bits()
+                    .map_err(|e| core_error(e, context))?;
                 bits.set_range(5..8, 0);
-                mister_core.send_status_bits(bits);
+                mister_core
+                    .send_status_bits(bits)
+                    .map_err(|e| core_error(e, context))?;
             }
         }
 
         super::switch_to_vt(0).map_err(|s| JsError::from_opaque(JsString::from(s).into()))
     }
 
-    fn switch_to_term(ContextData(mut app): ContextData<AppRef>) -> JsResult<()> {
+    fn switch_to_term(
+        ContextData(mut app): ContextData<AppRef>,
+        context: &mut Context,
+    ) -> JsResult<()> {
         app.platform_mut().core_manager_mut().hide_osd();
         if let Some(mut core) = app.platform_mut().core_manager_mut().get_current_core() {
             if let Some(menu) = core.as_menu_core_mut() {
@@ -283,11 +294,15 @@ mod js {
                     x_offset: 0,
                     y_offset: 0,
                 })
-                .expect("Uh...");
+                .map_err(JsError::from_rust)?;
 
-                let mut bits = *mister_core.read_status_bits();
+                let mut bits = *mister_core
+                    .read_status_bits()
+                    .map_err(|e| core_error(e, context))?;
                 bits.set_range(5..8, 0x160);
-                mister_core.send_status_bits(bits);
+                mister_core
+                    .send_status_bits(bits)
+                    .map_err(|e| core_error(e, context))?;
             }
         }
 
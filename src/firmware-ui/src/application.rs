@@ -145,6 +145,12 @@ impl OneFpgaApp {
         let mut triggered_commands = vec![];
 
         loop {
+            // Run the commands sent to the current core from other threads, in
+            // menus as well as while the core is running.
+            if let Some(mut core) = self.platform.core_manager_mut().get_current_core() {
+                core.process_commands();
+            }
+
             let events = self.platform.events();
 
             let mut longest_shortcut = Shortcut::default();
@@ -1,12 +1,28 @@
 use crate::application::OneFpgaApp;
 use crate::input::commands::CommandId;
 use image::DynamicImage;
+use one_fpga::core::CoreEvent;
 use one_fpga::{Core, OneFpgaCore};
 use sdl3::event::Event;
 use std::fmt::Debug;
 use std::time::Instant;
 use tracing::{debug, error, info, trace};
 
+/// Accumulates the fractional mouse motion reported by SDL, so slow movements
+/// of less than a unit per event still reach the core.
+#[derive(Debug, Default)]
+struct MouseDelta(f32);
+
+impl MouseDelta {
+    /// Add `delta` and return the whole units to send, keeping the remainder.
+    fn take(&mut self, delta: f32) -> i32 {
+        self.0 += delta;
+        let whole = self.0.trunc();
+        self.0 -= whole;
+        whole as i32
+    }
+}
+
 fn core_loop<E: Debug>(
     app: &mut OneFpgaApp,
     core: &mut OneFpgaCore,
@@ -18,16 +34,22 @@ fn core_loop<E: Debug>(
         usize,
         &[u8],
     ) -> Result<(), E>,
+    mut event_handler: impl FnMut(&mut OneFpgaApp, &mut OneFpgaCore, &CoreEvent) -> Result<(), E>,
     mut idle_handler: impl FnMut(&mut OneFpgaApp, &mut OneFpgaCore) -> Result<(), E>,
 ) -> Result<(), E> {
     let mut should_check_savestates = matches!(core.save_state(0), Ok(Some(_)));
     let mut i = 0;
+    let (mut mouse_x, mut mouse_y) = (MouseDelta::default(), MouseDelta::default());
+    let mut mouse_wheel = MouseDelta::default();
 
     // This is a special loop that forwards everything to the core,
     // except for the menu button(s).
     app.run_event_loop(move |app, state| {
         i += 1;
 
+        // Run the commands sent to the core from other threads.
+        core.process_commands();
+
         for ev in state.events() {
             match ev {
                 Event::KeyDown {
@@ -51,12 +73,33 @@ fn core_loop<E: Debug>(
                 Event::ControllerButtonUp { which, button, .. } => {
                     let _ = core.gamepad_button_up((which - 1) as usize, (*button).into());
                 }
-                // TODO: this.
-                // Event::ControllerAxisMotion {
-                //     which, axis, value, ..
-                // } => {
-                //     let _ = core.axis_motion((which - 1) as u8, *axis, *value);
-                // }
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
+                } => {
+                    let _ = core.gamepad_axis_motion((which - 1) as usize, (*axis).into(), *value);
+                }
+                Event::MouseMotion { xrel, yrel, .. } => {
+                    let (dx, dy) = (mouse_x.take(*xrel), mouse_y.take(*yrel));
+                    if dx != 0 || dy != 0 {
+                        let _ = core.mouse_move(dx, dy);
+                    }
+                }
+                Event::MouseWheel { y, .. } => {
+                    let delta = mouse_wheel.take(*y);
+                    if delta != 0 {
+                        let _ = core.mouse_wheel(delta);
+                    }
+                }
+                Event::MouseButtonDown { mouse_btn, .. } => {
+                    if let Ok(button) = (*mouse_btn).try_into() {
+                        let _ = core.mouse_button_down(button);
+                    }
+                }
+                Event::MouseButtonUp { mouse_btn, .. } => {
+                    if let Ok(button) = (*mouse_btn).try_into() {
+                        let _ = core.mouse_button_up(button);
+                    }
+                }
                 _ => {}
             }
         }
@@ -68,48 +111,53 @@ fn core_loop<E: Debug>(
             }
         }
 
-        // Check Savestates and SD Card every 5 loop. This should still be under every
-        // frame, since we approximate 600fps.
-        if should_check_savestates && i % 5 == 0 {
-            let mut should_save_savestates = false;
-
-            for i in 0.. {
-                match core.save_state(i) {
-                    Ok(Some(ss)) => {
-                        if ss.is_dirty() {
-                            should_save_savestates = true;
-                            break;
+        // Poll the core for events (savestates, SD card, ...) every 5 loop. This
+        // should still be under every frame, since we approximate 600fps.
+        if i % 5 == 0 {
+            let events = match core.poll_events() {
+                Ok(events) => events,
+                Err(err) => {
+                    error!(?err, "Error polling core events.");
+                    Vec::new()
+                }
+            };
+
+            let mut dirty_slots = Vec::new();
+            for event in events {
+                match event {
+                    CoreEvent::SaveStateDirty { slot } => dirty_slots.push(slot),
+                    CoreEvent::QuitRequested => return Some(Ok(())),
+                    event => {
+                        trace!(?event, "Core event");
+                        if let Err(e) = event_handler(app, core, &event) {
+                            return Some(Err(e));
                         }
                     }
-                    Ok(None) | Err(_) => break,
                 }
             }
 
-            if should_save_savestates {
+            if should_check_savestates && !dirty_slots.is_empty() {
                 let start = Instant::now();
                 let screenshot = core.screenshot().ok();
 
-                for i in 0.. {
-                    match core.save_state_mut(i) {
-                        Ok(Some(ss)) => {
-                            if ss.is_dirty() {
-                                let mut buffer = vec![];
-                                if let Err(err) = ss.save(&mut buffer) {
-                                    error!(?err, "Error saving savestate. Will stop trying.");
-                                    should_check_savestates = false;
-                                    break;
-                                }
-
-                                if let Err(err) =
-                                    savestate_handler(app, core, screenshot.as_ref(), i, &buffer)
-                                {
-                                    error!(?err, "Error saving savestate. Will stop trying.");
-                                    should_check_savestates = false;
-                                    break;
-                                }
-                            }
-                        }
-                        Ok(None) | Err(_) => break,
+                for slot in dirty_slots {
+                    let Ok(Some(ss)) = core.save_state_mut(slot) else {
+                        continue;
+                    };
+
+                    let mut buffer = vec![];
+                    if let Err(err) = ss.save_with_screenshot(&mut buffer, screenshot.as_ref()) {
+                        error!(?err, "Error saving savestate. Will stop trying.");
+                        should_check_savestates = false;
+                        break;
+                    }
+
+                    if let Err(err) =
+                        savestate_handler(app, core, screenshot.as_ref(), slot, &buffer)
+                    {
+                        error!(?err, "Error saving savestate. Will stop trying.");
+                        should_check_savestates = false;
+                        break;
                     }
                 }
 
@@ -117,10 +165,6 @@ fn core_loop<E: Debug>(
             }
         }
 
-        if i % 10 == 0 && core.should_quit() {
-            return Some(Ok(()));
-        }
-
         if i % 10 == 0 {
             if let Err(e) = idle_handler(app, core) {
                 return Some(Err(e));
@@ -143,6 +187,7 @@ pub fn run_core_loop<E: Debug>(
         usize,
         &[u8],
     ) -> Result<(), E>,
+    event_handler: impl FnMut(&mut OneFpgaApp, &mut OneFpgaCore, &CoreEvent) -> Result<(), E>,
     idle_handler: impl FnMut(&mut OneFpgaApp, &mut OneFpgaCore) -> Result<(), E>,
 ) -> Result<(), E> {
     debug!("Starting core loop...");
@@ -151,7 +196,14 @@ pub fn run_core_loop<E: Debug>(
     app.hide_toolbar();
     app.platform_mut().core_manager_mut().hide_osd();
 
-    let result = core_loop(app, core, shortcut_handler, savestate_handler, idle_handler);
+    let result = core_loop(
+        app,
+        core,
+        shortcut_handler,
+        savestate_handler,
+        event_handler,
+        idle_handler,
+    );
 
     debug!("Core loop ended");
     info!("Loading Main Menu");
@@ -160,3 +212,18 @@ pub fn run_core_loop<E: Debug>(
 
     result
 }
+
+#[test]
+fn mouse_delta_keeps_remainders() {
+    let mut delta = MouseDelta::default();
+    let sent = [0.25, 0.25, 0.25, 0.25, 0.75, 0.75]
+        .iter()
+        .map(|d| delta.take(*d))
+        .collect::<Vec<_>>();
+    assert_eq!(sent, [0, 0, 0, 1, 0, 1]);
+
+    // Moving back uses the remainder first.
+    assert_eq!(delta.take(-0.75), 0);
+    assert_eq!(delta.take(-0.75), -1);
+    assert_eq!(delta.take(2.5), 2);
+}
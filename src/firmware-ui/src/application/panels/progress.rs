@@ -13,6 +13,7 @@ use embedded_layout::align::horizontal;
 use embedded_layout::layout::linear::{spacing, LinearLayout};
 use embedded_layout::object_chain::Chain;
 
+#[derive(Clone, Copy)]
 struct ProgressBar {
     width: u32,
     total: u32,
@@ -108,31 +109,23 @@ pub enum ProgressBarUpdate {
     Idle,
 }
 
-pub fn progress_bar(
-    app: &mut OneFpgaApp,
-    message: &str,
-    total: u32,
-    mut update_callback: impl FnMut() -> ProgressBarUpdate,
-) -> bool {
-    let display_area = app.main_buffer().bounding_box();
-
-    let bar = ProgressBar::new(display_area.size.width * 3 / 4, total, 0);
-    let message = message.to_string();
-
-    let mut layout = LinearLayout::vertical(
+/// Draw a message and a progress bar, filling the display.
+fn draw_layout<D>(display: &mut D, message: &str, bar: ProgressBar)
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let display_area = display.bounding_box();
+    let layout = LinearLayout::vertical(
         Chain::new(
             LinearLayout::vertical(
                 Chain::new(Text::new(
-                    &message,
+                    message,
                     Point::zero(),
                     MonoTextStyle::new(&ascii::FONT_8X13_BOLD, BinaryColor::On),
                 ))
                 .append(
-                    Line::new(
-                        Point::zero(),
-                        Point::new(display_area.bounding_box().size.width as i32, 0),
-                    )
-                    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1)),
+                    Line::new(Point::zero(), Point::new(display_area.size.width as i32, 0))
+                        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1)),
                 ),
             )
             .arrange(),
@@ -143,12 +136,36 @@ pub fn progress_bar(
     .with_spacing(spacing::DistributeFill(display_area.size.height - 32))
     .arrange();
 
+    let _ = display.clear(BinaryColor::Off);
+    let _ = layout.draw(display);
+}
+
+/// Draw a progress bar once, for when the event loop cannot run (e.g. while a
+/// file is sent to a core).
+pub fn draw_progress_bar<D>(display: &mut D, message: &str, current: u32, total: u32)
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let width = display.bounding_box().size.width * 3 / 4;
+    draw_layout(display, message, ProgressBar::new(width, total, current));
+}
+
+pub fn progress_bar(
+    app: &mut OneFpgaApp,
+    message: &str,
+    total: u32,
+    mut update_callback: impl FnMut() -> ProgressBarUpdate,
+) -> bool {
+    let display_area = app.main_buffer().bounding_box();
+
+    let mut bar = ProgressBar::new(display_area.size.width * 3 / 4, total, 0);
+    let message = message.to_string();
+
     let mut last_update = std::time::Instant::now();
 
     app.run_draw_loop(|app, _state| {
         let mut buffer = app.main_buffer().color_converted();
-        let _ = buffer.clear(BinaryColor::Off);
-        let _ = layout.draw(&mut buffer);
+        draw_layout(&mut buffer, &message, bar);
 
         let now = std::time::Instant::now();
         let elapsed = now - last_update;
@@ -157,11 +174,9 @@ pub fn progress_bar(
 
             match update_callback() {
                 ProgressBarUpdate::UpdateBar(current) => {
-                    let bar = &mut layout.inner_mut().object;
                     bar.set_progress(current, None);
                 }
                 ProgressBarUpdate::UpdateBarTotal(current, total) => {
-                    let bar = &mut layout.inner_mut().object;
                     bar.set_progress(current, Some(total));
                 }
                 ProgressBarUpdate::UpdateMessage(_new_message) => {
@@ -1,16 +1,80 @@
+use std::collections::HashSet;
 use std::path::Path;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use tracing::info;
+use embedded_graphics::geometry::OriginDimensions;
+use embedded_graphics::pixelcolor::BinaryColor;
+use tracing::{info, warn};
 
+use mister_fpga::config_string::ConfigMenu;
 use mister_fpga::core::file::SdCard;
-use mister_fpga::core::{MenuCore, MisterFpgaCore};
+use mister_fpga::core::save_file::SaveFileOptions;
+use mister_fpga::core::{MenuCore, MisterFpgaCore, TransferProgress};
 use mister_fpga::fpga::MisterFpga;
-use one_fpga::core::SaveState;
+use mister_fpga::mra::Mra;
+use mister_fpga::osd::OsdDisplay;
+use one_fpga::core::{Error, Playlist, SaveState};
 use one_fpga::runner::{CoreLaunchInfo, CoreType, Slot};
 use one_fpga::{Core, OneFpgaCore};
 
+use crate::application::panels::progress::draw_progress_bar;
+use crate::data::paths;
+use crate::macguiver::buffer::DrawBuffer;
+
+/// How often the OSD progress bar is redrawn while files are sent to a core.
+const PROGRESS_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shows the progress of file transfers on the OSD while a core is launched.
+struct OsdProgress {
+    fpga: MisterFpga,
+    display: OsdDisplay,
+    buffer: DrawBuffer<BinaryColor>,
+    last_draw: Option<Instant>,
+}
+
+impl OsdProgress {
+    fn new(fpga: MisterFpga) -> Self {
+        let display = OsdDisplay::main();
+        let buffer = DrawBuffer::new(display.size());
+        Self {
+            fpga,
+            display,
+            buffer,
+            last_draw: None,
+        }
+    }
+
+    fn update(&mut self, progress: &TransferProgress) {
+        let done = progress.sent >= progress.total;
+        if let Some(last) = self.last_draw {
+            if !done && last.elapsed() < PROGRESS_REDRAW_INTERVAL {
+                return;
+            }
+        } else {
+            self.fpga.osd_enable();
+        }
+        self.last_draw = Some(Instant::now());
+
+        draw_progress_bar(
+            &mut self.buffer,
+            "Loading...",
+            progress.sent,
+            progress.total,
+        );
+        self.display.send(&mut self.fpga, &self.buffer);
+    }
+}
+
+impl Drop for OsdProgress {
+    /// Hide the OSD if the progress bar was shown, even if the launch failed.
+    fn drop(&mut self) {
+        if self.last_draw.is_some() {
+            self.fpga.osd_disable();
+        }
+    }
+}
+
 pub struct CoreManager {
     fpga: MisterFpga,
     current_core: Option<OneFpgaCore>,
@@ -33,26 +97,22 @@ impl CoreManager {
     }
 
     /// Create a core for the current FPGA configuration.
-    fn create_core(&mut self, is_menu: bool) -> Result<OneFpgaCore, String> {
+    fn create_core(&mut self, is_menu: bool) -> Result<OneFpgaCore, Error> {
         let mut core = if is_menu {
-            MenuCore::new(self.fpga.clone())
-                .map(OneFpgaCore::new)
-                .map_err(|e| format!("Could not instantiate Core: {e}"))?
+            MenuCore::new(self.fpga.clone()).map(OneFpgaCore::new)?
         } else {
-            MisterFpgaCore::new(self.fpga.clone())
-                .map(OneFpgaCore::new)
-                .map_err(|e| format!("Could not instantiate Core: {e}"))?
+            MisterFpgaCore::new(self.fpga.clone()).map(OneFpgaCore::new)?
         };
 
-        core.init().map_err(|e| e.to_string())?;
-        core.set_volume(255).map_err(|e| e.to_string())?;
-        core.set_rtc(SystemTime::now()).map_err(|e| e.to_string())?;
+        core.init()?;
+        core.set_volume(255)?;
+        core.set_rtc(SystemTime::now())?;
 
         self.current_core = Some(core.clone());
         Ok(core)
     }
 
-    pub fn load(&mut self, program: &[u8], is_menu: bool) -> Result<OneFpgaCore, String> {
+    pub fn load(&mut self, program: &[u8], is_menu: bool) -> Result<OneFpgaCore, Error> {
         let program = if &program[..6] != b"MiSTer" {
             program
         } else {
@@ -62,15 +122,13 @@ impl CoreManager {
         };
 
         self.fpga.wait_for_ready();
-        self.fpga
-            .load(program)
-            .map_err(|e| format!("Could not load program: {e:?}"))?;
+        self.fpga.load(program)?;
         self.fpga.core_reset();
 
         self.create_core(is_menu)
     }
 
-    pub fn load_menu(&mut self) -> Result<OneFpgaCore, String> {
+    pub fn load_menu(&mut self) -> Result<OneFpgaCore, Error> {
         info!("Loading menu");
         let bytes = include_bytes!("../assets/menu.rbf");
         let core = self.load(bytes, true)?;
@@ -78,39 +136,119 @@ impl CoreManager {
         Ok(core)
     }
 
-    pub fn load_core(&mut self, path: impl AsRef<Path>) -> Result<OneFpgaCore, String> {
+    pub fn load_core(&mut self, path: impl AsRef<Path>) -> Result<OneFpgaCore, Error> {
         info!("Loading core from: {:?}", path.as_ref().display());
-        let bytes = std::fs::read(path.as_ref()).map_err(|e| e.to_string())?;
+        let bytes = std::fs::read(path.as_ref())?;
         let core = self.load(&bytes, false)?;
         Ok(core)
     }
 
-    pub fn launch(&mut self, info: CoreLaunchInfo<()>) -> Result<OneFpgaCore, String> {
+    /// Launch a core, then send it its files. The progress of every file sent is
+    /// shown on the OSD and given to `on_progress`, which can return `false` to
+    /// cancel the launch.
+    pub fn launch(
+        &mut self,
+        info: CoreLaunchInfo<()>,
+        mut on_progress: impl FnMut(&TransferProgress) -> bool,
+    ) -> Result<OneFpgaCore, Error> {
+        let mut mra = None;
         let mut core = match info.core {
-            CoreType::Current => self.get_current_core().ok_or("No core running")?,
+            CoreType::Current => self
+                .get_current_core()
+                .ok_or_else(|| Error::Message("No core running".to_string()))?,
             CoreType::Menu => self.load_menu()?,
             CoreType::RbfFile(path) => self.load_core(path)?,
+            CoreType::Mra(path) => {
+                let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+                let game = Mra::from_file(&path)?;
+                let rbf = game.find_rbf(&paths::mister_arcade_cores_paths(&dir))?;
+                mra = Some((game, dir));
+                self.load_core(rbf)?
+            }
         };
 
-        let mister_core = core.as_any_mut().downcast_mut::<MisterFpgaCore>().unwrap();
+        let mut osd_progress = OsdProgress::new(self.fpga.clone());
+        let mut progress = |p: &TransferProgress| {
+            osd_progress.update(p);
+            on_progress(p)
+        };
+
+        let mister_core = core
+            .as_any_mut()
+            .downcast_mut::<MisterFpgaCore>()
+            .ok_or_else(|| Error::unsupported("Only MiSTer cores can be launched"))?;
+
+        // Restore the core options, importing them from MiSTer if they were
+        // never saved.
+        if let Some(status_file) = info.status_files.first() {
+            if let Some(path) = info.status_files.iter().find(|p| p.exists()) {
+                mister_core.load_status_file(path)?;
+            } else if mister_core.import_mister_config(paths::mister_config_path())? {
+                mister_core.save_status_file(status_file)?;
+            }
+            mister_core.set_status_file(Some(status_file.clone()));
+        }
+
+        // Send the ROMs of arcade games, and set their DIP switches before they
+        // are restored.
+        if let Some((game, dir)) = &mra {
+            mister_core.load_mra(game, paths::mister_mame_paths(dir), &mut progress)?;
+        }
+
+        if let Some(dip_file) = &info.dip_file {
+            if dip_file.exists() {
+                mister_core.load_dip_file(dip_file)?;
+            }
+            mister_core.set_dip_file(Some(dip_file.clone()));
+        }
+
+        // Resend the files remembered by the core, from its own file by default.
+        let remember_file = info
+            .remember_file
+            .clone()
+            .unwrap_or_else(|| paths::remember_file_path(&mister_core.config().name));
+        // Remembered files are optional too (e.g. a palette that was deleted).
+        if let Err(error) = mister_core.load_remember_file(&remember_file) {
+            warn!(%error, ?remember_file, "Could not load remembered files");
+        }
+
+        for bios in &info.bios {
+            mister_core.send_bios_with_progress(bios.clone(), &mut progress)?;
+        }
 
         if let Some(rom) = &info.rom {
-            mister_core
-                .send_rom(rom.clone())
-                .map_err(|e| e.to_string())?;
+            mister_core.send_rom_with_progress(rom.clone(), &mut progress)?;
+
+            // Cheats are optional, don't fail the launch if they can't be loaded.
+            if let Err(error) = mister_core.load_cheats(paths::mister_cheats_path()) {
+                warn!(%error, "Could not load cheats");
+            }
         }
 
         if !info.files.is_empty() {
-            let should_sav = mister_core
+            // Only slots of files with save support mount save files, the
+            // others mount disk images, CDs or directories.
+            let save_slots = mister_core
                 .menu_options()
                 .iter()
-                .filter_map(|x| x.as_load_file_info())
-                .any(|i| i.save_support);
+                .filter_map(ConfigMenu::as_load_file)
+                .filter_map(ConfigMenu::as_load_file_info)
+                .filter(|i| i.save_support)
+                .map(|i| i.index as usize)
+                .collect::<HashSet<_>>();
 
-            if should_sav {
-                for (idx, f) in info.files {
-                    if let Slot::File(ref path) = f {
-                        mister_core.mount(SdCard::from_path(path)?, idx as u8)?;
+            for (idx, f) in info.files {
+                if let Slot::File(ref path) = f {
+                    // Playlists of multi-disk games insert their first disk.
+                    if Playlist::is_playlist(path) {
+                        mister_core.insert_image(idx, path)?;
+                    } else {
+                        let card = if save_slots.contains(&idx) {
+                            SdCard::from_save_file(path, SaveFileOptions::default())?
+                        } else {
+                            SdCard::from_path(path)?
+                        };
+                        mister_core.mount(card, idx as u8)?;
                     }
                 }
             }
@@ -121,11 +259,20 @@ impl CoreManager {
         }
 
         // Load all savestates.
+        if let Some(slots) = info.save_state_slots {
+            mister_core.set_save_state_slots(slots)?;
+        }
         if let Some(savestate_manager) = mister_core.save_states_mut() {
             for (slot, state) in savestate_manager.slots_mut().iter_mut().enumerate() {
                 if let Some(Slot::File(path)) = info.save_state.get(slot) {
-                    let mut f = std::fs::File::open(path).map_err(|e| e.to_string())?;
-                    state.load(&mut f).map_err(|e| e.to_string())?;
+                    // A state from another version of the core shouldn't
+                    // prevent the game from starting.
+                    let result = std::fs::File::open(path)
+                        .map_err(Error::from)
+                        .and_then(|mut f| state.load(&mut f));
+                    if let Err(error) = result {
+                        warn!(%error, ?path, "Skipping save state that could not be loaded");
+                    }
                 }
             }
         }
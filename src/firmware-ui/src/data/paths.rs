@@ -1,5 +1,5 @@
 use cfg_if::cfg_if;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn config_root_path() -> PathBuf {
     // On DE10-Nano, the configurations are in /media/fat/1fpga
@@ -17,6 +17,33 @@ pub fn config_root_path() -> PathBuf {
     p
 }
 
+/// The directory where MiSTer saves its per-core configurations.
+pub fn mister_config_path() -> PathBuf {
+    PathBuf::from("/media/fat/config")
+}
+
+/// The directory containing the cheat archives, per core.
+pub fn mister_cheats_path() -> PathBuf {
+    PathBuf::from("/media/fat/cheats")
+}
+
+/// The directories to look for the core of an MRA file in, most specific first.
+pub fn mister_arcade_cores_paths(mra_dir: &Path) -> Vec<PathBuf> {
+    vec![
+        mra_dir.join("cores"),
+        PathBuf::from("/media/fat/_Arcade/cores"),
+    ]
+}
+
+/// The directories to look for the ROM archives (MAME zip files) of an MRA file in.
+pub fn mister_mame_paths(mra_dir: &Path) -> Vec<PathBuf> {
+    vec![
+        mra_dir.to_path_buf(),
+        PathBuf::from("/media/fat/games/mame"),
+        PathBuf::from("/media/fat/_Arcade/mame"),
+    ]
+}
+
 pub fn screenshots_root() -> PathBuf {
     let p = config_root_path().join("screenshots");
     if !p.exists() {
@@ -49,6 +76,12 @@ pub fn sav_root_path() -> PathBuf {
     p
 }
 
+/// The file the files remembered by the `FC` entries of a core are saved to,
+/// when the launcher does not give one.
+pub fn remember_file_path(core_name: &str) -> PathBuf {
+    core_root_path().join(format!("{core_name}.files"))
+}
+
 pub fn savestates_path(core_name: &str) -> PathBuf {
     savestates_root_path().join(core_name)
 }
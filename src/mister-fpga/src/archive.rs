@@ -0,0 +1,186 @@
+//! Files inside zip and 7z archives, so ROMs can be listed and sent to the core
+//! without being extracted first. Paths to files inside archives are split with
+//! [`one_fpga::core::rom::split_archive_path`].
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Serialize;
+
+use one_fpga::core::Error;
+
+/// The kind of archive, from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    SevenZip,
+}
+
+impl ArchiveKind {
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?;
+        if ext.eq_ignore_ascii_case("zip") {
+            Some(Self::Zip)
+        } else if ext.eq_ignore_ascii_case("7z") {
+            Some(Self::SevenZip)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file in an archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArchiveEntry {
+    /// The path of the file in the archive, separated by `/`.
+    pub name: String,
+    pub size: u64,
+}
+
+pub(crate) fn zip_error(error: zip::result::ZipError) -> Error {
+    match error {
+        zip::result::ZipError::Io(e) => Error::IoError(e),
+        e => Error::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    }
+}
+
+fn sevenz_error(error: sevenz_rust::Error) -> Error {
+    match error {
+        sevenz_rust::Error::Io(e, _) | sevenz_rust::Error::FileOpen(e, _) => Error::IoError(e),
+        e => Error::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    }
+}
+
+fn kind(path: &Path) -> Result<ArchiveKind, Error> {
+    ArchiveKind::from_path(path)
+        .ok_or_else(|| Error::unsupported(format!("{path:?} is not a supported archive")))
+}
+
+/// List the files of an archive. Directories are skipped.
+pub fn list(path: impl AsRef<Path>) -> Result<Vec<ArchiveEntry>, Error> {
+    let path = path.as_ref();
+    match kind(path)? {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(zip_error)?;
+            let mut entries = Vec::with_capacity(archive.len());
+            for i in 0..archive.len() {
+                let file = archive.by_index_raw(i).map_err(zip_error)?;
+                if file.is_file() {
+                    entries.push(ArchiveEntry {
+                        name: file.name().to_string(),
+                        size: file.size(),
+                    });
+                }
+            }
+            Ok(entries)
+        }
+        ArchiveKind::SevenZip => {
+            let reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+                .map_err(sevenz_error)?;
+            Ok(reader
+                .archive()
+                .files
+                .iter()
+                .filter(|f| !f.is_directory && !f.is_anti_item)
+                .map(|f| ArchiveEntry {
+                    name: f.name().replace('\\', "/"),
+                    size: f.size,
+                })
+                .collect())
+        }
+    }
+}
+
+/// Open a file of an archive, and call `f` with a reader of its content and its
+/// size. The content is decompressed while it is read.
+pub fn with_entry<T>(
+    path: impl AsRef<Path>,
+    name: &str,
+    f: impl FnOnce(&mut dyn Read, u64) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let path = path.as_ref();
+    let not_found = || {
+        Error::IoError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{name:?} not found in {path:?}"),
+        ))
+    };
+
+    match kind(path)? {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(zip_error)?;
+            let mut file = match archive.by_name(name) {
+                Ok(file) => file,
+                Err(zip::result::ZipError::FileNotFound) => return Err(not_found()),
+                Err(e) => return Err(zip_error(e)),
+            };
+            let size = file.size();
+            f(&mut file, size)
+        }
+        ArchiveKind::SevenZip => {
+            let mut reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+                .map_err(sevenz_error)?;
+
+            // Files of solid archives are decompressed in order, so the files
+            // before the one we want are read and discarded.
+            let mut f = Some(f);
+            let mut result = None;
+            reader
+                .for_each_entries(|entry, reader| {
+                    if entry.name().replace('\\', "/") != name {
+                        std::io::copy(reader, &mut std::io::sink())?;
+                        return Ok(true);
+                    }
+                    if let Some(f) = f.take() {
+                        result = Some(f(reader, entry.size));
+                    }
+                    Ok(false)
+                })
+                .map_err(sevenz_error)?;
+            result.unwrap_or_else(|| Err(not_found()))
+        }
+    }
+}
+
+#[test]
+fn zip_archive() {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let path = std::env::temp_dir().join(format!("mister-fpga-archive-{}.zip", std::process::id()));
+    let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+    zip.add_directory("USA", SimpleFileOptions::default())
+        .unwrap();
+    zip.start_file("USA/Game.sfc", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(b"ROM DATA").unwrap();
+    zip.finish().unwrap();
+
+    assert_eq!(
+        list(&path).unwrap(),
+        [ArchiveEntry {
+            name: "USA/Game.sfc".to_string(),
+            size: 8,
+        }]
+    );
+    let content = with_entry(&path, "USA/Game.sfc", |reader, size| {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        Ok((content, size))
+    })
+    .unwrap();
+    assert_eq!(content, (b"ROM DATA".to_vec(), 8));
+    assert!(matches!(
+        with_entry(&path, "Game.sfc", |_, _| Ok(())),
+        Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound
+    ));
+
+    let member = path.join("USA").join("Game.sfc");
+    assert_eq!(
+        one_fpga::core::rom::split_archive_path(&member),
+        Some((path.clone(), "USA/Game.sfc".to_string()))
+    );
+    assert_eq!(one_fpga::core::rom::split_archive_path(&path), None);
+
+    std::fs::remove_file(path).unwrap();
+}
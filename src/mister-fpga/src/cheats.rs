@@ -0,0 +1,193 @@
+//! Cheats for cores that support them (with a `C` entry in their config string).
+//! Cheats are distributed as zip archives of cheat files, one archive per game,
+//! named after the game and the CRC32 of its ROM (e.g. `Game (USA) [1234abcd].zip`).
+//! Each file in the archive is a cheat, containing codes of 16 bytes each. The
+//! codes of enabled cheats are sent to the core on a special file index.
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tracing::warn;
+
+use one_fpga::core::{CoreSettingItem, Error, SettingId};
+
+use crate::archive::zip_error;
+
+/// The file index used to send the cheat codes to the core.
+pub const CHEATS_INDEX: u8 = 255;
+
+/// The size of a single cheat code, in bytes.
+pub const CHEAT_CODE_SIZE: usize = 16;
+
+/// A single cheat, which can contain multiple codes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Cheat {
+    pub name: String,
+    pub enabled: bool,
+
+    #[serde(skip)]
+    codes: Vec<u8>,
+}
+
+impl Cheat {
+    pub fn new(name: impl Into<String>, codes: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            enabled: false,
+            codes,
+        }
+    }
+
+    pub fn setting_id(&self) -> SettingId {
+        SettingId::from_label(&format!("cheat:{}", self.name))
+    }
+
+    pub fn codes(&self) -> &[u8] {
+        &self.codes
+    }
+}
+
+/// The cheats available for a game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new(mut cheats: Vec<Cheat>) -> Self {
+        cheats.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { cheats }
+    }
+
+    /// Load all cheats from a zip archive. Files with invalid codes are skipped.
+    pub fn from_zip(reader: impl Read + Seek) -> Result<Self, Error> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+        let mut cheats = Vec::new();
+
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).map_err(zip_error)?;
+            if !file.is_file() {
+                continue;
+            }
+
+            let Some(name) = file
+                .enclosed_name()
+                .and_then(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            else {
+                continue;
+            };
+
+            let mut codes = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut codes)?;
+            if codes.is_empty() || codes.len() % CHEAT_CODE_SIZE != 0 {
+                warn!(?name, size = codes.len(), "Invalid cheat file, skipping");
+                continue;
+            }
+
+            cheats.push(Cheat::new(name, codes));
+        }
+
+        Ok(Self::new(cheats))
+    }
+
+    /// Find the cheat archive for a ROM in a directory, by its CRC32.
+    pub fn find_archive(dir: impl AsRef<Path>, crc: u32) -> Result<Option<PathBuf>, Error> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+
+        let needle = format!("[{crc:08x}]");
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_lowercase()) else {
+                continue;
+            };
+            if name.ends_with(".zip") && name.contains(&needle) {
+                return Ok(Some(path));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter()
+    }
+
+    /// Enable or disable the cheat with the setting `id`. Returns `None` if there
+    /// is no such cheat.
+    pub fn set_enabled(&mut self, id: SettingId, enabled: bool) -> Option<bool> {
+        let cheat = self.cheats.iter_mut().find(|c| c.setting_id() == id)?;
+        cheat.enabled = enabled;
+        Some(enabled)
+    }
+
+    /// Return the codes of all enabled cheats, concatenated.
+    pub fn codes(&self) -> Vec<u8> {
+        self.cheats
+            .iter()
+            .filter(|c| c.enabled)
+            .flat_map(|c| c.codes.iter().copied())
+            .collect()
+    }
+
+    /// Return a page containing all cheats as options, or `None` if there are no
+    /// cheats.
+    pub fn as_core_menu_item(&self, label: &str) -> Option<CoreSettingItem> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let items = self
+            .cheats
+            .iter()
+            .map(|c| CoreSettingItem::bool_option(c.setting_id(), &c.name, Some(c.enabled)))
+            .collect();
+        Some(CoreSettingItem::page("cheats", label, label, items))
+    }
+}
+
+#[test]
+fn cheats_from_zip() {
+    use std::io::{Cursor, Write};
+
+    let mut buffer = Cursor::new(Vec::new());
+    {
+        let mut zip = zip::ZipWriter::new(&mut buffer);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("Infinite Lives.gg", options).unwrap();
+        zip.write_all(&[1; 16]).unwrap();
+        zip.start_file("Invalid.gg", options).unwrap();
+        zip.write_all(&[2; 15]).unwrap();
+        zip.start_file("All Items.gg", options).unwrap();
+        zip.write_all(&[3; 32]).unwrap();
+        zip.finish().unwrap();
+    }
+    buffer.set_position(0);
+
+    let mut cheats = Cheats::from_zip(buffer).unwrap();
+    let names: Vec<_> = cheats.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["All Items", "Infinite Lives"]);
+    assert!(cheats.codes().is_empty());
+
+    let id = SettingId::from_label("cheat:Infinite Lives");
+    assert_eq!(cheats.set_enabled(id, true), Some(true));
+    assert_eq!(cheats.codes(), vec![1; 16]);
+    assert_eq!(
+        cheats.set_enabled(SettingId::from_label("cheat:Unknown"), true),
+        None
+    );
+
+    let page = cheats.as_core_menu_item("Cheats").unwrap();
+    assert_eq!(page.items().unwrap().len(), 2);
+    assert!(matches!(
+        &page.items().unwrap()[1],
+        CoreSettingItem::BoolOption { value: true, .. }
+    ));
+}
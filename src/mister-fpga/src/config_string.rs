@@ -16,10 +16,13 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use tracing::{debug, warn};
 
-use one_fpga::core::{CoreSettingItem, CoreSettings, SettingId};
+use one_fpga::core::{CoreSettingItem, CoreSettings, Error, FileSlot, SettingId};
 pub use types::*;
 
+use crate::cheats::Cheats;
+use crate::dip::DipSwitches;
 use crate::fpga::user_io;
+use crate::remember::RememberedFiles;
 use crate::types::StatusBitMap;
 
 pub mod midi;
@@ -27,10 +30,12 @@ pub mod settings;
 pub mod uart;
 
 mod parser;
+mod render;
 
 mod types;
 
-static LABELED_SPEED_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\d*)(\([^)]*\))?").unwrap());
+static LABELED_SPEED_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(\d*)(?:\(([^)]*)\))?$").unwrap());
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct FileExtension(pub [u8; 3]);
@@ -74,7 +79,7 @@ impl FileExtension {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LoadFileInfo {
     /// Core supports save files, load a file, and mount a save for reading or writing
     pub save_support: bool,
@@ -106,12 +111,13 @@ impl LoadFileInfo {
 }
 
 /// A component of a Core config string.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ConfigMenu {
     /// Empty lines, potentially with text.
     Empty(Option<String>),
 
-    /// Cheat menu option.
+    /// Cheat menu option. The cheats themselves are loaded from a cheat archive
+    /// matching the ROM, and shown as a page at this position.
     Cheat(Option<String>),
 
     /// Disable the option if the menu mask is set.
@@ -126,7 +132,8 @@ pub enum ConfigMenu {
     /// Hide the option if the menu mask is NOT set.
     HideUnless(u32, Box<ConfigMenu>),
 
-    /// DIP switch menu option.
+    /// DIP switch menu option. The switches themselves are defined outside of
+    /// the config string (in the MRA file), and shown at this position.
     Dip,
 
     /// Load file menu option.
@@ -151,6 +158,10 @@ pub enum ConfigMenu {
     ///   are the range of bits that will be set in the status register.
     /// - `{Name}` is what is shown to describe the option.
     /// - {Options...} - a list of comma separated options.
+    ///
+    /// `o{Index1}[{Index2}]` is the same, but for bits 32-63. Bits can also be specified
+    /// in brackets, as `O[{Index}]` or `O[{Last}:{First}]`, with indices from 0 to 127
+    /// (regardless of the prefix case).
     Option {
         bits: Range<u8>,
         label: String,
@@ -161,6 +172,7 @@ pub enum ConfigMenu {
     /// specified `{Index}` bit in status register. A perfect example of this is for a reset
     /// button. `{Name}` is the text that describes the button function.
     /// `R{Index},{Name}` is the same but should close the OSD.
+    /// Like options, `t` and `r` address bits 32-63, and `T[{Index}]` any bit up to 127.
     Trigger {
         close_osd: bool,
         index: u8,
@@ -239,9 +251,16 @@ impl ConfigMenu {
         }
     }
 
-    pub fn as_core_menu_item(&self, status: &StatusBitMap) -> Vec<CoreSettingItem> {
+    pub fn as_core_menu_item(
+        &self,
+        status: &StatusBitMap,
+        dips: &DipSwitches,
+        cheats: &Cheats,
+        remembered: &RememberedFiles,
+    ) -> Vec<CoreSettingItem> {
         match self {
-            ConfigMenu::LoadFile(info) | ConfigMenu::LoadFileAndRemember(info) => {
+            ConfigMenu::LoadFileAndRemember(info) => remembered.as_core_menu_items(info),
+            ConfigMenu::LoadFile(info) => {
                 vec![CoreSettingItem::file_select(
                     info.setting_id(),
                     info.label
@@ -272,28 +291,28 @@ impl ConfigMenu {
             ConfigMenu::Page { label, .. } => {
                 vec![CoreSettingItem::page(label, label, label, Vec::new())]
             }
-            ConfigMenu::PageItem(_, sub) => sub.as_core_menu_item(status),
+            ConfigMenu::PageItem(_, sub) => sub.as_core_menu_item(status, dips, cheats, remembered),
             ConfigMenu::HideIf(mask, sub) => {
                 if status.get(*mask as usize) {
                     vec![]
                 } else {
-                    sub.as_core_menu_item(status)
+                    sub.as_core_menu_item(status, dips, cheats, remembered)
                 }
             }
             ConfigMenu::HideUnless(mask, sub) => {
                 if !status.get(*mask as usize) {
                     vec![]
                 } else {
-                    sub.as_core_menu_item(status)
+                    sub.as_core_menu_item(status, dips, cheats, remembered)
                 }
             }
             ConfigMenu::DisableIf(mask, sub) => sub
-                .as_core_menu_item(status)
+                .as_core_menu_item(status, dips, cheats, remembered)
                 .into_iter()
                 .map(|item| item.with_disabled(status.get(*mask as usize)))
                 .collect(),
             ConfigMenu::DisableUnless(mask, sub) => sub
-                .as_core_menu_item(status)
+                .as_core_menu_item(status, dips, cheats, remembered)
                 .into_iter()
                 .map(|item| item.with_disabled(!status.get(*mask as usize)))
                 .collect(),
@@ -304,6 +323,11 @@ impl ConfigMenu {
                     vec![CoreSettingItem::Separator]
                 }
             }
+            ConfigMenu::Dip => dips.as_core_menu_items(),
+            ConfigMenu::Cheat(label) => cheats
+                .as_core_menu_item(label.as_deref().unwrap_or("Cheats"))
+                .into_iter()
+                .collect(),
             ConfigMenu::Info(_) => vec![],
             ConfigMenu::Version(v) => {
                 vec![CoreSettingItem::label(false, &format!("Version: {v}"))]
@@ -312,6 +336,30 @@ impl ConfigMenu {
         }
     }
 
+    pub fn as_mount_sd_card(&self) -> Option<&Self> {
+        match self {
+            ConfigMenu::MountSdCard { .. } => Some(self),
+            ConfigMenu::DisableIf(_, sub)
+            | ConfigMenu::DisableUnless(_, sub)
+            | ConfigMenu::HideIf(_, sub)
+            | ConfigMenu::HideUnless(_, sub)
+            | ConfigMenu::PageItem(_, sub) => sub.as_mount_sd_card(),
+            _ => None,
+        }
+    }
+
+    pub fn as_cheat(&self) -> Option<&Self> {
+        match self {
+            ConfigMenu::Cheat(_) => Some(self),
+            ConfigMenu::DisableIf(_, sub)
+            | ConfigMenu::DisableUnless(_, sub)
+            | ConfigMenu::HideIf(_, sub)
+            | ConfigMenu::HideUnless(_, sub)
+            | ConfigMenu::PageItem(_, sub) => sub.as_cheat(),
+            _ => None,
+        }
+    }
+
     pub fn as_load_file(&self) -> Option<&Self> {
         match self {
             ConfigMenu::LoadFile(_) | ConfigMenu::LoadFileAndRemember(_) => Some(self),
@@ -331,6 +379,20 @@ impl ConfigMenu {
         }
     }
 
+    /// Return the load file information of an `FC` entry, whose file should be
+    /// remembered, including entries in pages or with conditions.
+    pub fn as_remembered_file_info(&self) -> Option<&LoadFileInfo> {
+        match self {
+            ConfigMenu::LoadFileAndRemember(info) => Some(info),
+            ConfigMenu::DisableIf(_, sub)
+            | ConfigMenu::DisableUnless(_, sub)
+            | ConfigMenu::HideIf(_, sub)
+            | ConfigMenu::HideUnless(_, sub)
+            | ConfigMenu::PageItem(_, sub) => sub.as_remembered_file_info(),
+            _ => None,
+        }
+    }
+
     pub fn setting_id(&self) -> Option<SettingId> {
         match self {
             ConfigMenu::Page { label, .. } => Some(SettingId::from_label(label)),
@@ -351,8 +413,7 @@ impl ConfigMenu {
             | ConfigMenu::DisableUnless(_, sub)
             | ConfigMenu::HideIf(_, sub)
             | ConfigMenu::HideUnless(_, sub) => sub.label(),
-            // TODO: add those.
-            // ConfigMenu::Cheat(name) => name.as_ref().map(|x| x.as_str()),
+            ConfigMenu::Cheat(name) => name.as_deref(),
             ConfigMenu::LoadFileAndRemember(info) | ConfigMenu::LoadFile(info) => {
                 info.label.as_deref()
             }
@@ -375,7 +436,9 @@ impl ConfigMenu {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A parsed config string. Use [`std::fmt::Display`] to render it back to a
+/// config string.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Config {
     /// The name of the core.
     pub name: String,
@@ -390,19 +453,35 @@ pub struct Config {
 impl Config {
     /// Create a new config from the FPGA.
     /// This is disabled in Test as this module is still included in the test build.
-    pub fn from_fpga(fpga: &mut crate::fpga::MisterFpga) -> Result<Self, String> {
+    pub fn from_fpga(fpga: &mut crate::fpga::MisterFpga) -> Result<Self, Error> {
         let mut cfg_string = String::with_capacity(1024);
         fpga.spi_mut()
             .execute(user_io::UserIoGetString(&mut cfg_string))?;
         debug!(?cfg_string, "Config string from FPGA");
 
-        Self::from_str(&cfg_string)
+        Self::from_str(&cfg_string).map_err(Error::InvalidConfig)
     }
 
     pub fn settings(&self) -> &settings::Settings {
         &self.settings
     }
 
+    /// Return the mask of status bits used by options, excluding triggers
+    /// and the reserved reset bit. These are the bits that can be saved
+    /// and restored between runs of a core.
+    pub fn option_bit_map_mask(&self) -> StatusBitMap {
+        let mut arr = self.status_bit_map_mask();
+        arr.set(0, false);
+
+        for item in self.menu.iter() {
+            if let Some(ConfigMenu::Trigger { index, .. }) = item.as_trigger() {
+                arr.set(*index as usize, false);
+            }
+        }
+
+        arr
+    }
+
     pub fn status_bit_map_mask(&self) -> StatusBitMap {
         let mut arr = StatusBitMap::new();
         // First bit is always 1 for soft reset (reserved).
@@ -421,20 +500,28 @@ impl Config {
         arr
     }
 
+    /// Whether the core supports cheats.
+    pub fn supports_cheats(&self) -> bool {
+        self.menu.iter().any(|item| item.as_cheat().is_some())
+    }
+
     pub fn load_info(&self, path: impl AsRef<Path>) -> Result<Option<LoadFileInfo>, String> {
-        let path_ext = match path.as_ref().extension() {
-            Some(ext) => ext.to_string_lossy(),
-            None => return Err("No extension".to_string()),
-        };
-        debug!("Looking for info for extension '{}'", path_ext);
+        match path.as_ref().extension() {
+            Some(ext) => self.load_info_for_extension(&ext.to_string_lossy()),
+            None => Err("No extension".to_string()),
+        }
+    }
+
+    /// Find the load file information for a file extension (without the leading dot).
+    pub fn load_info_for_extension(&self, ext: &str) -> Result<Option<LoadFileInfo>, String> {
+        if ext.is_empty() {
+            return Err("No extension".to_string());
+        }
+        debug!("Looking for info for extension '{}'", ext);
 
         for item in self.menu.iter() {
             if let ConfigMenu::LoadFile(ref info) = item {
-                if info
-                    .extensions
-                    .iter()
-                    .any(|ext| ext.eq_ignore_ascii_case(&path_ext))
-                {
+                if info.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)) {
                     return Ok(Some(info.as_ref().clone()));
                 }
             }
@@ -442,6 +529,86 @@ impl Config {
         Ok(None)
     }
 
+    /// Find the load file information for a BIOS. Files named `boot.rom` or
+    /// `bootN.rom` (N from 0 to 3) go to the MiSTer boot ROM indices (`N << 6`).
+    /// Other files go to the index 0 (`F0`) entry matching their extension, or
+    /// to the boot ROM index if the core does not declare one.
+    pub fn bios_load_info(&self, path: Option<&Path>) -> LoadFileInfo {
+        let file_name = path
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let ext = path
+            .and_then(|p| p.extension())
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let index = match file_name.as_str() {
+            "boot.rom" | "boot0.rom" => 0,
+            "boot1.rom" => 0x40,
+            "boot2.rom" => 0x80,
+            "boot3.rom" => 0xC0,
+            _ => 0,
+        };
+
+        let candidates = self
+            .menu
+            .iter()
+            .filter_map(ConfigMenu::as_load_file)
+            .filter_map(ConfigMenu::as_load_file_info)
+            .filter(|info| info.index == index)
+            .collect::<Vec<_>>();
+
+        candidates
+            .iter()
+            .find(|info| info.extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext)))
+            .or_else(|| candidates.first())
+            .map(|info| (*info).clone())
+            .unwrap_or(LoadFileInfo {
+                save_support: false,
+                index,
+                extensions: vec![],
+                label: None,
+                address: None,
+            })
+    }
+
+    /// The slots in which the core can mount files (`S` entries).
+    pub fn mount_slots(&self) -> Vec<FileSlot> {
+        self.menu
+            .iter()
+            .filter_map(ConfigMenu::as_mount_sd_card)
+            .filter_map(|item| match item {
+                ConfigMenu::MountSdCard {
+                    slot,
+                    extensions,
+                    label,
+                } => Some(FileSlot {
+                    index: *slot as usize,
+                    label: label.clone(),
+                    extensions: extensions.iter().map(|e| e.to_string()).collect(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The slots in which the core can load a BIOS (`F0` entries). See
+    /// [`Self::bios_load_info`].
+    pub fn bios_slots(&self) -> Vec<FileSlot> {
+        self.menu
+            .iter()
+            .filter_map(ConfigMenu::as_load_file)
+            .filter_map(ConfigMenu::as_load_file_info)
+            .filter(|info| info.index == 0)
+            .map(|info| FileSlot {
+                index: info.index as usize,
+                label: info.label.clone(),
+                extensions: info.extensions.iter().map(|e| e.to_string()).collect(),
+            })
+            .collect()
+    }
+
     pub fn snes_default_button_list(&self) -> Option<&Vec<String>> {
         for item in self.menu.iter() {
             if let ConfigMenu::SnesButtonDefaultList { ref buttons } = item {
@@ -451,6 +618,25 @@ impl Config {
         None
     }
 
+    /// The list of joystick buttons used by the core, in the order of their bits
+    /// (after the 4 directions).
+    pub fn joystick_button_list(&self) -> Option<&Vec<String>> {
+        for item in self.menu.iter() {
+            if let ConfigMenu::JoystickButtons { ref buttons, .. } = item {
+                return Some(buttons);
+            }
+        }
+        None
+    }
+
+    /// Whether the keyboard is locked to joystick emulation (`J1`), which cores
+    /// without a keyboard (consoles and arcades) do.
+    pub fn keyboard_locked(&self) -> bool {
+        self.menu
+            .iter()
+            .any(|item| matches!(item, ConfigMenu::JoystickButtons { keyboard: true, .. }))
+    }
+
     pub fn version(&self) -> Option<&str> {
         for item in self.menu.iter() {
             if let ConfigMenu::Version(ref version) = item {
@@ -460,9 +646,15 @@ impl Config {
         None
     }
 
-    pub fn as_core_settings(&self, bits: &StatusBitMap) -> CoreSettings {
+    pub fn as_core_settings(
+        &self,
+        bits: &StatusBitMap,
+        dips: &DipSwitches,
+        cheats: &Cheats,
+        remembered: &RememberedFiles,
+    ) -> CoreSettings {
         let it = self.menu.iter().flat_map(|item| {
-            item.as_core_menu_item(bits)
+            item.as_core_menu_item(bits, dips, cheats, remembered)
                 .into_iter()
                 .map(move |i| (item, i))
         });
@@ -600,7 +792,31 @@ fn config_string_nes() {
 #[test]
 fn config_string_nes_menu() {
     let config = Config::from_str(CONFIG_STRING_NES).unwrap();
-    config.as_core_settings(&StatusBitMap::new());
+    config.as_core_settings(
+        &StatusBitMap::new(),
+        &DipSwitches::default(),
+        &Cheats::default(),
+        &RememberedFiles::default(),
+    );
+}
+
+#[test]
+fn config_string_nes_cheats() {
+    let config = Config::from_str(CONFIG_STRING_NES).unwrap();
+    assert!(config.supports_cheats());
+
+    let config = Config::from_str("Test;;O1,Option,Off,On;V,v1").unwrap();
+    assert!(!config.supports_cheats());
+}
+
+#[test]
+fn config_string_keyboard_locked() {
+    let config = Config::from_str(CONFIG_STRING_NES).unwrap();
+    assert!(config.keyboard_locked());
+
+    let config = Config::from_str("Test;;J,Fire;V,v1").unwrap();
+    assert!(!config.keyboard_locked());
+    assert!(config.joystick_button_list().is_some());
 }
 
 #[test]
@@ -661,6 +877,26 @@ fn config_string_chess() {
     );
 }
 
+#[test]
+fn config_string_option_mask() {
+    let config =
+        Config::from_str("Test;;O12,Scale,1x,2x,3x;T3,Cheat;R0,Reset;OD,Sound,Off,On;V,v1")
+            .unwrap();
+
+    assert_eq!(
+        config.status_bit_map_mask(),
+        StatusBitMap::new()
+            .with(0)
+            .with_range(1..3, 0b11)
+            .with(3)
+            .with(13)
+    );
+    assert_eq!(
+        config.option_bit_map_mask(),
+        StatusBitMap::new().with_range(1..3, 0b11).with(13)
+    );
+}
+
 #[test]
 fn config_string_ao486() {
     // From https://github.com/MiSTer-devel/ao486_MiSTer/blob/09b29b2/ao486.sv#L199
@@ -817,3 +1053,81 @@ fn config_string_gba() {
     );
     assert!(config.is_ok(), "{:?}", config);
 }
+
+#[test]
+fn config_string_bios_load_info() {
+    let config = Config::from_str("TEST;;F0,ROMBIN,Load BIOS,30000000;F1,BIN,Load Game;").unwrap();
+
+    let info = config.bios_load_info(Some(Path::new("/bios/bios.bin")));
+    assert_eq!(info.index, 0);
+    assert!(info.address.is_some());
+
+    let info = config.bios_load_info(Some(Path::new("/bios/boot2.rom")));
+    assert_eq!(info.index, 0x80);
+    assert!(info.address.is_none());
+
+    let info = config.bios_load_info(None);
+    assert_eq!(info.index, 0);
+}
+
+#[test]
+fn config_string_slots() {
+    let config = Config::from_str(
+        "TEST;;F0,ROMBIN,Load BIOS;F1,BIN,Load Game;S0,IMGVHD,Mount Disk;P1,Page;P1S1,ISO,CD;",
+    )
+    .unwrap();
+
+    let bios = config.bios_slots();
+    assert_eq!(bios.len(), 1);
+    assert_eq!(bios[0].extensions, vec!["ROM", "BIN"]);
+
+    let mounts = config.mount_slots();
+    assert_eq!(
+        mounts.iter().map(|s| s.index).collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+    assert_eq!(mounts[0].label.as_deref(), Some("Mount Disk"));
+}
+
+#[test]
+fn config_string_extended_bits() {
+    let config = Config::from_str(
+        "TEST;;O[70:68],Dithering,On,Off,Low;o[40],Low,Off,On;oUV,Upper,1,2,3,4;\
+        O[127],Last,Off,On;t[100],Trigger;rA,Reset;T[110],Eject;",
+    )
+    .unwrap();
+
+    let bits: Vec<_> = config
+        .menu
+        .iter()
+        .map(|item| match item {
+            ConfigMenu::Option { bits, .. } => bits.clone(),
+            ConfigMenu::Trigger { index, .. } => *index..(*index + 1),
+            _ => unreachable!(),
+        })
+        .collect();
+    // Bracketed indices are absolute, whatever the case of the prefix.
+    assert_eq!(
+        bits,
+        [68..71, 40..41, 62..64, 127..128, 100..101, 42..43, 110..111]
+    );
+
+    assert!(Config::from_str("TEST;;O[128],Invalid,Off,On;").is_err());
+    assert!(Config::from_str("TEST;;O[68:70],Invalid,Off,On;").is_err());
+
+    let status = StatusBitMap::new().with_range(68..71, 2).with(127);
+    let items = config.as_core_settings(
+        &status,
+        &DipSwitches::default(),
+        &Cheats::default(),
+        &RememberedFiles::default(),
+    );
+    assert!(matches!(
+        &items.items()[0],
+        CoreSettingItem::IntOption { value: 2, .. }
+    ));
+    assert!(matches!(
+        &items.items()[3],
+        CoreSettingItem::BoolOption { value: true, .. }
+    ));
+}
@@ -3,7 +3,7 @@ use std::str::FromStr;
 
 const DEFAULT_MIDI_SPEED: u32 = 31250;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MidiSpeed {
     pub speed: u32,
     pub label: String,
@@ -15,9 +15,9 @@ impl FromStr for MidiSpeed {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let captures = LABELED_SPEED_RE.captures(s).ok_or("Invalid MIDI mode")?;
 
-        let speed = match captures.get(1) {
-            None => DEFAULT_MIDI_SPEED,
-            Some(s) => s.as_str().parse::<u32>().map_err(|_| "Invalid MIDI mode")?,
+        let speed = match captures.get(1).map(|s| s.as_str()) {
+            None | Some("") => DEFAULT_MIDI_SPEED,
+            Some(s) => s.parse::<u32>().map_err(|_| "Invalid MIDI mode")?,
         };
 
         let label = captures
@@ -2,7 +2,7 @@ use super::*;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::{char, digit1, one_of, satisfy};
-use nom::combinator::{map, opt, recognize, value};
+use nom::combinator::{map, map_opt, map_res, opt, recognize, value};
 use nom::multi::{many0, many1, separated_list0, separated_list1};
 use nom::sequence::{delimited, pair, preceded, separated_pair};
 use nom::{IResult, Parser};
@@ -21,10 +21,9 @@ fn name(input: Input) -> Result<String> {
 /// Parse the core settings, which is always the second entry.
 fn core_settings(input: Input) -> Result<settings::Settings> {
     // TODO: add a proper parser for the settings line.
-    map(
-        recognize(many0(satisfy(|c| c != ';' && c != '-' && c != 'C'))),
-        |s: Input| settings::Settings::from_str(s).unwrap(),
-    )
+    map_res(recognize(many0(satisfy(|c| c != ';'))), |s: Input| {
+        settings::Settings::from_str(s)
+    })
     .parse(input)
 }
 
@@ -97,7 +96,7 @@ fn disable_if(line: u8) -> impl FnMut(Input) -> Result<ConfigMenu> {
 fn disable_unless(line: u8) -> impl FnMut(Input) -> Result<ConfigMenu> {
     move |input| {
         map((char('d'), integer, config_menu_line(line)), |(_, s, c)| {
-            ConfigMenu::DisableIf(s, Box::new(c))
+            ConfigMenu::DisableUnless(s, Box::new(c))
         })
         .parse(input)
     }
@@ -117,7 +116,7 @@ fn hide_if(line: u8) -> impl FnMut(Input) -> Result<ConfigMenu> {
 fn hide_unless(line: u8) -> impl FnMut(Input) -> Result<ConfigMenu> {
     move |input| {
         map((char('h'), integer, config_menu_line(line)), |(_, s, c)| {
-            ConfigMenu::HideIf(s, Box::new(c))
+            ConfigMenu::HideUnless(s, Box::new(c))
         })
         .parse(input)
     }
@@ -145,9 +144,10 @@ fn file<'a>(line: u8) -> impl Fn(Input<'a>) -> Result<'a, ConfigMenu> {
                             opt(satisfy(is_valid_filename_char)),
                         ))),
                     ),
+                    // The text can be empty if an address follows.
                     opt(preceded(
                         char(','),
-                        recognize(many1(satisfy(|c| c != ';' && c != ','))),
+                        recognize(many0(satisfy(|c| c != ';' && c != ','))),
                     )),
                     opt(preceded(char(','), hex_u32)),
                 ),
@@ -165,7 +165,7 @@ fn file<'a>(line: u8) -> impl Fn(Input<'a>) -> Result<'a, ConfigMenu> {
                         save_support: save.is_some(),
                         index,
                         extensions,
-                        label: text.map(|s: Input| s.to_string()),
+                        label: text.filter(|s| !s.is_empty()).map(|s| s.to_string()),
                         address,
                     };
                     if remember.is_some() {
@@ -218,6 +218,9 @@ fn sd_card(input: Input) -> Result<ConfigMenu> {
     .parse(input)
 }
 
+/// The number of status bits addressable by the config string.
+const STATUS_BIT_COUNT: u32 = 128;
+
 fn single_char_bit_index(input: Input) -> Result<u8> {
     map(one_of("0123456789ABCDEFGHIJKLMNOPQRSTUV"), |i| match i {
         '0'..='9' => i as u8 - b'0',
@@ -227,43 +230,69 @@ fn single_char_bit_index(input: Input) -> Result<u8> {
     .parse(input)
 }
 
-fn status_bit_index(input: Input) -> Result<u8> {
-    alt((
-        map(delimited(char('['), integer, char(']')), |i| i as u8),
-        single_char_bit_index,
-    ))
+/// A bit index in brackets, e.g. `[70]`. Bracketed indices are absolute, and can
+/// address any of the status bits.
+fn bracketed_bit_index(input: Input) -> Result<u8> {
+    map_opt(delimited(char('['), integer, char(']')), |i| {
+        (i < STATUS_BIT_COUNT).then_some(i as u8)
+    })
     .parse(input)
 }
 
-fn status_bit_range(input: Input) -> Result<Range<u8>> {
-    alt((
-        map(
-            delimited(
-                char('['),
-                separated_pair(integer, char(':'), integer),
-                char(']'),
+/// A bit index, either bracketed or a single character. Single characters only
+/// address 32 bits, starting at `offset` (32 for lowercase prefixes).
+fn status_bit_index(offset: u8) -> impl FnMut(Input) -> Result<u8> {
+    move |input| {
+        alt((
+            bracketed_bit_index,
+            map(single_char_bit_index, |i| i + offset),
+        ))
+        .parse(input)
+    }
+}
+
+/// A range of bits, e.g. `[70:68]`, `12` or `4`. See [`status_bit_index`].
+fn status_bit_range(offset: u8) -> impl FnMut(Input) -> Result<Range<u8>> {
+    move |input| {
+        alt((
+            map_opt(
+                delimited(
+                    char('['),
+                    separated_pair(integer, char(':'), integer),
+                    char(']'),
+                ),
+                // Bit ranges are inclusive.
+                |(b, a)| (a <= b && b < STATUS_BIT_COUNT).then_some((a as u8)..(b as u8 + 1)),
             ),
-            |(b, a)| (a as u8)..(b as u8 + 1), // Bit ranges are inclusive.
-        ),
-        // Single bits are also accepted in ranges.
-        map(
-            pair(single_char_bit_index, single_char_bit_index),
-            |(a, b)| a..(b + 1), // Bit ranges are inclusive.
-        ),
-        map(status_bit_index, |a| a..(a + 1)),
-    ))
-    .parse(input)
+            // Single bits are also accepted in ranges.
+            map(
+                pair(single_char_bit_index, single_char_bit_index),
+                |(a, b)| (a + offset)..(b + offset + 1), // Bit ranges are inclusive.
+            ),
+            map(status_bit_index(offset), |a| a..(a + 1)),
+        ))
+        .parse(input)
+    }
+}
+
+/// A status bit index prefixed by `upper`, or by its lowercase version to address
+/// bits 32 to 63 with a single character.
+fn prefixed_bit_index(upper: char) -> impl FnMut(Input) -> Result<u8> {
+    move |input| {
+        alt((
+            preceded(char(upper), status_bit_index(0)),
+            preceded(char(upper.to_ascii_lowercase()), status_bit_index(32)),
+        ))
+        .parse(input)
+    }
 }
 
 fn option(input: Input) -> Result<ConfigMenu> {
     map(
         (
             alt((
-                preceded(char('O'), status_bit_range),
-                preceded(
-                    char('o'),
-                    map(status_bit_range, |i| (i.start + 32)..(i.end + 32)),
-                ),
+                preceded(char('O'), status_bit_range(0)),
+                preceded(char('o'), status_bit_range(32)),
             )),
             char(','),
             recognize(many0(satisfy(|c| c != ';' && c != ','))),
@@ -285,13 +314,11 @@ fn option(input: Input) -> Result<ConfigMenu> {
 fn trigger(input: Input) -> Result<ConfigMenu> {
     map(
         (
-            alt((
-                preceded(char('T'), status_bit_index),
-                preceded(char('t'), map(status_bit_index, |i| i + 32)),
-            )),
+            prefixed_bit_index('T'),
+            opt(char(',')),
             recognize(many0(satisfy(|c| c != ';'))),
         ),
-        |(index, label)| ConfigMenu::Trigger {
+        |(index, _, label)| ConfigMenu::Trigger {
             close_osd: false,
             index,
             label: label.to_string(),
@@ -303,10 +330,7 @@ fn trigger(input: Input) -> Result<ConfigMenu> {
 fn reset(input: Input) -> Result<ConfigMenu> {
     map(
         (
-            alt((
-                preceded(char('R'), status_bit_index),
-                preceded(char('r'), map(status_bit_index, |i| i + 32)),
-            )),
+            prefixed_bit_index('R'),
             char(','),
             recognize(many0(satisfy(|c| c != ';'))),
         ),
@@ -323,10 +347,10 @@ fn info(input: Input) -> Result<ConfigMenu> {
     map(
         preceded(
             char::<Input, _>('I'),
-            separated_list0(
+            many0(preceded(
                 char(','),
                 recognize(many0(satisfy(|i| i != ',' && i != ';'))),
-            ),
+            )),
         ),
         |lines| ConfigMenu::Info(lines.iter().map(|x| x.to_string()).collect()),
     )
@@ -370,7 +394,7 @@ fn joystick_buttons(input: Input) -> Result<ConfigMenu> {
                 ),
             ),
             |(joy_emulation, buttons)| ConfigMenu::JoystickButtons {
-                keyboard: joy_emulation.is_none(),
+                keyboard: joy_emulation.is_some(),
                 buttons: buttons.iter().map(|x| x.to_string()).collect(),
             },
         ),
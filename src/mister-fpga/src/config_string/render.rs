@@ -0,0 +1,242 @@
+//! Render a config back to a MiSTer config string. The output can be parsed back
+//! to the same structure (though not necessarily to the same string, e.g. file
+//! indices are always explicit).
+use std::fmt::{Display, Formatter, Result, Write};
+use std::ops::Range;
+
+use super::settings::Settings;
+use super::{Config, ConfigMenu, FileExtension, LoadFileInfo};
+
+const BIT_INDEX_CHARS: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+/// Write a single status bit index (`A`, or `[40]` for bits above 31).
+fn write_bit_index(f: &mut Formatter<'_>, index: u8) -> Result {
+    match BIT_INDEX_CHARS.get(index as usize) {
+        Some(c) => f.write_char(*c as char),
+        None => write!(f, "[{index}]"),
+    }
+}
+
+/// Write a status bit index with its prefix, using the lowercase prefix for bits
+/// 32 to 63 when possible.
+fn write_prefixed_bit_index(f: &mut Formatter<'_>, prefix: char, index: u8) -> Result {
+    if (32..64).contains(&index) {
+        f.write_char(prefix.to_ascii_lowercase())?;
+        write_bit_index(f, index - 32)
+    } else {
+        f.write_char(prefix)?;
+        write_bit_index(f, index)
+    }
+}
+
+/// Write a range of status bits with its prefix (`O12`, `o12` or `O[40:38]`).
+fn write_prefixed_bit_range(f: &mut Formatter<'_>, prefix: char, bits: &Range<u8>) -> Result {
+    let (prefix, start, last) = if bits.start >= 32 && bits.end <= 64 {
+        (prefix.to_ascii_lowercase(), bits.start - 32, bits.end - 33)
+    } else {
+        (prefix, bits.start, bits.end.saturating_sub(1))
+    };
+
+    f.write_char(prefix)?;
+    if last < 32 {
+        write_bit_index(f, start)?;
+        if last != start {
+            write_bit_index(f, last)?;
+        }
+        Ok(())
+    } else if last == start {
+        write!(f, "[{start}]")
+    } else {
+        write!(f, "[{last}:{start}]")
+    }
+}
+
+fn write_extensions(f: &mut Formatter<'_>, extensions: &[FileExtension]) -> Result {
+    for ext in extensions {
+        // Use the padded extension, as extensions are parsed 3 characters at a time.
+        f.write_str(&String::from_utf8_lossy(&ext.0))?;
+    }
+    Ok(())
+}
+
+fn write_load_file(f: &mut Formatter<'_>, remember: bool, info: &LoadFileInfo) -> Result {
+    f.write_char('F')?;
+    if remember {
+        f.write_char('C')?;
+    }
+    if info.save_support {
+        f.write_char('S')?;
+    }
+    write!(f, "{},", info.index)?;
+    write_extensions(f, &info.extensions)?;
+
+    match (&info.label, &info.address) {
+        (Some(label), Some(address)) => write!(f, ",{label},{:08X}", address.as_u32()),
+        (None, Some(address)) => write!(f, ",,{:08X}", address.as_u32()),
+        (Some(label), None) => write!(f, ",{label}"),
+        (None, None) => Ok(()),
+    }
+}
+
+impl Display for ConfigMenu {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ConfigMenu::Empty(None) => f.write_char('-'),
+            ConfigMenu::Empty(Some(text)) => write!(f, "-{text}"),
+            ConfigMenu::Cheat(None) => f.write_char('C'),
+            ConfigMenu::Cheat(Some(text)) => write!(f, "C,{text}"),
+            ConfigMenu::DisableIf(bit, sub) => write!(f, "D{bit}{sub}"),
+            ConfigMenu::DisableUnless(bit, sub) => write!(f, "d{bit}{sub}"),
+            ConfigMenu::HideIf(bit, sub) => write!(f, "H{bit}{sub}"),
+            ConfigMenu::HideUnless(bit, sub) => write!(f, "h{bit}{sub}"),
+            ConfigMenu::Dip => f.write_str("DIP"),
+            ConfigMenu::LoadFile(info) => write_load_file(f, false, info),
+            ConfigMenu::LoadFileAndRemember(info) => write_load_file(f, true, info),
+            ConfigMenu::MountSdCard {
+                slot,
+                extensions,
+                label,
+            } => {
+                write!(f, "S{slot},")?;
+                write_extensions(f, extensions)?;
+                match label {
+                    Some(label) => write!(f, ",{label}"),
+                    None => Ok(()),
+                }
+            }
+            ConfigMenu::Option {
+                bits,
+                label,
+                choices,
+            } => {
+                write_prefixed_bit_range(f, 'O', bits)?;
+                write!(f, ",{label},{}", choices.join(","))
+            }
+            ConfigMenu::Trigger {
+                close_osd,
+                index,
+                label,
+            } => {
+                write_prefixed_bit_index(f, if *close_osd { 'R' } else { 'T' }, *index)?;
+                write!(f, ",{label}")
+            }
+            ConfigMenu::JoystickButtons { keyboard, buttons } => {
+                let lock = if *keyboard { "1" } else { "" };
+                write!(f, "J{lock},{}", buttons.join(","))
+            }
+            ConfigMenu::SnesButtonDefaultList { buttons } => write!(f, "jn,{}", buttons.join(",")),
+            ConfigMenu::SnesButtonDefaultPositionalList { buttons } => {
+                write!(f, "jp,{}", buttons.join(","))
+            }
+            ConfigMenu::Page { index, label } => write!(f, "P{index},{label}"),
+            ConfigMenu::PageItem(index, sub) => write!(f, "P{index}{sub}"),
+            ConfigMenu::Info(lines) => {
+                f.write_char('I')?;
+                for line in lines {
+                    write!(f, ",{line}")?;
+                }
+                Ok(())
+            }
+            ConfigMenu::Version(version) => write!(f, "V,{version}"),
+        }
+    }
+}
+
+/// Render a list of speeds (`31250:115200(Label)`), with their prefix.
+fn speeds<'a>(prefix: &str, speeds: impl Iterator<Item = (u32, &'a str)>) -> String {
+    let speeds: Vec<String> = speeds
+        .map(|(speed, label)| {
+            if label == speed.to_string() {
+                speed.to_string()
+            } else {
+                format!("{speed}({label})")
+            }
+        })
+        .collect();
+    format!("{prefix}{}", speeds.join(":"))
+}
+
+impl Display for Settings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let mut parts = Vec::new();
+        if let Some((address, size)) = &self.save_state {
+            parts.push(format!("SS{:X}:{:X}", address.as_u32(), size));
+        }
+        if !self.uart_mode.is_empty() {
+            parts.push(speeds(
+                "UART",
+                self.uart_mode.iter().map(|s| (s.speed, s.label.as_str())),
+            ));
+        }
+        if !self.midi_mode.is_empty() {
+            parts.push(speeds(
+                "MIDI",
+                self.midi_mode.iter().map(|s| (s.speed, s.label.as_str())),
+            ));
+        }
+        f.write_str(&parts.join(","))
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{};{};", self.name, self.settings)?;
+        for (i, item) in self.menu.iter().enumerate() {
+            if i > 0 {
+                f.write_char(';')?;
+            }
+            write!(f, "{item}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn render_config() {
+    use std::str::FromStr;
+
+    let config = Config::from_str(
+        "Test;SS3E000000:80000,UART31250:115200(Fast),MIDI;\
+        FS,GBA,Load,300C0000;\
+        FC2,BINROM;\
+        S0,VHDIMG,Disk;\
+        -;\
+        -Text;\
+        C,Cheats;\
+        DIP;\
+        H1O[6],Cheats Enabled,Yes,No;\
+        d2P1O[40:38],Slot,1,2,3,4;\
+        h3T4,Trigger;\
+        D0r5,Reset;\
+        P1,Page;\
+        I,One,Two;\
+        J1,A,B;\
+        jn,A,B;\
+        jp,Y,B;\
+        V,v1",
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.to_string(),
+        "Test;SS3E000000:80000,UART31250:115200(Fast),MIDI31250;\
+        FS1,GBA,Load,300C0000;\
+        FC2,BINROM;\
+        S0,VHDIMG,Disk;\
+        -;\
+        -Text;\
+        C,Cheats;\
+        DIP;\
+        H1O6,Cheats Enabled,Yes,No;\
+        d2P1o68,Slot,1,2,3,4;\
+        h3T4,Trigger;\
+        D0r5,Reset;\
+        P1,Page;\
+        I,One,Two;\
+        J1,A,B;\
+        jn,A,B;\
+        jp,Y,B;\
+        V,v1"
+    );
+    assert_eq!(Config::from_str(&config.to_string()).unwrap(), config);
+}
@@ -5,7 +5,7 @@ use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::str::FromStr;
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, PartialEq)]
 pub struct Settings {
     /// UART mode
     pub uart_mode: Vec<uart::UartSpeed>,
@@ -73,14 +73,14 @@ impl FromStr for Settings {
                 continue;
             }
 
-            if let Some(s) = s.strip_prefix("SS") {
+            if let Some(s) = setting.strip_prefix("SS") {
                 save_state = Some(Self::parse_save_state(s)?);
-            } else if let Some(s) = s.strip_prefix("UART") {
+            } else if let Some(s) = setting.strip_prefix("UART") {
                 // Parse strings of format "12345(label):56789(label 2)".
                 for speed in s.split(':') {
                     uart_mode.push(speed.parse::<uart::UartSpeed>()?);
                 }
-            } else if let Some(s) = s.strip_prefix("MIDI") {
+            } else if let Some(s) = setting.strip_prefix("MIDI") {
                 // Parse strings of format "12345(label):56789(label 2)".
                 for speed in s.split(':') {
                     midi_mode.push(speed.parse::<midi::MidiSpeed>()?);
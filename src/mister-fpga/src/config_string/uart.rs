@@ -1,7 +1,7 @@
 use super::LABELED_SPEED_RE;
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UartSpeed {
     pub speed: u32,
     pub label: String,
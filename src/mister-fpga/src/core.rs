@@ -1,5 +1,11 @@
 pub mod buttons;
+pub mod cd;
+pub mod ddram;
 pub mod file;
+pub mod inputs;
+pub mod mouse;
+pub mod save_file;
+pub mod vfat;
 pub mod volume;
 
 pub mod video;
@@ -7,6 +7,26 @@ use static_assertions::{const_assert, const_assert_eq};
 use strum::{Display, EnumCount, EnumIter, EnumString, FromRepr, IntoEnumIterator};
 use tracing::trace;
 
+/// Absolute analog values at or below this threshold are considered centered.
+pub const ANALOG_DEADZONE: i16 = 2048;
+
+/// Value above which an analog trigger is considered pressed.
+pub const TRIGGER_THRESHOLD: i16 = 16384;
+
+/// Convert a raw axis value to the signed 8-bit range that MiSTer cores expect.
+/// Values within the deadzone are centered, and the rest of the range is
+/// rescaled so that `-127..=127` can still be reached.
+pub fn scale_axis(value: i16, deadzone: i16) -> i8 {
+    let deadzone = deadzone.clamp(0, i16::MAX - 1) as i32;
+    let value = (value as i32).clamp(-(i16::MAX as i32), i16::MAX as i32);
+    if value.abs() <= deadzone {
+        return 0;
+    }
+
+    let scaled = (value.abs() - deadzone) * 127 / (i16::MAX as i32 - deadzone);
+    (scaled * value.signum()) as i8
+}
+
 /// Buttons supported by the MisterFPGA API.
 /// This is MiSTer specific.
 #[derive(
@@ -221,3 +241,14 @@ impl ButtonMap {
         s
     }
 }
+
+#[test]
+fn scale_axis_range() {
+    assert_eq!(scale_axis(0, ANALOG_DEADZONE), 0);
+    assert_eq!(scale_axis(ANALOG_DEADZONE, ANALOG_DEADZONE), 0);
+    assert_eq!(scale_axis(-ANALOG_DEADZONE, ANALOG_DEADZONE), 0);
+    assert_eq!(scale_axis(i16::MAX, ANALOG_DEADZONE), 127);
+    assert_eq!(scale_axis(i16::MIN, ANALOG_DEADZONE), -127);
+    assert_eq!(scale_axis(i16::MAX, 0), 127);
+    assert!(scale_axis(ANALOG_DEADZONE + 300, ANALOG_DEADZONE) > 0);
+}
@@ -0,0 +1,513 @@
+//! CD images (CUE/BIN, CHD and ISO) that can be mounted to CD-based cores.
+//!
+//! A disc is made of tracks laid out one after the other, each with its own
+//! sector format. The core reads the disc as a flat sequence of sectors, either
+//! raw (2352 bytes, with sync and header) or user data only (2048 bytes), and
+//! sectors are read from the image files (or decompressed from CHD hunks) as
+//! they are requested.
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use tracing::debug;
+
+pub mod chd;
+pub mod cue;
+
+/// The size of a raw CD sector.
+pub const RAW_SECTOR_SIZE: usize = 2352;
+
+/// The size of the user data of a Mode 1 (or Mode 2 Form 1) sector.
+pub const DATA_SECTOR_SIZE: usize = 2048;
+
+/// The number of sectors (frames) per second of a CD.
+const FRAMES_PER_SECOND: u32 = 75;
+
+/// The 2 seconds of lead-in before LBA 0, as MSF addresses count from there.
+const MSF_OFFSET: u32 = 150;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CdError {
+    #[error("Invalid CUE sheet at line {line}: {message}")]
+    InvalidCue { line: usize, message: String },
+
+    #[error("Unsupported track type {0:?}")]
+    UnsupportedTrackType(String),
+
+    #[error("Unsupported CD image {0:?}")]
+    UnsupportedImage(String),
+
+    #[error("CHD error: {0}")]
+    Chd(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<CdError> for one_fpga::core::Error {
+    fn from(value: CdError) -> Self {
+        match value {
+            CdError::Io(e) => one_fpga::core::Error::IoError(e),
+            CdError::UnsupportedTrackType(_) | CdError::UnsupportedImage(_) => {
+                one_fpga::core::Error::Unsupported(value.to_string())
+            }
+            e => one_fpga::core::Error::Message(e.to_string()),
+        }
+    }
+}
+
+impl From<CdError> for std::io::Error {
+    fn from(value: CdError) -> Self {
+        match value {
+            CdError::Io(e) => e,
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// A position on a disc, in minutes, seconds and frames (including the 2
+/// seconds lead-in).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Msf {
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+}
+
+impl Msf {
+    /// The MSF address of a logical block address.
+    pub fn from_lba(lba: u32) -> Self {
+        let lba = lba + MSF_OFFSET;
+        Self {
+            minutes: (lba / FRAMES_PER_SECOND / 60) as u8,
+            seconds: (lba / FRAMES_PER_SECOND % 60) as u8,
+            frames: (lba % FRAMES_PER_SECOND) as u8,
+        }
+    }
+
+    /// Parse a `mm:ss:ff` length, as used in CUE sheets, into a number of
+    /// sectors. Unlike an MSF address, this does not include the lead-in.
+    pub fn parse_length(s: &str) -> Option<u32> {
+        let mut parts = s.split(':').map(|p| p.parse::<u32>().ok());
+        let (m, s, f) = (parts.next()??, parts.next()??, parts.next()??);
+        if parts.next().is_some() || s >= 60 || f >= FRAMES_PER_SECOND {
+            return None;
+        }
+        Some((m * 60 + s) * FRAMES_PER_SECOND + f)
+    }
+
+    fn to_bcd(self) -> [u8; 3] {
+        let bcd = |v: u8| ((v / 10) << 4) | (v % 10);
+        [bcd(self.minutes), bcd(self.seconds), bcd(self.frames)]
+    }
+}
+
+/// The format of the sectors of a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackType {
+    /// CD-DA audio, 2352 bytes of 16-bit stereo samples per sector.
+    Audio,
+
+    /// Mode 1 data, only the 2048 bytes of user data are stored.
+    Mode1,
+
+    /// Mode 1 data, stored as raw 2352 bytes sectors.
+    Mode1Raw,
+
+    /// Mode 2 data, stored without the sync and header (2336 bytes).
+    Mode2,
+
+    /// Mode 2 data, stored as raw 2352 bytes sectors.
+    Mode2Raw,
+}
+
+impl TrackType {
+    /// The size of a sector of this track in the image file.
+    pub fn stored_size(&self) -> usize {
+        match self {
+            TrackType::Mode1 => DATA_SECTOR_SIZE,
+            TrackType::Mode2 => 2336,
+            TrackType::Audio | TrackType::Mode1Raw | TrackType::Mode2Raw => RAW_SECTOR_SIZE,
+        }
+    }
+
+    pub fn is_audio(&self) -> bool {
+        matches!(self, TrackType::Audio)
+    }
+}
+
+/// A track of a disc. Addresses are logical block addresses (sectors from the
+/// start of the disc).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+    pub number: u8,
+    pub kind: TrackType,
+
+    /// The address of the start of the track (index 01).
+    pub start: u32,
+
+    /// The number of sectors of pregap before the start of the track.
+    pub pregap: u32,
+
+    /// The number of sectors of the track, from its start.
+    pub length: u32,
+}
+
+/// The table of contents of a disc.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toc {
+    pub tracks: Vec<Track>,
+
+    /// The address of the end of the last track.
+    pub lead_out: u32,
+}
+
+impl Toc {
+    /// The track containing a sector, including its pregap.
+    pub fn track_at(&self, lba: u32) -> Option<&Track> {
+        self.tracks
+            .iter()
+            .rev()
+            .find(|t| t.start.saturating_sub(t.pregap) <= lba)
+            .filter(|_| lba < self.lead_out)
+    }
+
+    /// Serialize the table of contents for cores that request it. All numbers
+    /// are little endian.
+    ///
+    /// The header is the first and last track numbers (1 byte each), 2 reserved
+    /// bytes and the lead-out address (4 bytes). It is followed by 12 bytes per
+    /// track: its number, 1 for data or 0 for audio, 2 reserved bytes, then its
+    /// start address and length (4 bytes each).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.tracks.len() * 12);
+        bytes.push(self.tracks.first().map_or(0, |t| t.number));
+        bytes.push(self.tracks.last().map_or(0, |t| t.number));
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.lead_out.to_le_bytes());
+        for track in &self.tracks {
+            bytes.push(track.number);
+            bytes.push(if track.kind.is_audio() { 0 } else { 1 });
+            bytes.extend_from_slice(&[0, 0]);
+            bytes.extend_from_slice(&track.start.to_le_bytes());
+            bytes.extend_from_slice(&track.length.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// Where the sectors of a span of the disc come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpanData {
+    /// Sectors that are not stored in the image (e.g. a `PREGAP`), and read as
+    /// silence or empty data.
+    Zero,
+
+    /// Sectors stored in a file, starting at a byte offset.
+    File { file: usize, offset: u64 },
+
+    /// Sectors stored in the CHD, starting at a frame.
+    Chd { frame: u32 },
+}
+
+/// Consecutive sectors of a disc, stored the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    start: u32,
+    sectors: u32,
+    kind: TrackType,
+    data: SpanData,
+}
+
+/// The size of the sectors the core reads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CdSectorSize {
+    /// Raw 2352 bytes sectors.
+    #[default]
+    Raw,
+
+    /// The 2048 bytes of user data of data sectors.
+    Data,
+}
+
+impl CdSectorSize {
+    pub fn from_block_size(size: usize) -> Option<Self> {
+        match size {
+            RAW_SECTOR_SIZE => Some(Self::Raw),
+            DATA_SECTOR_SIZE => Some(Self::Data),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        match self {
+            Self::Raw => RAW_SECTOR_SIZE,
+            Self::Data => DATA_SECTOR_SIZE,
+        }
+    }
+}
+
+/// A read-only CD image, which reads as a flat sequence of sectors.
+#[derive(Debug)]
+pub struct CdImage {
+    toc: Toc,
+    spans: Vec<Span>,
+    files: Vec<File>,
+    chd: Option<chd::ChdSource>,
+    sector_size: CdSectorSize,
+    position: u64,
+
+    /// The last sector read, as it is read in smaller parts.
+    sector: Option<(u32, [u8; RAW_SECTOR_SIZE])>,
+}
+
+impl CdImage {
+    /// Open a CUE sheet, a CHD file or an ISO file, from its extension.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CdError> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match ext.as_str() {
+            "cue" => cue::open(path),
+            "chd" => chd::open(path),
+            "iso" => Self::from_iso(File::open(path)?),
+            _ => Err(CdError::UnsupportedImage(path.display().to_string())),
+        }
+    }
+
+    /// Whether a file can be opened as a CD image, from its extension.
+    pub fn is_cd_image(path: impl AsRef<Path>) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| {
+                ["cue", "chd", "iso"]
+                    .iter()
+                    .any(|x| x.eq_ignore_ascii_case(e))
+            })
+    }
+
+    /// A disc with a single Mode 1 data track, stored as 2048 bytes sectors.
+    /// It is read as data sectors until the core asks for raw sectors.
+    pub fn from_iso(file: File) -> Result<Self, CdError> {
+        let sectors = (file.metadata()?.len() / DATA_SECTOR_SIZE as u64) as u32;
+        let track = Track {
+            number: 1,
+            kind: TrackType::Mode1,
+            start: 0,
+            pregap: 0,
+            length: sectors,
+        };
+        let span = Span {
+            start: 0,
+            sectors,
+            kind: TrackType::Mode1,
+            data: SpanData::File { file: 0, offset: 0 },
+        };
+        let mut image = Self::new(vec![track], vec![span], vec![file], None);
+        image.sector_size = CdSectorSize::Data;
+        Ok(image)
+    }
+
+    fn new(
+        tracks: Vec<Track>,
+        spans: Vec<Span>,
+        files: Vec<File>,
+        chd: Option<chd::ChdSource>,
+    ) -> Self {
+        let lead_out = spans.last().map_or(0, |s| s.start + s.sectors);
+        debug!(?tracks, lead_out, "Opened CD image");
+        Self {
+            toc: Toc { tracks, lead_out },
+            spans,
+            files,
+            chd,
+            sector_size: CdSectorSize::default(),
+            position: 0,
+            sector: None,
+        }
+    }
+
+    pub fn toc(&self) -> &Toc {
+        &self.toc
+    }
+
+    pub fn sector_size(&self) -> CdSectorSize {
+        self.sector_size
+    }
+
+    /// Set the size of the sectors read, which changes the size of the image.
+    pub fn set_sector_size(&mut self, sector_size: CdSectorSize) {
+        if self.sector_size != sector_size {
+            let lba = self.position / self.sector_size.bytes() as u64;
+            self.sector_size = sector_size;
+            self.position = lba * sector_size.bytes() as u64;
+        }
+    }
+
+    /// The size of the image, in bytes, with the current sector size.
+    pub fn size(&self) -> u64 {
+        self.toc.lead_out as u64 * self.sector_size.bytes() as u64
+    }
+
+    /// Read a raw 2352 bytes sector. Sectors that are not stored as raw sectors
+    /// get their sync and header rebuilt. The EDC and ECC are left empty.
+    pub fn read_raw_sector(
+        &mut self,
+        lba: u32,
+        buffer: &mut [u8; RAW_SECTOR_SIZE],
+    ) -> Result<(), CdError> {
+        buffer.fill(0);
+        let Some(span) = self
+            .spans
+            .iter()
+            .find(|s| s.start <= lba && lba < s.start + s.sectors)
+            .copied()
+        else {
+            return Ok(());
+        };
+        let index = lba - span.start;
+        let stored = span.kind.stored_size();
+        let offset = match span.kind {
+            TrackType::Audio | TrackType::Mode1Raw | TrackType::Mode2Raw => 0,
+            TrackType::Mode1 => 16,
+            TrackType::Mode2 => 16,
+        };
+        let target = &mut buffer[offset..offset + stored];
+
+        match span.data {
+            SpanData::Zero => {}
+            SpanData::File { file, offset } => {
+                let file = &mut self.files[file];
+                file.seek(SeekFrom::Start(offset + index as u64 * stored as u64))?;
+                file.read_exact(target)?;
+            }
+            SpanData::Chd { frame } => {
+                let chd = self
+                    .chd
+                    .as_mut()
+                    .ok_or_else(|| CdError::Chd("No CHD opened".to_string()))?;
+                chd.read_frame(frame + index, target, span.kind.is_audio())?;
+            }
+        }
+
+        // Rebuild the sync pattern and header of cooked data sectors.
+        if offset != 0 || (span.data == SpanData::Zero && !span.kind.is_audio()) {
+            buffer[0] = 0;
+            buffer[1..11].fill(0xFF);
+            buffer[11] = 0;
+            buffer[12..15].copy_from_slice(&Msf::from_lba(lba).to_bcd());
+            buffer[15] = match span.kind {
+                TrackType::Mode2 | TrackType::Mode2Raw => 2,
+                _ => 1,
+            };
+        }
+        Ok(())
+    }
+
+    /// Read a sector in the current sector size.
+    fn read_sector(&mut self, lba: u32) -> Result<&[u8], CdError> {
+        let sector = match self.sector.take() {
+            Some((l, sector)) if l == lba => sector,
+            _ => {
+                let mut buffer = [0; RAW_SECTOR_SIZE];
+                self.read_raw_sector(lba, &mut buffer)?;
+                buffer
+            }
+        };
+        let (_, sector) = self.sector.insert((lba, sector));
+
+        Ok(match self.sector_size {
+            CdSectorSize::Raw => &sector[..],
+            // Mode 2 Form 1 has 8 more bytes of subheader before the user data.
+            CdSectorSize::Data if sector[15] == 2 => &sector[24..24 + DATA_SECTOR_SIZE],
+            CdSectorSize::Data => &sector[16..16 + DATA_SECTOR_SIZE],
+        })
+    }
+}
+
+impl Read for CdImage {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.sector_size.bytes() as u64;
+        let mut read = 0;
+        while read < buf.len() && self.position < self.size() {
+            let lba = (self.position / size) as u32;
+            let offset = (self.position % size) as usize;
+            let sector = self.read_sector(lba)?;
+            let len = (sector.len() - offset).min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&sector[offset..offset + len]);
+            read += len;
+            self.position += len as u64;
+        }
+        Ok(read)
+    }
+}
+
+impl Write for CdImage {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "CD images are read-only",
+        ))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for CdImage {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(p) => self.size().checked_add_signed(p),
+            SeekFrom::Current(p) => self.position.checked_add_signed(p),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl one_fpga::core::MountedFile for CdImage {}
+
+#[test]
+fn iso_sectors() {
+    let path = std::env::temp_dir().join(format!("mister-fpga-cd-{}.iso", std::process::id()));
+    let data = (0..DATA_SECTOR_SIZE * 3)
+        .map(|i| (i / 7) as u8)
+        .collect::<Vec<_>>();
+    std::fs::write(&path, &data).unwrap();
+
+    let mut image = CdImage::open(&path).unwrap();
+    assert_eq!(image.toc().lead_out, 3);
+    // ISO images only hold user data, so they are read as such by default.
+    assert_eq!(image.sector_size(), CdSectorSize::Data);
+    assert_eq!(image.size(), 3 * DATA_SECTOR_SIZE as u64);
+    image.set_sector_size(CdSectorSize::Raw);
+    assert_eq!(image.size(), 3 * RAW_SECTOR_SIZE as u64);
+
+    let mut raw = [0; RAW_SECTOR_SIZE];
+    image.read_raw_sector(1, &mut raw).unwrap();
+    assert_eq!(
+        &raw[..12],
+        &[0, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 0]
+    );
+    assert_eq!(&raw[12..16], &[0x00, 0x02, 0x01, 1]);
+    assert_eq!(
+        &raw[16..16 + DATA_SECTOR_SIZE],
+        &data[DATA_SECTOR_SIZE..DATA_SECTOR_SIZE * 2]
+    );
+
+    image.set_sector_size(CdSectorSize::Data);
+    let mut content = Vec::new();
+    image.seek(SeekFrom::Start(100)).unwrap();
+    image.read_to_end(&mut content).unwrap();
+    assert_eq!(content, &data[100..]);
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,235 @@
+//! CHD (MAME compressed hunks of data) CD images. Hunks are decompressed when a
+//! sector in them is read, and the last one is kept.
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use super::{CdError, CdImage, Span, SpanData, Track, TrackType, RAW_SECTOR_SIZE};
+
+/// The size of a frame in a CD CHD: a raw sector followed by its subchannel data.
+const FRAME_SIZE: usize = RAW_SECTOR_SIZE + 96;
+
+/// Tracks of a CD CHD are padded to a multiple of this many frames.
+const TRACK_PADDING: u32 = 4;
+
+/// The metadata tags of the CD tracks.
+const CDROM_TRACK: u32 = u32::from_be_bytes(*b"CHTR");
+const CDROM_TRACK2: u32 = u32::from_be_bytes(*b"CHT2");
+
+fn chd_error(error: ::chd::Error) -> CdError {
+    CdError::Chd(error.to_string())
+}
+
+/// The hunks of an opened CHD file.
+pub struct ChdSource {
+    chd: ::chd::Chd<BufReader<File>>,
+    frames_per_hunk: u32,
+    hunk: Option<u32>,
+    buffer: Vec<u8>,
+    compressed: Vec<u8>,
+}
+
+impl Debug for ChdSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChdSource")
+            .field("frames_per_hunk", &self.frames_per_hunk)
+            .field("hunk", &self.hunk)
+            .finish()
+    }
+}
+
+impl ChdSource {
+    /// Read the first `target.len()` bytes of a frame. Audio samples are stored
+    /// big endian in CHD files, so they are swapped back.
+    pub fn read_frame(
+        &mut self,
+        frame: u32,
+        target: &mut [u8],
+        audio: bool,
+    ) -> Result<(), CdError> {
+        let hunk = frame / self.frames_per_hunk;
+        if self.hunk != Some(hunk) {
+            self.hunk = None;
+            self.chd
+                .hunk(hunk)
+                .and_then(|mut h| h.read_hunk_in(&mut self.compressed, &mut self.buffer))
+                .map_err(chd_error)?;
+            self.hunk = Some(hunk);
+        }
+
+        let offset = (frame % self.frames_per_hunk) as usize * FRAME_SIZE;
+        target.copy_from_slice(&self.buffer[offset..offset + target.len()]);
+        if audio {
+            target
+                .as_chunks_mut::<2>()
+                .0
+                .iter_mut()
+                .for_each(|s| s.swap(0, 1));
+        }
+        Ok(())
+    }
+}
+
+/// A track from the CHD metadata, e.g.
+/// `TRACK:1 TYPE:MODE2_RAW SUBTYPE:NONE FRAMES:1234 PREGAP:150 PGTYPE:VAUDIO ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChdTrack {
+    number: u8,
+    kind: TrackType,
+    frames: u32,
+    pregap: u32,
+
+    /// Whether the pregap is stored in the frames of the track.
+    pregap_stored: bool,
+    postgap: u32,
+}
+
+fn parse_track(metadata: &str) -> Result<ChdTrack, CdError> {
+    let field = |name: &str| {
+        metadata
+            .split_whitespace()
+            .find_map(|f| f.strip_prefix(name)?.strip_prefix(':'))
+            .map(|v| v.trim_end_matches('\0'))
+    };
+    let number = |name: &str| field(name).and_then(|v| v.parse().ok()).unwrap_or(0);
+
+    let kind = match field("TYPE") {
+        Some("AUDIO") => TrackType::Audio,
+        Some("MODE1") | Some("MODE2_FORM1") => TrackType::Mode1,
+        Some("MODE1_RAW") => TrackType::Mode1Raw,
+        Some("MODE2") | Some("MODE2_FORM_MIX") => TrackType::Mode2,
+        Some("MODE2_RAW") => TrackType::Mode2Raw,
+        other => {
+            return Err(CdError::UnsupportedTrackType(
+                other.unwrap_or_default().to_string(),
+            ))
+        }
+    };
+
+    Ok(ChdTrack {
+        number: number("TRACK") as u8,
+        kind,
+        frames: number("FRAMES"),
+        pregap: number("PREGAP"),
+        pregap_stored: field("PGTYPE").is_some_and(|t| t.starts_with('V')),
+        postgap: number("POSTGAP"),
+    })
+}
+
+/// Lay out the tracks of a CHD on the disc.
+fn layout(chd_tracks: &[ChdTrack]) -> (Vec<Track>, Vec<Span>) {
+    let mut tracks = Vec::new();
+    let mut spans = Vec::new();
+    let mut lba = 0;
+    let mut frame = 0;
+
+    for track in chd_tracks {
+        let stored_pregap = if track.pregap_stored { track.pregap } else { 0 };
+        if track.pregap > stored_pregap {
+            spans.push(Span {
+                start: lba,
+                sectors: track.pregap - stored_pregap,
+                kind: track.kind,
+                data: SpanData::Zero,
+            });
+            lba += track.pregap - stored_pregap;
+        }
+
+        spans.push(Span {
+            start: lba,
+            sectors: track.frames,
+            kind: track.kind,
+            data: SpanData::Chd { frame },
+        });
+        tracks.push(Track {
+            number: track.number,
+            kind: track.kind,
+            start: lba + stored_pregap,
+            pregap: track.pregap,
+            length: track.frames.saturating_sub(stored_pregap) + track.postgap,
+        });
+        lba += track.frames;
+        frame += track.frames.div_ceil(TRACK_PADDING) * TRACK_PADDING;
+
+        if track.postgap > 0 {
+            spans.push(Span {
+                start: lba,
+                sectors: track.postgap,
+                kind: track.kind,
+                data: SpanData::Zero,
+            });
+            lba += track.postgap;
+        }
+    }
+
+    (tracks, spans)
+}
+
+/// Open a CHD file.
+pub fn open(path: &Path) -> Result<CdImage, CdError> {
+    let mut chd = ::chd::Chd::open(BufReader::new(File::open(path)?), None).map_err(chd_error)?;
+    let hunk_size = chd.header().hunk_size() as usize;
+    if !hunk_size.is_multiple_of(FRAME_SIZE) {
+        return Err(CdError::UnsupportedImage(format!(
+            "{} is not a CD image",
+            path.display()
+        )));
+    }
+
+    let tracks = chd
+        .metadata_refs()
+        .try_into_vec()
+        .map_err(chd_error)?
+        .into_iter()
+        .filter(|m| m.metatag == CDROM_TRACK || m.metatag == CDROM_TRACK2)
+        .map(|m| parse_track(&String::from_utf8_lossy(&m.value)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (tracks, spans) = layout(&tracks);
+    let source = ChdSource {
+        buffer: chd.get_hunksized_buffer(),
+        chd,
+        frames_per_hunk: (hunk_size / FRAME_SIZE) as u32,
+        hunk: None,
+        compressed: Vec::new(),
+    };
+    Ok(CdImage::new(tracks, spans, Vec::new(), Some(source)))
+}
+
+#[test]
+fn chd_tracks() {
+    let data = parse_track(
+        "TRACK:1 TYPE:MODE2_RAW SUBTYPE:NONE FRAMES:1001 PREGAP:0 PGTYPE:MODE1 PGSUB:RW POSTGAP:0\0",
+    )
+    .unwrap();
+    let audio = parse_track(
+        "TRACK:2 TYPE:AUDIO SUBTYPE:NONE FRAMES:500 PREGAP:150 PGTYPE:VAUDIO PGSUB:RW POSTGAP:0",
+    )
+    .unwrap();
+    assert_eq!(
+        audio,
+        ChdTrack {
+            number: 2,
+            kind: TrackType::Audio,
+            frames: 500,
+            pregap: 150,
+            pregap_stored: true,
+            postgap: 0,
+        }
+    );
+
+    let (tracks, spans) = layout(&[data, audio]);
+    let starts = tracks
+        .iter()
+        .map(|t| (t.start, t.pregap, t.length))
+        .collect::<Vec<_>>();
+    assert_eq!(starts, [(0, 0, 1001), (1151, 150, 350)]);
+    // The first track is padded to 1004 frames in the CHD.
+    assert_eq!(spans[1].data, SpanData::Chd { frame: 1004 });
+
+    assert!(matches!(
+        parse_track("TRACK:1 TYPE:CDI FRAMES:1"),
+        Err(CdError::UnsupportedTrackType(_))
+    ));
+}
@@ -0,0 +1,287 @@
+//! CUE sheets, which describe the tracks of a disc stored in one or more BIN
+//! files.
+use std::fs::File;
+use std::path::Path;
+
+use super::{CdError, CdImage, Msf, Span, SpanData, Track, TrackType};
+
+/// A track of a CUE sheet. Indexes are in sectors from the start of its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueTrack {
+    pub number: u8,
+    pub kind: TrackType,
+
+    /// Sectors of silence (or empty data) before the track that are not stored
+    /// in the file (`PREGAP`).
+    pub pregap: u32,
+
+    /// Sectors of silence after the track that are not stored in the file
+    /// (`POSTGAP`).
+    pub postgap: u32,
+
+    /// `INDEX 00`, the start of the pregap stored in the file, if any.
+    pub index0: Option<u32>,
+
+    /// `INDEX 01`, the start of the track.
+    pub index1: u32,
+}
+
+impl CueTrack {
+    /// The first sector of the track stored in the file.
+    fn file_start(&self) -> u32 {
+        self.index0.unwrap_or(self.index1)
+    }
+}
+
+/// A `FILE` of a CUE sheet and its tracks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueFile {
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+/// Split a line into words, keeping quoted strings together.
+fn words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            words.push(&quoted[..end]);
+            rest = quoted[end..].trim_start_matches('"').trim_start();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            words.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+    }
+    words
+}
+
+/// Parse a CUE sheet.
+pub fn parse(text: &str) -> Result<Vec<CueFile>, CdError> {
+    let mut files: Vec<CueFile> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let error = |message: &str| CdError::InvalidCue {
+            line: i + 1,
+            message: message.to_string(),
+        };
+        let length = |s: Option<&&str>| {
+            s.and_then(|s| Msf::parse_length(s))
+                .ok_or_else(|| error("Invalid time"))
+        };
+
+        let words = words(line);
+        let Some(command) = words.first() else {
+            continue;
+        };
+        match command.to_uppercase().as_str() {
+            "FILE" => {
+                let name = words.get(1).ok_or_else(|| error("Missing file name"))?;
+                let kind = words.get(2).map(|k| k.to_uppercase());
+                if kind.as_deref().is_some_and(|k| k != "BINARY") {
+                    return Err(CdError::UnsupportedImage(format!(
+                        "{} file {name:?}",
+                        kind.unwrap_or_default()
+                    )));
+                }
+                files.push(CueFile {
+                    name: name.to_string(),
+                    tracks: Vec::new(),
+                });
+            }
+            "TRACK" => {
+                let file = files.last_mut().ok_or_else(|| error("TRACK before FILE"))?;
+                let number = words
+                    .get(1)
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| error("Invalid track number"))?;
+                let kind = match words.get(2).map(|k| k.to_uppercase()).as_deref() {
+                    Some("AUDIO") => TrackType::Audio,
+                    Some("MODE1/2048") => TrackType::Mode1,
+                    Some("MODE1/2352") => TrackType::Mode1Raw,
+                    Some("MODE2/2336") => TrackType::Mode2,
+                    Some("MODE2/2352") => TrackType::Mode2Raw,
+                    Some(other) => return Err(CdError::UnsupportedTrackType(other.to_string())),
+                    None => return Err(error("Missing track type")),
+                };
+                file.tracks.push(CueTrack {
+                    number,
+                    kind,
+                    pregap: 0,
+                    postgap: 0,
+                    index0: None,
+                    index1: 0,
+                });
+            }
+            command @ ("INDEX" | "PREGAP" | "POSTGAP") => {
+                let track = files
+                    .last_mut()
+                    .and_then(|f| f.tracks.last_mut())
+                    .ok_or_else(|| error("Missing TRACK"))?;
+                match command {
+                    "PREGAP" => track.pregap = length(words.get(1))?,
+                    "POSTGAP" => track.postgap = length(words.get(1))?,
+                    _ => {
+                        let position = length(words.get(2))?;
+                        match words.get(1).and_then(|n| n.parse::<u8>().ok()) {
+                            Some(0) => track.index0 = Some(position),
+                            Some(1) => track.index1 = position,
+                            // Other indexes are only markers inside the track.
+                            Some(_) => {}
+                            None => return Err(error("Invalid index number")),
+                        }
+                    }
+                }
+            }
+            // REM, CATALOG, TITLE, PERFORMER, FLAGS, ISRC, etc.
+            _ => {}
+        }
+    }
+
+    if files.iter().all(|f| f.tracks.is_empty()) {
+        return Err(CdError::InvalidCue {
+            line: 0,
+            message: "No tracks".to_string(),
+        });
+    }
+    Ok(files)
+}
+
+/// Lay out the tracks of a CUE sheet on the disc, from the size (in bytes) of
+/// each of its files.
+fn layout(files: &[CueFile], sizes: &[u64]) -> (Vec<Track>, Vec<Span>) {
+    let mut tracks = Vec::new();
+    let mut spans = Vec::new();
+    let mut lba = 0;
+
+    for (index, (file, size)) in files.iter().zip(sizes).enumerate() {
+        let mut offset = 0u64;
+        for (i, track) in file.tracks.iter().enumerate() {
+            let stored = track.kind.stored_size() as u64;
+            let sectors = match file.tracks.get(i + 1) {
+                Some(next) => next.file_start().saturating_sub(track.file_start()),
+                None => (size.saturating_sub(offset) / stored) as u32,
+            };
+            let stored_pregap = track.index1.saturating_sub(track.file_start());
+
+            if track.pregap > 0 {
+                spans.push(Span {
+                    start: lba,
+                    sectors: track.pregap,
+                    kind: track.kind,
+                    data: SpanData::Zero,
+                });
+                lba += track.pregap;
+            }
+            spans.push(Span {
+                start: lba,
+                sectors,
+                kind: track.kind,
+                data: SpanData::File {
+                    file: index,
+                    offset,
+                },
+            });
+            tracks.push(Track {
+                number: track.number,
+                kind: track.kind,
+                start: lba + stored_pregap,
+                pregap: track.pregap + stored_pregap,
+                length: sectors.saturating_sub(stored_pregap) + track.postgap,
+            });
+            lba += sectors;
+            offset += sectors as u64 * stored;
+
+            if track.postgap > 0 {
+                spans.push(Span {
+                    start: lba,
+                    sectors: track.postgap,
+                    kind: track.kind,
+                    data: SpanData::Zero,
+                });
+                lba += track.postgap;
+            }
+        }
+    }
+
+    (tracks, spans)
+}
+
+/// Open a CUE sheet and the BIN files next to it.
+pub fn open(path: &Path) -> Result<CdImage, CdError> {
+    let cue = parse(&std::fs::read_to_string(path)?)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let files = cue
+        .iter()
+        .map(|f| File::open(dir.join(&f.name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sizes = files
+        .iter()
+        .map(|f| f.metadata().map(|m| m.len()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (tracks, spans) = layout(&cue, &sizes);
+    Ok(CdImage::new(tracks, spans, files, None))
+}
+
+#[test]
+fn cue_sheet() {
+    let cue = parse(
+        r#"
+        REM Single file with a data track and audio tracks
+        FILE "Game (USA).bin" BINARY
+          TRACK 01 MODE2/2352
+            INDEX 01 00:00:00
+          TRACK 02 AUDIO
+            INDEX 00 00:02:00
+            INDEX 01 00:04:00
+        FILE "Game (USA) (Track 3).bin" BINARY
+          TRACK 03 AUDIO
+            PREGAP 00:02:00
+            INDEX 01 00:00:00
+        "#,
+    )
+    .unwrap();
+    assert_eq!(cue.len(), 2);
+    assert_eq!(cue[0].name, "Game (USA).bin");
+    assert_eq!(
+        cue[0].tracks[1],
+        CueTrack {
+            number: 2,
+            kind: TrackType::Audio,
+            pregap: 0,
+            postgap: 0,
+            index0: Some(150),
+            index1: 300,
+        }
+    );
+
+    let (tracks, spans) = layout(&cue, &[2352 * 1000, 2352 * 500]);
+    let starts = tracks
+        .iter()
+        .map(|t| (t.start, t.pregap, t.length))
+        .collect::<Vec<_>>();
+    assert_eq!(starts, [(0, 0, 150), (300, 150, 700), (1150, 150, 500)]);
+    assert_eq!(spans.last().map(|s| s.start + s.sectors), Some(1650));
+    assert_eq!(
+        spans[2],
+        Span {
+            start: 1000,
+            sectors: 150,
+            kind: TrackType::Audio,
+            data: SpanData::Zero,
+        }
+    );
+
+    assert!(matches!(
+        parse("FILE \"a.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00"),
+        Err(CdError::UnsupportedImage(_))
+    ));
+    assert!(matches!(
+        parse("FILE \"a.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:61:00"),
+        Err(CdError::InvalidCue { line: 3, .. })
+    ));
+}
@@ -0,0 +1,261 @@
+//! Writing files to the DDRAM shared between the HPS and the core. Each chunk
+//! is read back after being written and compared with the source, and written
+//! again if the memory does not hold what was sent.
+use std::io::Read;
+
+use cyclone_v::memory::MemoryMapper;
+use tracing::{debug, warn};
+
+/// The size of the chunks written to DDRAM.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The number of times a chunk is written again after its read-back CRC does
+/// not match before the write fails.
+pub const MAX_RETRIES: usize = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DdramError {
+    /// The source ended before the expected size was written.
+    #[error("Unexpected end of file at offset {offset} (expected {size} bytes)")]
+    UnexpectedEof { offset: usize, size: usize },
+
+    /// The file does not fit in the mapped memory.
+    #[error("File of {size} bytes does not fit in {capacity} bytes of memory")]
+    TooLarge { size: usize, capacity: usize },
+
+    /// The memory did not hold the data written, even after retrying.
+    #[error("CRC mismatch at offset {offset}: expected {expected:08X}, read back {actual:08X}")]
+    CrcMismatch {
+        offset: usize,
+        expected: u32,
+        actual: u32,
+    },
+
+    /// The write was cancelled.
+    #[error("The write was cancelled")]
+    Cancelled,
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<DdramError> for one_fpga::core::Error {
+    fn from(value: DdramError) -> Self {
+        match value {
+            DdramError::Io(e) => one_fpga::core::Error::IoError(e),
+            DdramError::Cancelled => one_fpga::core::Error::Cancelled,
+            DdramError::UnexpectedEof { .. } => one_fpga::core::Error::IoError(
+                std::io::Error::new(std::io::ErrorKind::UnexpectedEof, value),
+            ),
+            DdramError::TooLarge { .. } => one_fpga::core::Error::Unsupported(value.to_string()),
+            DdramError::CrcMismatch { .. } => one_fpga::core::Error::Protocol(value.to_string()),
+        }
+    }
+}
+
+/// Write `size` bytes of `reader` at the start of `mem`, and return their CRC32.
+/// `progress` is called with the number of bytes written after each chunk, and
+/// cancels the write if it returns `false`.
+pub fn write_verified(
+    mem: &mut impl MemoryMapper,
+    mut reader: impl Read,
+    size: usize,
+    progress: &mut dyn FnMut(u32) -> bool,
+) -> Result<u32, DdramError> {
+    if size > mem.len() {
+        return Err(DdramError::TooLarge {
+            size,
+            capacity: mem.len(),
+        });
+    }
+
+    let mut crc = crc32fast::Hasher::new();
+    let mut chunk = vec![0u8; CHUNK_SIZE.min(size)];
+    let mut offset = 0;
+    while offset < size {
+        let len = chunk.len().min(size - offset);
+        let chunk = &mut chunk[..len];
+        reader.read_exact(chunk).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => DdramError::UnexpectedEof { offset, size },
+            _ => DdramError::Io(e),
+        })?;
+
+        let expected = crc32fast::hash(chunk);
+        write_chunk(mem, offset, chunk, expected)?;
+        crc.update(chunk);
+
+        offset += len;
+        if !progress(offset as u32) {
+            return Err(DdramError::Cancelled);
+        }
+    }
+
+    // Read the whole file back, in case a chunk was overwritten after it was
+    // verified.
+    let expected = crc.finalize();
+    let actual = crc32fast::hash(mem.as_range(..size));
+    if actual != expected {
+        return Err(DdramError::CrcMismatch {
+            offset: 0,
+            expected,
+            actual,
+        });
+    }
+
+    debug!("CRC: {:08X}", expected);
+    Ok(expected)
+}
+
+/// Write a chunk at `offset` and read it back, retrying up to [`MAX_RETRIES`]
+/// times if its CRC32 is not `expected`.
+fn write_chunk(
+    mem: &mut impl MemoryMapper,
+    offset: usize,
+    chunk: &[u8],
+    expected: u32,
+) -> Result<(), DdramError> {
+    let range = offset..offset + chunk.len();
+    let mut attempt = 0;
+    loop {
+        mem.as_mut_range(range.clone()).copy_from_slice(chunk);
+        let actual = crc32fast::hash(mem.as_range(range.clone()));
+        if actual == expected {
+            return Ok(());
+        }
+
+        if attempt == MAX_RETRIES {
+            return Err(DdramError::CrcMismatch {
+                offset,
+                expected,
+                actual,
+            });
+        }
+        attempt += 1;
+        warn!(offset, attempt, "DDRAM read back does not match, retrying");
+    }
+}
+
+/// A memory that drops the first `failures` writes to a range starting at
+/// offset `at`.
+#[cfg(test)]
+struct FlakyMemory<'a> {
+    inner: cyclone_v::memory::RegionMemoryMapper<'a>,
+    at: usize,
+    failures: usize,
+    scratch: Vec<u8>,
+}
+
+#[cfg(test)]
+impl MemoryMapper for FlakyMemory<'_> {
+    fn create(_address: usize, _size: usize) -> Result<Self, &'static str> {
+        Err("FlakyMemory wraps an existing memory and cannot map an address")
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn as_ptr<T>(&self) -> *const T {
+        self.inner.as_ptr()
+    }
+
+    fn as_mut_ptr<T>(&mut self) -> *mut T {
+        self.inner.as_mut_ptr()
+    }
+
+    fn as_mut_range(&mut self, range: impl std::ops::RangeBounds<usize>) -> &mut [u8] {
+        if range.start_bound() == std::ops::Bound::Included(&self.at) && self.failures > 0 {
+            self.failures -= 1;
+            let len = self.inner.as_range(range).len();
+            self.scratch = vec![0; len];
+            return &mut self.scratch;
+        }
+        self.inner.as_mut_range(range)
+    }
+}
+
+#[cfg(test)]
+fn data(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 7 + i / 251) as u8).collect()
+}
+
+#[test]
+fn writes_multiple_chunks() {
+    let source = data(CHUNK_SIZE * 2 + 123);
+    let mut region = vec![0u8; source.len() + 16];
+    let mut mem = cyclone_v::memory::RegionMemoryMapper::new(&mut region);
+    let mut calls = Vec::new();
+
+    let crc = write_verified(&mut mem, source.as_slice(), source.len(), &mut |sent| {
+        calls.push(sent);
+        true
+    })
+    .unwrap();
+
+    assert_eq!(crc, crc32fast::hash(&source));
+    assert_eq!(&region[..source.len()], source.as_slice());
+    assert_eq!(&region[source.len()..], &[0; 16]);
+    assert_eq!(
+        calls,
+        [
+            CHUNK_SIZE as u32,
+            CHUNK_SIZE as u32 * 2,
+            source.len() as u32
+        ]
+    );
+}
+
+#[test]
+fn retries_on_mismatch() {
+    let source = data(CHUNK_SIZE + 10);
+    let mut region = vec![0u8; source.len()];
+    let mut mem = FlakyMemory {
+        inner: cyclone_v::memory::RegionMemoryMapper::new(&mut region),
+        at: CHUNK_SIZE,
+        failures: MAX_RETRIES,
+        scratch: Vec::new(),
+    };
+    let crc = write_verified(&mut mem, source.as_slice(), source.len(), &mut |_| true);
+    assert_eq!(crc.unwrap(), crc32fast::hash(&source));
+    assert_eq!(region, source);
+
+    let mut region = vec![0u8; source.len()];
+    let mut mem = FlakyMemory {
+        inner: cyclone_v::memory::RegionMemoryMapper::new(&mut region),
+        at: CHUNK_SIZE,
+        failures: MAX_RETRIES + 1,
+        scratch: Vec::new(),
+    };
+    assert!(matches!(
+        write_verified(&mut mem, source.as_slice(), source.len(), &mut |_| true),
+        Err(DdramError::CrcMismatch { offset, .. }) if offset == CHUNK_SIZE
+    ));
+}
+
+#[test]
+fn errors() {
+    let source = data(100);
+    let mut region = vec![0u8; 50];
+    let mut mem = cyclone_v::memory::RegionMemoryMapper::new(&mut region);
+    assert!(matches!(
+        write_verified(&mut mem, source.as_slice(), 100, &mut |_| true),
+        Err(DdramError::TooLarge {
+            size: 100,
+            capacity: 50
+        })
+    ));
+
+    let mut region = vec![0u8; 200];
+    let mut mem = cyclone_v::memory::RegionMemoryMapper::new(&mut region);
+    assert!(matches!(
+        write_verified(&mut mem, source.as_slice(), 200, &mut |_| true),
+        Err(DdramError::UnexpectedEof {
+            offset: 0,
+            size: 200
+        })
+    ));
+    assert!(matches!(
+        write_verified(&mut mem, source.as_slice(), 100, &mut |_| false),
+        Err(DdramError::Cancelled)
+    ));
+}
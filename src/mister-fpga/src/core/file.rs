@@ -3,6 +3,10 @@ use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use tracing::trace;
 
+use crate::core::cd::{CdImage, CdSectorSize};
+use crate::core::save_file::{SaveFile, SaveFileOptions};
+use crate::core::vfat::{VirtualFat, DEFAULT_FREE_SPACE};
+
 #[derive(Debug)]
 enum SdMountFileInner {
     /// A memory based sd card.
@@ -24,6 +28,15 @@ enum SdMountFileInner {
         /// as the filesystem allows.
         max_size: Option<u64>,
     },
+
+    /// A read-only CD image, read as a sequence of sectors.
+    Cd(Box<CdImage>),
+
+    /// A host directory presented as a FAT disk.
+    Fat(Box<VirtualFat>),
+
+    /// A save file, written back when the core stops writing to it.
+    Save(SaveFile),
 }
 
 impl one_fpga::core::MountedFile for SdMountFileInner {}
@@ -34,6 +47,9 @@ impl Read for SdMountFileInner {
             SdMountFileInner::Memory(data) => data.read(buf),
             SdMountFileInner::File { f: Some(f), .. } => f.read(buf),
             SdMountFileInner::File { .. } => Ok(0),
+            SdMountFileInner::Cd(image) => image.read(buf),
+            SdMountFileInner::Fat(disk) => disk.read(buf),
+            SdMountFileInner::Save(save) => save.read(buf),
         }
     }
 }
@@ -81,6 +97,9 @@ impl Write for SdMountFileInner {
                 result
             }
             SdMountFileInner::File { .. } => Err(std::io::Error::other("File is not writable")),
+            SdMountFileInner::Cd(image) => image.write(buf),
+            SdMountFileInner::Fat(disk) => disk.write(buf),
+            SdMountFileInner::Save(save) => save.write(buf),
         }
     }
 
@@ -89,6 +108,9 @@ impl Write for SdMountFileInner {
             SdMountFileInner::Memory(data) => data.flush(),
             SdMountFileInner::File { f: Some(f), .. } => f.flush(),
             SdMountFileInner::File { .. } => Ok(()),
+            SdMountFileInner::Cd(image) => image.flush(),
+            SdMountFileInner::Fat(disk) => disk.flush(),
+            SdMountFileInner::Save(save) => save.flush(),
         }
     }
 }
@@ -114,6 +136,9 @@ impl Seek for SdMountFileInner {
                 result
             }
             SdMountFileInner::File { .. } => Err(std::io::Error::other("File is not writable")),
+            SdMountFileInner::Cd(image) => image.seek(pos),
+            SdMountFileInner::Fat(disk) => disk.seek(pos),
+            SdMountFileInner::Save(save) => save.seek(pos),
         }
     }
 }
@@ -129,8 +154,22 @@ pub struct SdCard {
 }
 
 impl SdCard {
+    /// Open a file to mount. CUE sheets, CHD and ISO files are opened as CD
+    /// images (see [`Self::from_cd_image`]), and directories as FAT disks
+    /// with [`DEFAULT_FREE_SPACE`] bytes free (see [`Self::from_directory`]).
     pub fn from_path(path: impl AsRef<Path>) -> Result<Self, String> {
         let path = path.as_ref().to_path_buf();
+        if path.is_dir() {
+            let disk = VirtualFat::with_free_space(&path, DEFAULT_FREE_SPACE)
+                .map_err(|e| format!("Failed to create FAT disk: {e}"))?;
+            return Ok(Self::from_directory(disk));
+        }
+        if CdImage::is_cd_image(&path) {
+            let image =
+                CdImage::open(&path).map_err(|e| format!("Failed to open CD image: {e}"))?;
+            return Ok(Self::from_cd_image(image));
+        }
+
         let mut writeable = true;
         let file = if !path.exists() {
             std::fs::create_dir_all(path.parent().unwrap())
@@ -163,6 +202,66 @@ impl SdCard {
         }
     }
 
+    /// A read-only CD image. Its sectors are served in the block size the core
+    /// reads (see [`Self::set_block_size`]).
+    pub fn from_cd_image(image: CdImage) -> Self {
+        Self {
+            writeable: false,
+            inner: SdMountFileInner::Cd(Box::new(image)),
+        }
+    }
+
+    /// A host directory presented as a FAT disk. Changes made by the core are
+    /// written back to the directory when the card is flushed.
+    pub fn from_directory(disk: VirtualFat) -> Self {
+        Self {
+            writeable: true,
+            inner: SdMountFileInner::Fat(Box::new(disk)),
+        }
+    }
+
+    /// A save file. Writes from the core are written back to the file once
+    /// the core stops writing for a while (see [`Self::flush_if_idle`]).
+    pub fn from_save_file(
+        path: impl AsRef<Path>,
+        options: SaveFileOptions,
+    ) -> Result<Self, String> {
+        let save =
+            SaveFile::open(path, options).map_err(|e| format!("Failed to open save file: {e}"))?;
+        Ok(Self {
+            writeable: true,
+            inner: SdMountFileInner::Save(save),
+        })
+    }
+
+    /// Write a save file back if the core stopped writing to it. Other cards
+    /// are written as the core writes to them.
+    pub fn flush_if_idle(&mut self, now: std::time::Instant) -> std::io::Result<bool> {
+        match &mut self.inner {
+            SdMountFileInner::Save(save) => save.flush_if_idle(now),
+            _ => Ok(false),
+        }
+    }
+
+    /// The CD image mounted, if this is one.
+    pub fn cd_image(&self) -> Option<&CdImage> {
+        match &self.inner {
+            SdMountFileInner::Cd(image) => Some(image),
+            _ => None,
+        }
+    }
+
+    /// Set the size of the blocks the core reads. CD images serve raw (2352
+    /// bytes) or data (2048 bytes) sectors depending on it, other files are
+    /// flat and ignore it.
+    pub fn set_block_size(&mut self, block_size: usize) {
+        if let SdMountFileInner::Cd(image) = &mut self.inner {
+            if let Some(size) = CdSectorSize::from_block_size(block_size) {
+                image.set_sector_size(size);
+            }
+        }
+    }
+
     pub fn writeable(&self) -> bool {
         self.writeable
     }
@@ -172,6 +271,9 @@ impl SdCard {
             SdMountFileInner::Memory(data) => data.get_ref().len() as u64,
             SdMountFileInner::File { f: Some(f), .. } => f.metadata().map(|m| m.len()).unwrap_or(0),
             SdMountFileInner::File { .. } => 0,
+            SdMountFileInner::Cd(image) => image.size(),
+            SdMountFileInner::Fat(disk) => disk.size(),
+            SdMountFileInner::Save(save) => save.size(),
         }
     }
 
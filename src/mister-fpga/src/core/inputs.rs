@@ -0,0 +1,436 @@
+use one_fpga::core::Error;
+use one_fpga::inputs::gamepad::ButtonSet;
+use one_fpga::inputs::keyboard::ScancodeSet;
+use one_fpga::inputs::{Axis, Button, MouseButton, Scancode};
+use strum::IntoEnumIterator;
+use tracing::debug;
+
+use crate::core::buttons::{scale_axis, ButtonMap, ANALOG_DEADZONE, TRIGGER_THRESHOLD};
+use crate::core::mouse::MouseState;
+use crate::fpga::user_io::{
+    AnalogStick, UserIoAnalogJoystick, UserIoJoystick, UserIoKeyboardKeyDown, UserIoKeyboardKeyUp,
+    UserIoMouse,
+};
+use crate::fpga::{SpiCommand, SpiCommandExt};
+use crate::keyboard::Ps2Scancode;
+
+/// Number of gamepads supported by the MiSTer API.
+pub const GAMEPAD_COUNT: usize = 6;
+
+/// The state of all inputs (keyboard, gamepads and mouse) sent to a core. This
+/// keeps track of what is pressed, and sends the SPI commands when it changes.
+#[derive(Debug, Clone)]
+pub struct CoreInputs {
+    keys: ScancodeSet,
+
+    // Pressed buttons, and their mapping to the core bits.
+    buttons: [ButtonSet; GAMEPAD_COUNT],
+    gamepads: [ButtonMap; GAMEPAD_COUNT],
+
+    // Last position sent for the (left, right) analog sticks of each gamepad.
+    analog: [[(i8, i8); 2]; GAMEPAD_COUNT],
+
+    // Core button bits that analog triggers (left, right) are mapped to, if any.
+    trigger_buttons: [Option<u8>; 2],
+
+    mouse: MouseState,
+
+    io_version: u8,
+}
+
+impl CoreInputs {
+    pub fn new(map: ButtonMap, mouse: MouseState, io_version: u8) -> Self {
+        Self {
+            keys: ScancodeSet::new(),
+            buttons: [ButtonSet::new(); GAMEPAD_COUNT],
+            gamepads: [map; GAMEPAD_COUNT],
+            analog: Default::default(),
+            trigger_buttons: [None; 2],
+            mouse,
+            io_version,
+        }
+    }
+
+    /// Set the core button bits that the left and right analog triggers are
+    /// mapped to.
+    pub fn with_trigger_buttons(self, trigger_buttons: [Option<u8>; 2]) -> Self {
+        Self {
+            trigger_buttons,
+            ..self
+        }
+    }
+
+    pub fn key_down(&mut self, spi: &mut impl SpiCommandExt, key: Scancode) -> Result<(), Error> {
+        let scancode = Ps2Scancode::from(key);
+        debug!(?key, ?scancode, "Keydown");
+        self.keys.insert(key);
+        if scancode != Ps2Scancode::None {
+            UserIoKeyboardKeyDown::from(scancode).execute(spi)?;
+        }
+        Ok(())
+    }
+
+    pub fn key_up(&mut self, spi: &mut impl SpiCommandExt, key: Scancode) -> Result<(), Error> {
+        let scancode = Ps2Scancode::from(key);
+        debug!(?key, ?scancode, "Keyup");
+        self.keys.remove(key);
+        if scancode != Ps2Scancode::None {
+            UserIoKeyboardKeyUp::from(scancode).execute(spi)?;
+        }
+        Ok(())
+    }
+
+    /// Press the keys in `keys` and release all others.
+    pub fn keys_set(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        keys: &ScancodeSet,
+    ) -> Result<(), Error> {
+        let released = self
+            .keys
+            .iter()
+            .filter(|k| !keys.contains(*k))
+            .collect::<Vec<_>>();
+        for key in released {
+            self.key_up(spi, key)?;
+        }
+
+        let pressed = keys
+            .iter()
+            .filter(|k| !self.keys.contains(*k))
+            .collect::<Vec<_>>();
+        for key in pressed {
+            self.key_down(spi, key)?;
+        }
+        Ok(())
+    }
+
+    pub fn keys(&self) -> &ScancodeSet {
+        &self.keys
+    }
+
+    pub fn gamepad(&self, idx: u8) -> Option<&ButtonMap> {
+        self.gamepads.get(idx as usize)
+    }
+
+    pub fn gamepad_mut(&mut self, idx: u8) -> Option<&mut ButtonMap> {
+        self.gamepads.get_mut(idx as usize)
+    }
+
+    /// Replace the button map of a gamepad and send it to the core.
+    pub fn send_gamepad(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        idx: u8,
+        map: ButtonMap,
+    ) -> Result<(), Error> {
+        let g = self
+            .gamepads
+            .get_mut(idx as usize)
+            .ok_or(Error::InvalidSlot(idx as usize))?;
+        *g = map;
+        UserIoJoystick::from_joystick_index(idx, g).execute(spi)?;
+        Ok(())
+    }
+
+    pub fn gamepad_button_down(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        idx: u8,
+        button: Button,
+    ) -> Result<(), Error> {
+        let mut buttons = self
+            .gamepad_buttons(idx)
+            .ok_or(Error::InvalidSlot(idx as usize))?;
+        buttons.insert(button);
+        self.gamepad_buttons_set(spi, idx, buttons)
+    }
+
+    pub fn gamepad_button_up(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        idx: u8,
+        button: Button,
+    ) -> Result<(), Error> {
+        let mut buttons = self
+            .gamepad_buttons(idx)
+            .ok_or(Error::InvalidSlot(idx as usize))?;
+        buttons.remove(button);
+        self.gamepad_buttons_set(spi, idx, buttons)
+    }
+
+    /// Press the buttons in `buttons` and release all others, then send the new
+    /// state of the gamepad to the core.
+    pub fn gamepad_buttons_set(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        idx: u8,
+        buttons: ButtonSet,
+    ) -> Result<(), Error> {
+        let i = idx as usize;
+        if i >= GAMEPAD_COUNT {
+            return Err(Error::InvalidSlot(idx as usize));
+        }
+
+        let g = &mut self.gamepads[i];
+        for button in Button::iter() {
+            match (self.buttons[i].contains(button), buttons.contains(button)) {
+                (false, true) => {
+                    g.down(button.as_repr());
+                }
+                (true, false) => {
+                    g.up(button.as_repr());
+                }
+                _ => {}
+            }
+        }
+        self.buttons[i] = buttons;
+
+        UserIoJoystick::from_joystick_index(idx, g).execute(spi)?;
+        Ok(())
+    }
+
+    pub fn gamepad_buttons(&self, idx: u8) -> Option<ButtonSet> {
+        self.buttons.get(idx as usize).copied()
+    }
+
+    /// Notify the core of an analog axis motion on a gamepad. Sticks are sent with
+    /// the analog joystick commands, while triggers are sent as digital presses of
+    /// the core's `L2`/`R2` buttons (if it has any).
+    pub fn gamepad_axis_motion(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        idx: u8,
+        axis: Axis,
+        value: i16,
+    ) -> Result<(), Error> {
+        let i = idx as usize;
+        if i >= GAMEPAD_COUNT {
+            return Ok(());
+        }
+
+        if axis.is_trigger() {
+            let bit = if axis == Axis::TRIGGER_LEFT {
+                self.trigger_buttons[0]
+            } else {
+                self.trigger_buttons[1]
+            };
+            let Some(bit) = bit else {
+                return Ok(());
+            };
+
+            let g = &mut self.gamepads[i];
+            let before = g.value();
+            let after = if value >= TRIGGER_THRESHOLD {
+                before | (1 << bit)
+            } else {
+                before & !(1 << bit)
+            };
+            if before != after {
+                g.set(after);
+                UserIoJoystick::from_joystick_index(idx, g).execute(spi)?;
+            }
+            return Ok(());
+        }
+
+        let (stick, position) = match axis {
+            Axis::LEFT_X | Axis::LEFT_Y => (AnalogStick::Left, &mut self.analog[i][0]),
+            Axis::RIGHT_X | Axis::RIGHT_Y => (AnalogStick::Right, &mut self.analog[i][1]),
+            _ => return Ok(()),
+        };
+
+        let before = *position;
+        let value = scale_axis(value, ANALOG_DEADZONE);
+        if axis == Axis::LEFT_X || axis == Axis::RIGHT_X {
+            position.0 = value;
+        } else {
+            position.1 = value;
+        }
+
+        if before != *position {
+            let (x, y) = *position;
+            UserIoAnalogJoystick::new(idx, stick, x, y)
+                .with_io_version(self.io_version)
+                .execute(spi)?;
+        }
+        Ok(())
+    }
+
+    /// Send all pending mouse packets to the core.
+    fn send_mouse_packets(&mut self, spi: &mut impl SpiCommandExt) -> Result<(), Error> {
+        while let Some(packet) = self.mouse.next_packet() {
+            UserIoMouse::from(packet).execute(spi)?;
+        }
+        Ok(())
+    }
+
+    pub fn mouse_move(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        dx: i32,
+        dy: i32,
+    ) -> Result<(), Error> {
+        self.mouse.move_by(dx, dy);
+        self.send_mouse_packets(spi)
+    }
+
+    pub fn mouse_wheel(&mut self, spi: &mut impl SpiCommandExt, delta: i32) -> Result<(), Error> {
+        self.mouse.wheel(delta);
+        self.send_mouse_packets(spi)
+    }
+
+    pub fn mouse_button_down(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        button: MouseButton,
+    ) -> Result<(), Error> {
+        self.mouse.button_down(button);
+        self.send_mouse_packets(spi)
+    }
+
+    pub fn mouse_button_up(
+        &mut self,
+        spi: &mut impl SpiCommandExt,
+        button: MouseButton,
+    ) -> Result<(), Error> {
+        self.mouse.button_up(button);
+        self.send_mouse_packets(spi)
+    }
+}
+
+#[cfg(test)]
+fn key(name: &str) -> Scancode {
+    name.parse().unwrap()
+}
+
+#[cfg(test)]
+fn test_inputs() -> CoreInputs {
+    CoreInputs::new(ButtonMap::new(), MouseState::new(1), 1).with_trigger_buttons([Some(8), None])
+}
+
+#[test]
+fn inputs_keys() {
+    use crate::fpga::FakeSpi;
+    let mut spi = FakeSpi::default();
+    let mut inputs = test_inputs();
+
+    inputs.key_down(&mut spi, key("A")).unwrap();
+    inputs.key_down(&mut spi, key("B")).unwrap();
+    assert!(inputs.keys().contains(key("A")));
+    assert_eq!(spi.commands.len(), 2);
+
+    inputs.key_up(&mut spi, key("A")).unwrap();
+    assert!(!inputs.keys().contains(key("A")));
+    assert_eq!(spi.commands.len(), 3);
+
+    let mut keys = ScancodeSet::new();
+    keys.insert(key("C"));
+    spi.commands.clear();
+    inputs.keys_set(&mut spi, &keys).unwrap();
+    assert_eq!(inputs.keys(), &keys);
+    // Release B, press C.
+    assert_eq!(spi.commands.len(), 2);
+
+    spi.commands.clear();
+    inputs.keys_set(&mut spi, &keys).unwrap();
+    assert!(spi.commands.is_empty());
+}
+
+#[test]
+fn inputs_gamepad_buttons() {
+    use crate::fpga::FakeSpi;
+    let mut spi = FakeSpi::default();
+    let mut inputs = test_inputs();
+
+    inputs
+        .gamepad_button_down(&mut spi, 1, Button::DPadUp)
+        .unwrap();
+    let buttons = inputs.gamepad_buttons(1).unwrap();
+    assert!(buttons.contains(Button::DPadUp));
+    assert_ne!(inputs.gamepad(1).unwrap().value(), 0);
+    assert_eq!(spi.commands.len(), 1);
+
+    inputs
+        .gamepad_button_up(&mut spi, 1, Button::DPadUp)
+        .unwrap();
+    assert_eq!(inputs.gamepad_buttons(1), Some(ButtonSet::new()));
+    assert_eq!(inputs.gamepad(1).unwrap().value(), 0);
+
+    let mut set = ButtonSet::new();
+    set.insert(Button::A);
+    set.insert(Button::Start);
+    inputs.gamepad_buttons_set(&mut spi, 2, set).unwrap();
+    assert_eq!(inputs.gamepad_buttons(2), Some(set));
+    inputs
+        .gamepad_buttons_set(&mut spi, 2, ButtonSet::new())
+        .unwrap();
+    assert_eq!(inputs.gamepad(2).unwrap().value(), 0);
+
+    assert_eq!(inputs.gamepad_buttons(GAMEPAD_COUNT as u8), None);
+    assert!(inputs
+        .gamepad_button_down(&mut spi, GAMEPAD_COUNT as u8, Button::A)
+        .is_err());
+}
+
+#[test]
+fn inputs_gamepad_axis() {
+    use crate::fpga::FakeSpi;
+    let mut spi = FakeSpi::default();
+    let mut inputs = test_inputs();
+
+    // Within the deadzone, the stick is still centered and nothing is sent.
+    inputs
+        .gamepad_axis_motion(&mut spi, 0, Axis::LEFT_X, 100)
+        .unwrap();
+    assert!(spi.commands.is_empty());
+    inputs
+        .gamepad_axis_motion(&mut spi, 0, Axis::LEFT_X, i16::MAX)
+        .unwrap();
+    assert_eq!(spi.commands.len(), 1);
+
+    inputs
+        .gamepad_axis_motion(&mut spi, 0, Axis::TRIGGER_LEFT, i16::MAX)
+        .unwrap();
+    assert_eq!(inputs.gamepad(0).unwrap().value(), 1 << 8);
+    inputs
+        .gamepad_axis_motion(&mut spi, 0, Axis::TRIGGER_LEFT, 0)
+        .unwrap();
+    assert_eq!(inputs.gamepad(0).unwrap().value(), 0);
+    assert_eq!(spi.commands.len(), 3);
+
+    // The right trigger is not mapped.
+    inputs
+        .gamepad_axis_motion(&mut spi, 0, Axis::TRIGGER_RIGHT, i16::MAX)
+        .unwrap();
+    assert_eq!(spi.commands.len(), 3);
+}
+
+#[test]
+fn inputs_mouse() {
+    use crate::fpga::FakeSpi;
+    let mut spi = FakeSpi::default();
+    let mut inputs = test_inputs();
+
+    inputs.mouse_move(&mut spi, 10, -10).unwrap();
+    inputs.mouse_wheel(&mut spi, 1).unwrap();
+    inputs
+        .mouse_button_down(&mut spi, MouseButton::Left)
+        .unwrap();
+    inputs.mouse_button_up(&mut spi, MouseButton::Left).unwrap();
+    assert_eq!(spi.commands.len(), 4);
+
+    let (command, words) = &spi.commands[2];
+    assert_eq!(*command, 0x04);
+    assert_eq!(words[0] & 0xFF, 0x09);
+}
+
+#[test]
+fn inputs_invalid_gamepad() {
+    use crate::fpga::FakeSpi;
+    let mut spi = FakeSpi::default();
+    let mut inputs = test_inputs();
+
+    let result = inputs.gamepad_button_down(&mut spi, GAMEPAD_COUNT as u8, Button::A);
+    assert!(matches!(result, Err(Error::InvalidSlot(GAMEPAD_COUNT))));
+    assert!(spi.commands.is_empty());
+}
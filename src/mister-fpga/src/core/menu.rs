@@ -4,12 +4,15 @@ use crate::fpga::MisterFpga;
 use crate::types::units::UnitConversion;
 use cyclone_v::memory::{DevMemMemoryMapper, MemoryMapper};
 use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
-use one_fpga::core::{Bios, CoreSettings, Error, MountedFile, Rom, SaveState, SettingId};
+use one_fpga::core::{
+    Bios, CoreCapabilities, CoreEvent, CoreSettings, Error, MountedFile, Rom, SaveState, SettingId,
+};
 use one_fpga::inputs::gamepad::ButtonSet;
 use one_fpga::inputs::keyboard::ScancodeSet;
-use one_fpga::inputs::Button;
 use one_fpga::inputs::Scancode;
+use one_fpga::inputs::{Axis, Button, MouseButton};
 use one_fpga::Core;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 pub struct MenuCore {
@@ -19,36 +22,50 @@ pub struct MenuCore {
 
 impl MenuCore {
     #[inline]
-    fn image_buffer(&mut self) -> Result<ImageBuffer<Rgba<u8>, &mut [u8]>, String> {
+    fn image_buffer(&mut self) -> Result<ImageBuffer<Rgba<u8>, &mut [u8]>, Error> {
         let menu_fb_size = self.inner.video_info()?.fb_resolution();
 
-        Ok(ImageBuffer::<Rgba<u8>, _>::from_raw(
+        ImageBuffer::<Rgba<u8>, _>::from_raw(
             menu_fb_size.width as u32,
             menu_fb_size.height as u32,
             self.menu_fb_mapper.as_mut_range(..),
         )
-        .unwrap())
+        .ok_or_else(|| Error::Protocol("Invalid menu framebuffer size".to_string()))
     }
 
-    pub fn new(inner: MisterFpga) -> Result<Self, String> {
+    pub fn new(inner: MisterFpga) -> Result<Self, Error> {
         let mut inner = MisterFpgaCore::new(inner)?;
         inner.is_menu = true;
 
         let fb_base: usize = cyclone_v::ranges::HOST_MEMORY.start + 32.mebibytes();
         let fb_addr = fb_base + (1920 * 1080) * 4;
-        let menu_fb_mapper = DevMemMemoryMapper::create(fb_addr, 1920 * 1080 * 4)?;
+        let menu_fb_mapper = DevMemMemoryMapper::create(fb_addr, 1920 * 1080 * 4)
+            .map_err(|e| Error::IoError(std::io::Error::other(e)))?;
 
-        Ok(Self {
+        Ok(Self::from_core(inner, menu_fb_mapper))
+    }
+
+    fn from_core(inner: MisterFpgaCore, menu_fb_mapper: DevMemMemoryMapper) -> Self {
+        Self {
             inner,
             menu_fb_mapper,
-        })
+        }
+    }
+
+    /// Create a menu core over a fake FPGA (see [`MisterFpgaCore::fake`]).
+    #[cfg(test)]
+    fn fake() -> (Self, std::rc::Rc<std::cell::RefCell<crate::fpga::FakeSpi>>) {
+        let (mut inner, spi) = MisterFpgaCore::fake("MENU;;V,v1");
+        inner.is_menu = true;
+        let menu_fb_mapper = DevMemMemoryMapper::anonymous(1920 * 1080 * 4).unwrap();
+        (Self::from_core(inner, menu_fb_mapper), spi)
     }
 
-    pub fn video_info(&mut self) -> Result<VideoInfo, String> {
+    pub fn video_info(&mut self) -> Result<VideoInfo, Error> {
         self.inner.video_info()
     }
 
-    pub fn clear_framebuffer(&mut self) -> Result<(), String> {
+    pub fn clear_framebuffer(&mut self) -> Result<(), Error> {
         self.image_buffer()?.fill(0);
         Ok(())
     }
@@ -57,7 +74,7 @@ impl MenuCore {
         &mut self,
         image: &impl GenericImageView<Pixel = Rgba<u8>>,
         position: (i64, i64),
-    ) -> Result<(), String> {
+    ) -> Result<(), Error> {
         self.inner.framebuffer.update_type_from_core();
 
         let mut dest = self.image_buffer()?;
@@ -80,9 +97,19 @@ impl Core for MenuCore {
         "MENU"
     }
 
+    fn capabilities(&self) -> Result<CoreCapabilities, Error> {
+        Ok(CoreCapabilities {
+            screenshot: true,
+            keyboard: Some(false),
+            mouse: Some(false),
+            analog: Some(false),
+            rtc: Some(true),
+            ..Default::default()
+        })
+    }
+
     fn reset(&mut self) -> Result<(), Error> {
-        self.inner.soft_reset();
-        Ok(())
+        self.inner.soft_reset()
     }
 
     fn volume(&self) -> Result<u8, Error> {
@@ -102,75 +129,121 @@ impl Core for MenuCore {
     }
 
     fn save_state_mut(&mut self, _slot: usize) -> Result<Option<&mut dyn SaveState>, Error> {
-        unreachable!("Menu core does not support save states")
+        Ok(None)
     }
 
     fn save_state(&self, _slot: usize) -> Result<Option<&dyn SaveState>, Error> {
-        unreachable!("Menu core does not support save states")
+        Ok(None)
     }
 
     fn mounted_file_mut(&mut self, _slot: usize) -> Result<Option<&mut dyn MountedFile>, Error> {
-        unreachable!("Menu core does not support mounted files")
+        Ok(None)
+    }
+
+    fn insert_image(&mut self, _slot: usize, _path: &Path) -> Result<(), Error> {
+        Err(Error::unsupported("Menu core does not support disk images"))
+    }
+
+    fn eject_image(&mut self, _slot: usize) -> Result<(), Error> {
+        Err(Error::unsupported("Menu core does not support disk images"))
+    }
+
+    fn next_disk(&mut self, _slot: usize) -> Result<Option<PathBuf>, Error> {
+        Ok(None)
     }
 
     fn send_rom(&mut self, _rom: Rom) -> Result<(), Error> {
-        unreachable!("Menu core does not support ROMs")
+        Err(Error::unsupported("Menu core does not support ROMs"))
     }
 
     fn send_bios(&mut self, _bios: Bios) -> Result<(), Error> {
-        unreachable!("Menu core does not support BIOS")
+        Err(Error::unsupported("Menu core does not support BIOS"))
     }
 
+    // The menu core has no inputs; they are all ignored.
     fn key_up(&mut self, _key: Scancode) -> Result<(), Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(())
     }
 
     fn key_down(&mut self, _key: Scancode) -> Result<(), Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(())
     }
 
     fn keys_set(&mut self, _keys: ScancodeSet) -> Result<(), Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(())
     }
 
     fn keys(&self) -> Result<ScancodeSet, Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(ScancodeSet::new())
     }
 
     fn gamepad_button_up(&mut self, _index: usize, _button: Button) -> Result<(), Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(())
     }
 
     fn gamepad_button_down(&mut self, _index: usize, _button: Button) -> Result<(), Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(())
     }
 
     fn gamepad_buttons_set(&mut self, _index: usize, _buttons: ButtonSet) -> Result<(), Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(())
     }
 
     fn gamepad_buttons(&self, _index: usize) -> Result<Option<ButtonSet>, Error> {
-        unreachable!("Menu core does not support inputs")
+        Ok(None)
+    }
+
+    fn gamepad_axis_motion(
+        &mut self,
+        _index: usize,
+        _axis: Axis,
+        _value: i16,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_move(&mut self, _dx: i32, _dy: i32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_wheel(&mut self, _delta: i32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_button_up(&mut self, _button: MouseButton) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_button_down(&mut self, _button: MouseButton) -> Result<(), Error> {
+        Ok(())
     }
 
     fn settings(&self) -> Result<CoreSettings, Error> {
-        unreachable!("Menu core does not have a core menu")
+        Ok(CoreSettings::new(self.name().to_string(), vec![]))
     }
 
     fn trigger(&mut self, _id: SettingId) -> Result<(), Error> {
-        todo!()
+        Err(Error::unsupported(
+            "Menu core does not support core settings",
+        ))
     }
 
     fn file_select(&mut self, _id: SettingId, _path: String) -> Result<(), Error> {
-        todo!()
+        Err(Error::unsupported(
+            "Menu core does not support core settings",
+        ))
     }
 
     fn int_option(&mut self, _id: SettingId, _value: u32) -> Result<u32, Error> {
-        todo!()
+        Err(Error::unsupported(
+            "Menu core does not support core settings",
+        ))
     }
 
     fn bool_option(&mut self, _id: SettingId, _value: bool) -> Result<bool, Error> {
-        todo!()
+        Err(Error::unsupported(
+            "Menu core does not support core settings",
+        ))
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -186,4 +259,64 @@ impl Core for MenuCore {
     fn should_quit(&self) -> bool {
         false
     }
+
+    fn poll_events(&mut self) -> Result<Vec<CoreEvent>, Error> {
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn core_methods() {
+    let (mut core, spi) = MenuCore::fake();
+    assert_eq!(core.name(), "MENU");
+    let capabilities = core.capabilities().unwrap();
+    assert!(capabilities.screenshot);
+    assert_eq!(capabilities.rtc, Some(true));
+    assert_eq!(capabilities.keyboard, Some(false));
+
+    // Volume, RTC and resets go to the menu core.
+    core.set_volume(200).unwrap();
+    assert_eq!(core.volume().unwrap(), 200);
+    core.set_rtc(SystemTime::UNIX_EPOCH).unwrap();
+    core.reset().unwrap();
+    let commands = spi
+        .borrow()
+        .commands
+        .iter()
+        .map(|(c, _)| *c)
+        .collect::<Vec<_>>();
+    assert_eq!(&commands[..2], &[0x26, 0x22]);
+    assert!(commands[2..].contains(&0x1E));
+
+    // Everything else is not supported by the menu.
+    spi.borrow_mut().commands.clear();
+    assert!(matches!(
+        core.send_rom(Rom::File(PathBuf::from("game.bin"))),
+        Err(Error::Unsupported(_))
+    ));
+    assert!(matches!(
+        core.insert_image(0, Path::new("disk.img")),
+        Err(Error::Unsupported(_))
+    ));
+    assert!(matches!(
+        core.bool_option(SettingId::from_label("Option"), true),
+        Err(Error::Unsupported(_))
+    ));
+    assert_eq!(core.gamepad_buttons(0).unwrap(), None);
+    assert_eq!(core.next_disk(0).unwrap(), None);
+    assert!(core.save_state(0).unwrap().is_none());
+    assert!(core.settings().unwrap().items().is_empty());
+
+    // Inputs are ignored.
+    core.key_down("A".parse().unwrap()).unwrap();
+    core.keys_set(ScancodeSet::new()).unwrap();
+    assert_eq!(core.keys().unwrap(), ScancodeSet::new());
+    core.gamepad_button_down(0, Button::A).unwrap();
+    core.gamepad_buttons_set(0, ButtonSet::new()).unwrap();
+    core.gamepad_axis_motion(0, Axis::LEFT_X, 100).unwrap();
+    core.mouse_move(1, 1).unwrap();
+    core.mouse_wheel(1).unwrap();
+    core.mouse_button_down(MouseButton::Left).unwrap();
+    assert!(spi.borrow().commands.is_empty());
+    assert!(core.poll_events().unwrap().is_empty());
 }
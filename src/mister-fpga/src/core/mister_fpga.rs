@@ -3,39 +3,76 @@ use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::Path;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
 
 use image::DynamicImage;
-use tracing::{debug, info, trace};
+use serde::Serialize;
+use tracing::{debug, info, trace, warn};
 
 use cyclone_v::memory::{DevMemMemoryMapper, MemoryMapper};
+use mister_fpga_ini::resolution::Resolution;
 use mister_fpga_ini::{Config, HdmiLimitedConfig, VgaMode};
-use one_fpga::core::{Bios, CoreSettings, Error, MountedFile, Rom, SaveState, SettingId};
+use one_fpga::core::rom::split_archive_path;
+use one_fpga::core::{
+    Bios, CoreCapabilities, CoreEvent, CoreSettings, Error, MountedFile, Playlist, Rom, SaveState,
+    SettingId,
+};
 use one_fpga::inputs::gamepad::ButtonSet;
 use one_fpga::inputs::keyboard::ScancodeSet;
-use one_fpga::inputs::{Button, Scancode};
+use one_fpga::inputs::{Axis, Button, MouseButton, Scancode};
 use one_fpga::Core;
 
+use crate::archive;
+use crate::cheats::{Cheats, CHEATS_INDEX, CHEAT_CODE_SIZE};
 use crate::config_string;
 use crate::config_string::{ConfigMenu, FpgaRamMemoryAddress, LoadFileInfo};
 use crate::core::buttons::ButtonMap;
+use crate::core::cd::{CdImage, Toc};
+use crate::core::ddram;
 use crate::core::file::SdCard;
+use crate::core::inputs::{CoreInputs, GAMEPAD_COUNT};
+use crate::core::mouse::MouseState;
 use crate::core::video;
 use crate::core::video::VideoInfo;
 use crate::core::volume::{IntoVolume, Volume};
+use crate::dip::{DipSwitches, DIP_SWITCHES_INDEX};
 use crate::fpga::file_io::{
     FileExtension, FileIndex, FileTxData16Bits, FileTxData8Bits, FileTxDisabled, FileTxEnabled,
 };
 use crate::fpga::user_io::{
     ButtonSwitches, GetSdStat, GetStatusBits, SdRead, SdStatOutput, SdWrite, SetSdConf, SetSdInfo,
-    SetSdStat, SetStatusBits, UserIoButtonSwitch, UserIoJoystick, UserIoKeyboardKeyDown,
-    UserIoKeyboardKeyUp, UserIoRtc,
+    SetSdStat, SetStatusBits, UserIoButtonSwitch, UserIoRtc,
 };
 use crate::fpga::{user_io, CoreInterfaceType, CoreType, MisterFpga};
-use crate::keyboard::Ps2Scancode;
+use crate::mra::{Mra, MraButtons, RomArchives};
+use crate::remember::RememberedFiles;
 use crate::savestate::SaveStateManager;
 use crate::types::StatusBitMap;
+use crate::uart::{UartBridge, UartEndpoint, UartMode, UartSettings, HPS_UART_PATH};
+
+/// The progress of a file being sent to the core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TransferProgress {
+    /// The file index the file is sent on.
+    pub index: u8,
+
+    /// The number of bytes sent so far.
+    pub sent: u32,
+
+    /// The size of the file, in bytes.
+    pub total: u32,
+}
+
+/// A callback called while a file is sent to the core. Returning `false` cancels
+/// the transfer, which then fails with [`Error::Cancelled`].
+pub type TransferCallback<'a> = dyn FnMut(&TransferProgress) -> bool + 'a;
+
+/// The size of a file to send to the core, which is sent as 32 bits.
+fn transfer_size(size: u64) -> Result<u32, Error> {
+    u32::try_from(size)
+        .map_err(|_| Error::unsupported(format!("File of {size} bytes is too large")))
+}
 
 #[derive(Debug)]
 pub enum MisterFpgaSendFileInfo {
@@ -49,7 +86,7 @@ pub enum MisterFpgaSendFileInfo {
 }
 
 impl MisterFpgaSendFileInfo {
-    pub fn from_file_info(file_info: LoadFileInfo) -> Result<Self, String> {
+    pub fn from_file_info(file_info: LoadFileInfo) -> Result<Self, Error> {
         let index = file_info.index;
         match file_info.address {
             None => Ok(Self::Buffered { index }),
@@ -57,11 +94,19 @@ impl MisterFpgaSendFileInfo {
         }
     }
 
-    pub fn from_path(path: impl AsRef<Path>, core: &MisterFpgaCore) -> Result<Self, String> {
+    pub fn from_path(path: impl AsRef<Path>, core: &MisterFpgaCore) -> Result<Self, Error> {
         let info = core
             .config
             .load_info(&path)?
-            .ok_or_else(|| format!("Could not find info for {:?}", path.as_ref()))?;
+            .ok_or_else(|| Error::unsupported(format!("No file slot for {:?}", path.as_ref())))?;
+        Self::from_file_info(info)
+    }
+
+    pub fn from_extension(ext: &str, core: &MisterFpgaCore) -> Result<Self, Error> {
+        let info = core
+            .config
+            .load_info_for_extension(ext)?
+            .ok_or_else(|| Error::unsupported(format!("No file slot for extension {:?}", ext)))?;
         Self::from_file_info(info)
     }
 
@@ -86,12 +131,43 @@ pub struct MisterFpgaCore {
     // All the images that are mounted. Can only have 16 images at once.
     cards: Box<[Option<SdCard>; 16]>,
 
+    // The playlists of the multi-disk games inserted, by card index.
+    playlists: Box<[Option<Playlist>; 16]>,
+
     save_states: Option<SaveStateManager<DevMemMemoryMapper>>,
-    gamepads: [ButtonMap; 6],
+    inputs: CoreInputs,
 
     status: StatusBitMap,
     status_counter: u8,
 
+    // Where to save the option bits when they change.
+    status_file: Option<PathBuf>,
+
+    dip_switches: DipSwitches,
+
+    // Where to save the DIP switches when they change.
+    dip_file: Option<PathBuf>,
+
+    cheats: Cheats,
+
+    // Files chosen for `FC` entries, and where to save them when they change.
+    remembered_files: RememberedFiles,
+    remember_file: Option<PathBuf>,
+
+    // The UART mode selected, and the bridge to Linux if it is enabled.
+    uart: UartSettings,
+    uart_endpoint: Option<UartEndpoint>,
+    uart_bridge: Option<UartBridge>,
+
+    // Events to return on the next poll.
+    events: Vec<CoreEvent>,
+
+    // The CRC32 of the last file sent to the core, used to find cheats.
+    file_crc: Option<u32>,
+
+    // The last video mode seen, to detect changes.
+    video_mode: Option<(Resolution, bool)>,
+
     pub(crate) framebuffer: crate::framebuffer::FpgaFramebuffer<DevMemMemoryMapper>,
 
     // Whether we should quit.
@@ -99,11 +175,37 @@ pub struct MisterFpgaCore {
 }
 
 impl MisterFpgaCore {
-    pub fn new(mut fpga: MisterFpga) -> Result<Self, String> {
+    pub fn new(mut fpga: MisterFpga) -> Result<Self, Error> {
         fpga.wait_for_ready();
 
         let config = config_string::Config::from_fpga(&mut fpga)?;
+        let core_type = fpga
+            .core_type()
+            .ok_or_else(|| Error::Protocol("Could not get core type.".to_string()))?;
+        let spi_type = fpga
+            .core_interface_type()
+            .ok_or_else(|| Error::Protocol("Could not get SPI type.".to_string()))?;
+        let io_version = fpga
+            .core_io_version()
+            .ok_or_else(|| Error::Protocol("Could not get IO version.".to_string()))?;
+        info!(?core_type, ?spi_type, io_version, "Core loaded");
 
+        Ok(Self::from_config(
+            fpga,
+            config,
+            (core_type, spi_type, io_version),
+            crate::framebuffer::FpgaFramebuffer::default(),
+        ))
+    }
+
+    /// Create the core from its config string and the interface read from the
+    /// FPGA (core type, SPI bus type and IO version).
+    fn from_config(
+        fpga: MisterFpga,
+        config: config_string::Config,
+        (core_type, spi_type, io_version): (CoreType, CoreInterfaceType, u8),
+        framebuffer: crate::framebuffer::FpgaFramebuffer<DevMemMemoryMapper>,
+    ) -> Self {
         let mut map = ButtonMap::default();
         if let Some(list) = config.snes_default_button_list() {
             info!("Loading mapping from config file");
@@ -119,17 +221,22 @@ impl MisterFpgaCore {
         );
         info!("Core config {:#?}", config);
 
-        let core_type = fpga.core_type().ok_or("Could not get core type.")?;
-        let spi_type = fpga
-            .core_interface_type()
-            .ok_or("Could not get SPI type.")?;
-        let io_version = fpga.core_io_version().ok_or("Could not get IO version.")?;
-        info!(?core_type, ?spi_type, io_version, "Core loaded");
-
         let save_states = SaveStateManager::from_config_string(&config);
+        let trigger_buttons = ["L2", "R2"].map(|name| {
+            config
+                .joystick_button_list()
+                .and_then(|list| {
+                    list.iter()
+                        .position(|b| b.trim().eq_ignore_ascii_case(name))
+                })
+                .map(|i| (i + 4) as u8)
+                .filter(|bit| *bit < 32)
+        });
+        let mouse = MouseState::new(Config::base().into_inner().mouse_throttle.unwrap_or(1));
+        let inputs = CoreInputs::new(map, mouse, io_version).with_trigger_buttons(trigger_buttons);
         const NONE: Option<SdCard> = None;
 
-        Ok(MisterFpgaCore {
+        MisterFpgaCore {
             is_menu: false,
             fpga,
             core_type,
@@ -138,13 +245,42 @@ impl MisterFpgaCore {
             volume: 0,
             config,
             cards: Box::new([NONE; 16]),
+            playlists: Default::default(),
             save_states,
-            gamepads: [map; 6],
+            inputs,
             status: Default::default(),
             status_counter: 0,
-            framebuffer: crate::framebuffer::FpgaFramebuffer::default(),
+            status_file: None,
+            dip_switches: DipSwitches::default(),
+            dip_file: None,
+            cheats: Cheats::default(),
+            remembered_files: RememberedFiles::default(),
+            remember_file: None,
+            uart: UartSettings::default(),
+            uart_endpoint: None,
+            uart_bridge: None,
+            events: Vec::new(),
+            file_crc: None,
+            video_mode: None,
+            framebuffer,
             should_quit: false,
-        })
+        }
+    }
+
+    /// Create a core over a fake FPGA, whose SPI commands are recorded in the
+    /// returned [`FakeSpi`](crate::fpga::FakeSpi).
+    #[cfg(test)]
+    pub(crate) fn fake(
+        config: &str,
+    ) -> (Self, std::rc::Rc<std::cell::RefCell<crate::fpga::FakeSpi>>) {
+        use std::str::FromStr;
+
+        let (fpga, spi) = MisterFpga::fake();
+        let config = config_string::Config::from_str(config).unwrap();
+        let memory = DevMemMemoryMapper::anonymous(crate::framebuffer::BUFFER_SIZE).unwrap();
+        let framebuffer = crate::framebuffer::FpgaFramebuffer::new(memory).unwrap();
+        let interface = (CoreType::CoreTypeGeneric, CoreInterfaceType::SpiBus16Bit, 1);
+        (Self::from_config(fpga, config, interface, framebuffer), spi)
     }
 
     pub fn spi(&mut self) -> crate::fpga::Spi<DevMemMemoryMapper> {
@@ -155,21 +291,21 @@ impl MisterFpgaCore {
     }
 
     /// Perform a soft reset.
-    pub fn soft_reset(&mut self) {
-        self.read_status_bits();
+    pub fn soft_reset(&mut self) -> Result<(), Error> {
+        self.read_status_bits()?;
         self.status.set(0, true);
-        self.send_status_bits(self.status);
+        self.send_status_bits(self.status)?;
         self.status.set(0, false);
-        self.send_status_bits(self.status);
+        self.send_status_bits(self.status)
     }
 
     /// Send the Real Time Clock to the core.
-    pub fn send_rtc(&mut self) -> Result<(), String> {
+    pub fn send_rtc(&mut self) -> Result<(), Error> {
         self.fpga.spi_mut().execute(UserIoRtc::now())?;
         Ok(())
     }
 
-    pub fn send_volume(&mut self, volume: impl IntoVolume) -> Result<(), String> {
+    pub fn send_volume(&mut self, volume: impl IntoVolume) -> Result<(), Error> {
         let volume = volume.into_volume();
         debug!(?volume, "Setting volume");
         self.fpga.spi_mut().execute(volume.into_user_io())?;
@@ -181,19 +317,27 @@ impl MisterFpgaCore {
         crate::framebuffer::FrameIter::new(&self.framebuffer)
     }
 
-    /// Send a file (ROM or BIOS) to the core on an index.
-    pub fn load_file(
+    /// Send a file (ROM or BIOS) to the core on an index. The path can be a file
+    /// inside an archive (see [`Self::load_archive_entry`]).
+    pub fn load_file(&mut self, path: &Path, file_info: Option<LoadFileInfo>) -> Result<(), Error> {
+        self.load_file_with_progress(path, file_info, &mut |_| true)
+    }
+
+    /// Send a file to the core, like [`Self::load_file`], calling `progress` while
+    /// it is sent.
+    pub fn load_file_with_progress(
         &mut self,
         path: &Path,
         file_info: Option<LoadFileInfo>,
-    ) -> Result<(), String> {
-        info!(?path, ?file_info, "Loading file");
-        let info = file_info.map_or_else(
-            || MisterFpgaSendFileInfo::from_path(path, self),
-            MisterFpgaSendFileInfo::from_file_info,
-        )?;
-        info!(?info, "info_send_file_info");
+        progress: &mut TransferCallback,
+    ) -> Result<(), Error> {
+        if !path.exists() {
+            if let Some((archive, entry)) = split_archive_path(path) {
+                return self.load_archive_entry(&archive, &entry, file_info, progress);
+            }
+        }
 
+        info!(?path, ?file_info, "Loading file");
         let ext = path
             .extension()
             .unwrap_or(OsStr::new(""))
@@ -201,27 +345,100 @@ impl MisterFpgaCore {
             .unwrap_or("")
             .to_uppercase();
 
-        let now = std::time::Instant::now();
-        debug!("Sending file {:?} to core", path);
+        let file = File::open(path)?;
+        let size = transfer_size(file.metadata()?.len())?;
+
+        self.load_reader_with_progress(file, &ext, size, file_info, progress)
+    }
 
-        let file = File::open(path).map_err(|e| e.to_string())?;
-        let size = file.metadata().map_err(|e| e.to_string())?.len() as u32;
+    /// Send a file inside a zip or 7z archive to the core on an index. The file is
+    /// decompressed while it is sent, and its extension is sent to the core.
+    pub fn load_archive_entry(
+        &mut self,
+        archive: &Path,
+        entry: &str,
+        file_info: Option<LoadFileInfo>,
+        progress: &mut TransferCallback,
+    ) -> Result<(), Error> {
+        info!(?archive, ?entry, ?file_info, "Loading file from archive");
+        let ext = Path::new(entry)
+            .extension()
+            .and_then(OsStr::to_str)
+            .unwrap_or("")
+            .to_uppercase();
+
+        archive::with_entry(archive, entry, |reader, size| {
+            self.load_reader_with_progress(reader, &ext, transfer_size(size)?, file_info, progress)
+        })
+    }
+
+    /// Send the content of a reader (ROM or BIOS) to the core on an index. The
+    /// extension (without the leading dot) is sent to the core, and used to find
+    /// the index if `file_info` is `None`. The reader must contain at least `size`
+    /// bytes.
+    pub fn load_reader(
+        &mut self,
+        reader: impl Read,
+        ext: &str,
+        size: u32,
+        file_info: Option<LoadFileInfo>,
+    ) -> Result<(), Error> {
+        self.load_reader_with_progress(reader, ext, size, file_info, &mut |_| true)
+    }
+
+    /// Send the content of a reader to the core, like [`Self::load_reader`],
+    /// calling `progress` while it is sent. If the transfer fails or is cancelled,
+    /// the core is told the transfer ended before the error is returned.
+    pub fn load_reader_with_progress(
+        &mut self,
+        reader: impl Read,
+        ext: &str,
+        size: u32,
+        file_info: Option<LoadFileInfo>,
+        progress: &mut TransferCallback,
+    ) -> Result<(), Error> {
+        let info = file_info.map_or_else(
+            || MisterFpgaSendFileInfo::from_extension(ext, self),
+            MisterFpgaSendFileInfo::from_file_info,
+        )?;
+        info!(?info, "info_send_file_info");
+
+        let ext = ext.to_uppercase();
+        let now = std::time::Instant::now();
+        debug!(?ext, ?size, "Sending file to core");
 
         self.start_send_file(info.index(), &ext, size)?;
-        match info {
+        let index = info.index();
+        let mut report = |sent: u32| {
+            progress(&TransferProgress {
+                index,
+                sent,
+                total: size,
+            })
+        };
+        let result = match info {
             MisterFpgaSendFileInfo::Memory { index, address } => {
                 trace!(?index, ?address, ?ext, ?size, "File info (memory)");
-                self.send_file_to_sdram_(size, address, file)?;
+                self.send_file_to_sdram_(size, address, reader, &mut report)
             }
             MisterFpgaSendFileInfo::Buffered { index } => {
                 trace!(?index, ?ext, ?size, "File info (buffered)");
-                self.send_file_to_buffer_(size, file)?;
+                self.send_file_to_buffer_(size, reader, &mut report)
             }
-        }
-        self.read_status_bits();
+        };
+        let crc = match result {
+            Ok(crc) => crc,
+            Err(error) => {
+                warn!(%error, "File transfer failed");
+                self.end_send_file()?;
+                return Err(error);
+            }
+        };
+        self.file_crc = Some(crc);
+        self.read_status_bits()?;
 
         self.status.set(0, false);
-        self.send_status_bits(self.status);
+        self.send_status_bits(self.status)?;
 
         // self.end_send_file()?;
         debug!("Done in {}ms", now.elapsed().as_millis());
@@ -229,15 +446,79 @@ impl MisterFpgaCore {
         Ok(())
     }
 
-    fn start_send_file(&mut self, index: u8, ext: &str, size: u32) -> Result<(), String> {
+    /// Send a BIOS to the core. The index is selected from the BIOS file name
+    /// (see [`config_string::Config::bios_load_info`]).
+    pub fn send_bios(&mut self, bios: Bios) -> Result<(), Error> {
+        self.send_bios_with_progress(bios, &mut |_| true)
+    }
+
+    /// Send a BIOS to the core, like [`Self::send_bios`], calling `progress`
+    /// while it is sent.
+    pub fn send_bios_with_progress(
+        &mut self,
+        mut bios: Bios,
+        progress: &mut TransferCallback,
+    ) -> Result<(), Error> {
+        let info = self.config.bios_load_info(bios.path());
+        let ext = bios
+            .path()
+            .and_then(|p| p.extension())
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        info!(path = ?bios.path(), ?info, "Loading BIOS");
+
+        let position = bios.stream_position()?;
+        let end = bios.seek(SeekFrom::End(0))?;
+        bios.seek(SeekFrom::Start(position))?;
+
+        let size = (end - position) as u32;
+        self.load_reader_with_progress(bios, &ext, size, Some(info), progress)?;
+        self.end_send_file()
+    }
+
+    /// Send a ROM to the core, calling `progress` while it is sent. The index is
+    /// selected from the extension of the ROM.
+    pub fn send_rom_with_progress(
+        &mut self,
+        rom: Rom,
+        progress: &mut TransferCallback,
+    ) -> Result<(), Error> {
+        match rom {
+            Rom::Memory(path, data) => {
+                let ext = path
+                    .as_ref()
+                    .and_then(|p| p.extension())
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let size = (data.get_ref().len() as u64).saturating_sub(data.position());
+                let size = transfer_size(size)?;
+                self.load_reader_with_progress(data, &ext, size, None, progress)
+            }
+            Rom::File(path) => self.load_file_with_progress(&path, None, progress),
+            Rom::Archive(archive, entry) => {
+                self.load_archive_entry(&archive, &entry, None, progress)
+            }
+        }?;
+
+        // Save states are only compatible with the game they were saved from.
+        let crc = self.file_crc;
+        if let Some(manager) = self.save_states.as_mut() {
+            manager.set_rom_hash(crc);
+        }
+        Ok(())
+    }
+
+    fn start_send_file(&mut self, index: u8, ext: &str, size: u32) -> Result<(), Error> {
         self.fpga.spi_mut().execute(FileIndex::from(index))?;
         self.fpga.spi_mut().execute(FileExtension(ext))?;
-        self.fpga.spi_mut().execute(FileTxEnabled(Some(size)))
+        self.fpga.spi_mut().execute(FileTxEnabled(Some(size)))?;
+        Ok(())
     }
 
-    pub fn end_send_file(&mut self) -> Result<(), String> {
+    pub fn end_send_file(&mut self) -> Result<(), Error> {
         // Disable download.
-        self.fpga.spi_mut().execute(FileTxDisabled)
+        self.fpga.spi_mut().execute(FileTxDisabled)?;
+        Ok(())
     }
 
     /// Return the core parsed config structure.
@@ -246,21 +527,21 @@ impl MisterFpgaCore {
     }
 
     /// Return the video info of the core.
-    pub fn video_info(&mut self) -> Result<VideoInfo, String> {
-        VideoInfo::create(self.spi_mut())
+    pub fn video_info(&mut self) -> Result<VideoInfo, Error> {
+        VideoInfo::create(self.spi_mut()).map_err(Error::Protocol)
     }
 
     pub fn status_mask(&self) -> StatusBitMap {
         self.config().status_bit_map_mask()
     }
 
-    pub fn status_pulse(&mut self, bit: usize) {
+    pub fn status_pulse(&mut self, bit: usize) -> Result<(), Error> {
         let mut bits = *self.status_bits();
         bits.set(bit, true);
-        self.send_status_bits(bits);
+        self.send_status_bits(bits)?;
 
         bits.set(bit, false);
-        self.send_status_bits(bits);
+        self.send_status_bits(bits)
     }
 
     /// Return the core status bits. This is an internal cache of the
@@ -271,89 +552,502 @@ impl MisterFpgaCore {
     }
 
     /// Update the internal cache and return it.
-    pub fn read_status_bits(&mut self) -> &StatusBitMap {
+    pub fn read_status_bits(&mut self) -> Result<&StatusBitMap, Error> {
         self.fpga
             .spi_mut()
-            .execute(GetStatusBits(&mut self.status, &mut self.status_counter))
-            .unwrap();
-        &self.status
+            .execute(GetStatusBits(&mut self.status, &mut self.status_counter))?;
+        Ok(&self.status)
     }
 
     /// Send status bits to the core.
-    pub fn send_status_bits(&mut self, bits: StatusBitMap) {
+    pub fn send_status_bits(&mut self, bits: StatusBitMap) -> Result<(), Error> {
         debug!(?bits, "Setting status bits");
-        self.fpga.spi_mut().execute(SetStatusBits(&bits)).unwrap();
+        self.fpga.spi_mut().execute(SetStatusBits(&bits))?;
         self.status = bits;
+        Ok(())
     }
 
-    pub fn menu_options(&self) -> &[ConfigMenu] {
-        self.config().menu.as_slice()
+    /// Restore the option bits from `bits`. Only bits used by options are
+    /// changed; triggers and the reset bit are left untouched.
+    pub fn restore_status_bits(&mut self, bits: &StatusBitMap) -> Result<(), Error> {
+        let mask = self.config().option_bit_map_mask();
+        let mut status = *self.read_status_bits()?;
+        for ((word, bits), mask) in status
+            .as_mut_raw_slice()
+            .iter_mut()
+            .zip(bits.as_raw_slice())
+            .zip(mask.as_raw_slice())
+        {
+            *word = (*word & !mask) | (bits & mask);
+        }
+        self.send_status_bits(status)
     }
 
-    /// Notify the core of a keyboard key down event.
-    pub fn key_down(&mut self, keycode: impl Into<Ps2Scancode> + Debug + Copy) {
-        let scancode = keycode.into();
-        debug!(?keycode, ?scancode, "Keydown");
-        if scancode != Ps2Scancode::None {
-            self.fpga
-                .spi_mut()
-                .execute(UserIoKeyboardKeyDown::from(scancode))
-                .unwrap();
+    /// Load the option bits from a file. The format is the same as MiSTer's
+    /// `config/<CORE>.CFG` files.
+    pub fn load_status_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let bytes = std::fs::read(path.as_ref())?;
+        debug!(path = ?path.as_ref(), "Loading status bits");
+        self.restore_status_bits(&StatusBitMap::from_bytes(&bytes))
+    }
+
+    /// Save the option bits to a file, in the format read by
+    /// [`Self::load_status_file`].
+    pub fn save_status_file(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let bits = self.status.masked(&self.config().option_bit_map_mask());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        std::fs::write(path, bits.to_bytes())?;
+        Ok(())
     }
 
-    /// Notify the core of a keyboard key up event.
-    pub fn key_up(&mut self, keycode: impl Into<Ps2Scancode> + Debug + Copy) {
-        let scancode = keycode.into();
-        debug!(?keycode, ?scancode, "Keyup");
-        if scancode != Ps2Scancode::None {
-            self.fpga
-                .spi_mut()
-                .execute(UserIoKeyboardKeyUp::from(scancode))
-                .unwrap();
+    /// Set the file the option bits are saved to whenever an option changes.
+    pub fn set_status_file(&mut self, path: Option<PathBuf>) {
+        self.status_file = path;
+    }
+
+    /// Import the options saved by MiSTer for this core, from
+    /// `<config_dir>/<CORE>.CFG`. Returns false if there was no file to
+    /// import.
+    pub fn import_mister_config(&mut self, config_dir: impl AsRef<Path>) -> Result<bool, Error> {
+        let path = config_dir
+            .as_ref()
+            .join(format!("{}.CFG", self.config().name));
+        if !path.exists() {
+            return Ok(false);
         }
+
+        info!(?path, "Importing MiSTer core options");
+        self.load_status_file(path)?;
+        Ok(true)
     }
 
-    pub fn gamepad(&self, idx: u8) -> Option<&ButtonMap> {
-        self.gamepads.get(idx as usize)
+    fn persist_status_bits(&self) -> Result<(), Error> {
+        match &self.status_file {
+            Some(path) => self.save_status_file(path),
+            None => Ok(()),
+        }
     }
 
-    pub fn gamepad_mut(&mut self, idx: u8) -> Option<&mut ButtonMap> {
-        self.gamepads.get_mut(idx as usize)
+    /// Return the DIP switches of the game (empty if the core has none).
+    pub fn dip_switches(&self) -> &DipSwitches {
+        &self.dip_switches
     }
 
-    pub fn send_gamepad(&mut self, idx: u8, map: ButtonMap) {
-        if idx > 5 {
-            return;
+    /// Set the DIP switches definitions and values, and send them to the core.
+    pub fn set_dip_switches(&mut self, dips: DipSwitches) -> Result<(), Error> {
+        self.dip_switches = dips;
+        self.send_dip_switches()
+    }
+
+    /// Send the current value of the DIP switches to the core. Does nothing if
+    /// there are no switches.
+    pub fn send_dip_switches(&mut self) -> Result<(), Error> {
+        if self.dip_switches.is_empty() {
+            return Ok(());
         }
 
+        let bytes = self.dip_switches.to_bytes();
+        debug!(value = self.dip_switches.value(), "Sending DIP switches");
         self.fpga
             .spi_mut()
-            .execute(UserIoJoystick::from_joystick_index(idx, &map))
-            .unwrap();
-        self.gamepads[idx as usize] = map;
+            .execute(FileIndex::from(DIP_SWITCHES_INDEX))?;
+        self.fpga.spi_mut().execute(FileTxEnabled(None))?;
+        self.send_file_to_buffer_(bytes.len() as u32, bytes.as_slice(), &mut |_| true)?;
+        self.end_send_file()
+    }
+
+    /// Load the DIP switches values from a file, in the same format as MiSTer's
+    /// `config/dips/<GAME>.dip` files, and send them to the core.
+    pub fn load_dip_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let bytes = std::fs::read(path.as_ref())?;
+        self.dip_switches.load_bytes(&bytes);
+        self.send_dip_switches()
+    }
+
+    /// Save the DIP switches values to a file, in the format read by
+    /// [`Self::load_dip_file`].
+    pub fn save_dip_file(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.dip_switches.to_bytes())?;
+        Ok(())
     }
 
-    /// Notify the core of a gamepad button down event.
-    pub fn gamepad_button_down(&mut self, joystick_idx: u8, button: u8) {
-        let g = &mut self.gamepads[joystick_idx as usize];
-        g.down(button);
+    /// Set the file the DIP switches are saved to whenever they change.
+    pub fn set_dip_file(&mut self, path: Option<PathBuf>) {
+        self.dip_file = path;
+    }
 
-        self.fpga
-            .spi_mut()
-            .execute(UserIoJoystick::from_joystick_index(joystick_idx, g))
-            .unwrap();
+    /// Select a choice of a DIP switch, send the switches to the core and save
+    /// them. Returns `None` if there is no switch with this ID.
+    fn set_dip_choice(&mut self, id: SettingId, index: usize) -> Result<Option<usize>, Error> {
+        let Some(choice) = self.dip_switches.set_choice(id, index) else {
+            return Ok(None);
+        };
+
+        self.send_dip_switches()?;
+        if let Some(path) = &self.dip_file {
+            self.save_dip_file(path)?;
+        }
+        Ok(Some(choice))
+    }
+
+    /// Start an arcade game from its MRA file. The DIP switches and default
+    /// buttons of the game are set, then each ROM is assembled from the archives
+    /// found in `rom_dirs` and sent to the core on its index, calling `progress`
+    /// while it is sent.
+    pub fn load_mra(
+        &mut self,
+        mra: &Mra,
+        rom_dirs: Vec<PathBuf>,
+        progress: &mut TransferCallback,
+    ) -> Result<(), Error> {
+        self.dip_switches = mra.dip_switches()?;
+        if let Some(map) = mra.buttons.as_ref().and_then(MraButtons::default_map) {
+            for idx in 0..GAMEPAD_COUNT as u8 {
+                if let Some(gamepad) = self.gamepad_mut(idx) {
+                    *gamepad = map;
+                }
+            }
+        }
+
+        let mut archives = RomArchives::new(rom_dirs);
+        let mut hasher = crc32fast::Hasher::new();
+        for rom in mra.roms.iter().filter(|r| !r.entries.is_empty()) {
+            let data = rom.assemble(&mut archives)?;
+            info!(index = rom.index, size = data.len(), "Sending MRA ROM");
+            hasher.update(&data);
+
+            let info = LoadFileInfo {
+                save_support: false,
+                index: rom.index,
+                extensions: vec![],
+                label: None,
+                address: None,
+            };
+            let size = data.len() as u32;
+            self.load_reader_with_progress(data.as_slice(), "", size, Some(info), progress)?;
+            self.end_send_file()?;
+        }
+
+        let crc = hasher.finalize();
+        if let Some(manager) = self.save_states.as_mut() {
+            manager.set_rom_hash(Some(crc));
+        }
+        self.send_dip_switches()
+    }
+
+    /// Return the files remembered for `FC` entries of the config string.
+    pub fn remembered_files(&self) -> &RememberedFiles {
+        &self.remembered_files
+    }
+
+    /// Load the files remembered for `FC` entries from a file, and send them to
+    /// the core. Files that do not exist anymore are skipped. Remembered files
+    /// are saved to this file when they change.
+    pub fn load_remember_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        if path.exists() {
+            self.remembered_files = RememberedFiles::from_file(path)?;
+        }
+        self.remember_file = Some(path.to_path_buf());
+        self.send_remembered_files()
+    }
+
+    /// Send all remembered files to the core. Files that do not exist or cannot
+    /// be sent are skipped.
+    pub fn send_remembered_files(&mut self) -> Result<(), Error> {
+        let infos: Vec<LoadFileInfo> = self
+            .menu_options()
+            .iter()
+            .filter_map(ConfigMenu::as_remembered_file_info)
+            .cloned()
+            .collect();
+
+        for info in infos {
+            let Some(path) = self.remembered_files.get(info.index) else {
+                continue;
+            };
+            if !path.exists() {
+                warn!(?path, "Remembered file does not exist, skipping");
+                continue;
+            }
+
+            let path = path.to_path_buf();
+            if let Err(error) = self.load_file(&path, Some(info)) {
+                warn!(%error, ?path, "Could not send remembered file, skipping");
+            }
+            self.end_send_file()?;
+        }
+        Ok(())
+    }
+
+    /// Remember (or forget, if `path` is `None`) the file of an `FC` entry, and
+    /// save the remembered files.
+    pub fn set_remembered_file(&mut self, index: u8, path: Option<PathBuf>) -> Result<(), Error> {
+        if !self.remembered_files.set(index, path) {
+            return Ok(());
+        }
+
+        match &self.remember_file {
+            Some(remember_file) => self.remembered_files.save(remember_file),
+            None => Ok(()),
+        }
+    }
+
+    /// Return the UART mode and speed selected.
+    pub fn uart(&self) -> UartSettings {
+        self.uart
+    }
+
+    /// Return the bridge between the core's UART and Linux, if it is running.
+    pub fn uart_bridge(&self) -> Option<&UartBridge> {
+        self.uart_bridge.as_ref()
+    }
+
+    /// Set the endpoint the UART is bridged to, instead of the default for the
+    /// mode (see [`UartEndpoint::for_mode`]). If the UART is enabled, it is
+    /// bridged to the new endpoint right away.
+    pub fn set_uart_endpoint(&mut self, endpoint: Option<UartEndpoint>) -> Result<(), Error> {
+        self.uart_endpoint = endpoint;
+        if self.uart.mode != UartMode::None {
+            self.set_uart(self.uart)?;
+        }
+        Ok(())
+    }
+
+    /// Set the UART mode and speed of the core, and bridge the HPS UART to the
+    /// endpoint of the mode.
+    pub fn set_uart(&mut self, uart: UartSettings) -> Result<(), Error> {
+        // Stop the current bridge first, so the device is free.
+        self.uart_bridge = None;
+        self.uart = uart;
+
+        info!(?uart, "Setting UART mode");
+        self.fpga.spi_mut().execute(user_io::SetUartMode {
+            mode: uart.mode as u16,
+            speed: uart.speed,
+        })?;
+
+        if uart.mode == UartMode::None {
+            return Ok(());
+        }
+        let Some(endpoint) = self
+            .uart_endpoint
+            .clone()
+            .or_else(|| UartEndpoint::for_mode(uart.mode))
+        else {
+            return Ok(());
+        };
+
+        let bridge = UartBridge::start(HPS_UART_PATH, uart.speed, endpoint)?;
+        if let Some(path) = bridge.pty_path() {
+            self.events.push(CoreEvent::Info {
+                message: format!("UART available at {}", path.display()),
+            });
+        }
+        self.uart_bridge = Some(bridge);
+        Ok(())
+    }
+
+    /// Select a UART option from the core settings, returning the choice
+    /// selected. Returns `None` if `id` is not a UART option.
+    fn set_uart_option(&mut self, id: SettingId, index: usize) -> Result<Option<usize>, Error> {
+        let is_mode = if id == UartSettings::mode_setting_id() {
+            true
+        } else if id == UartSettings::speed_setting_id() {
+            false
+        } else {
+            return Ok(None);
+        };
+
+        let settings = &self.config.settings;
+        let count = if is_mode {
+            UartMode::available(settings).len()
+        } else {
+            self.uart.mode.speeds(settings).len()
+        };
+        if count == 0 {
+            return Ok(Some(0));
+        }
+        let index = index % count;
+        let uart = if is_mode {
+            self.uart.with_mode_index(settings, index)
+        } else {
+            self.uart.with_speed_index(settings, index)
+        };
+        if let Some(uart) = uart {
+            self.set_uart(uart)?;
+        }
+        Ok(Some(index))
+    }
+
+    /// Return the CRC32 of the last file (e.g. ROM) sent to the core.
+    pub fn file_crc(&self) -> Option<u32> {
+        self.file_crc
+    }
+
+    /// Return the cheats of the game (empty if none were loaded).
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    /// Set the cheats of the game, and send the enabled ones to the core.
+    pub fn set_cheats(&mut self, cheats: Cheats) -> Result<(), Error> {
+        self.cheats = cheats;
+        self.send_cheats()
+    }
+
+    /// Load the cheats for the last file sent to the core, from the cheat
+    /// archives in `<cheats_dir>/<CORE>/`. Returns false if the core does not
+    /// support cheats or there was no archive for the file.
+    pub fn load_cheats(&mut self, cheats_dir: impl AsRef<Path>) -> Result<bool, Error> {
+        let Some(crc) = self.file_crc else {
+            return Ok(false);
+        };
+        if !self.config.supports_cheats() {
+            return Ok(false);
+        }
+
+        let dir = cheats_dir.as_ref().join(&self.config.name);
+        let Some(path) = Cheats::find_archive(dir, crc)? else {
+            return Ok(false);
+        };
+
+        info!(?path, "Loading cheats");
+        let cheats = Cheats::from_zip(File::open(path)?)?;
+        self.set_cheats(cheats)?;
+        Ok(true)
+    }
+
+    /// Send the codes of the enabled cheats to the core. Does nothing if the
+    /// core does not support cheats.
+    pub fn send_cheats(&mut self) -> Result<(), Error> {
+        if !self.config.supports_cheats() {
+            return Ok(());
+        }
+
+        let mut codes = self.cheats.codes();
+        debug!(count = codes.len() / CHEAT_CODE_SIZE, "Sending cheats");
+        if codes.is_empty() {
+            // The core expects at least one word, even without cheats.
+            codes = vec![0; 2];
+        }
+
+        self.fpga.spi_mut().execute(FileIndex::from(CHEATS_INDEX))?;
+        self.fpga.spi_mut().execute(FileTxEnabled(None))?;
+        self.send_file_to_buffer_(codes.len() as u32, codes.as_slice(), &mut |_| true)?;
+        self.end_send_file()
+    }
+
+    /// Enable or disable a cheat, and send the enabled cheats to the core.
+    /// Returns `None` if there is no cheat with this ID.
+    pub fn set_cheat_enabled(
+        &mut self,
+        id: SettingId,
+        enabled: bool,
+    ) -> Result<Option<bool>, Error> {
+        let Some(enabled) = self.cheats.set_enabled(id, enabled) else {
+            return Ok(None);
+        };
+
+        self.send_cheats()?;
+        Ok(Some(enabled))
+    }
+
+    pub fn menu_options(&self) -> &[ConfigMenu] {
+        self.config().menu.as_slice()
+    }
+
+    /// Notify the core of a keyboard key down event.
+    pub fn key_down(&mut self, key: Scancode) -> Result<(), Error> {
+        self.inputs.key_down(self.fpga.spi_mut(), key)
+    }
+
+    /// Notify the core of a keyboard key up event.
+    pub fn key_up(&mut self, key: Scancode) -> Result<(), Error> {
+        self.inputs.key_up(self.fpga.spi_mut(), key)
+    }
+
+    /// Press the keys in `keys` and release all others.
+    pub fn keys_set(&mut self, keys: &ScancodeSet) -> Result<(), Error> {
+        self.inputs.keys_set(self.fpga.spi_mut(), keys)
+    }
+
+    /// The keys currently pressed.
+    pub fn keys(&self) -> &ScancodeSet {
+        self.inputs.keys()
+    }
+
+    pub fn gamepad(&self, idx: u8) -> Option<&ButtonMap> {
+        self.inputs.gamepad(idx)
+    }
+
+    pub fn gamepad_mut(&mut self, idx: u8) -> Option<&mut ButtonMap> {
+        self.inputs.gamepad_mut(idx)
+    }
+
+    pub fn send_gamepad(&mut self, idx: u8, map: ButtonMap) -> Result<(), Error> {
+        self.inputs.send_gamepad(self.fpga.spi_mut(), idx, map)
+    }
+
+    /// Notify the core of a gamepad button down event.
+    pub fn gamepad_button_down(&mut self, joystick_idx: u8, button: Button) -> Result<(), Error> {
+        self.inputs
+            .gamepad_button_down(self.fpga.spi_mut(), joystick_idx, button)
     }
 
     /// Notify the core of a gamepad button up event.
-    pub fn gamepad_button_up(&mut self, joystick_idx: u8, button: u8) {
-        let g = &mut self.gamepads[joystick_idx as usize];
-        g.up(button);
+    pub fn gamepad_button_up(&mut self, joystick_idx: u8, button: Button) -> Result<(), Error> {
+        self.inputs
+            .gamepad_button_up(self.fpga.spi_mut(), joystick_idx, button)
+    }
 
-        self.fpga
-            .spi_mut()
-            .execute(UserIoJoystick::from_joystick_index(joystick_idx, g))
-            .unwrap();
+    /// Press the buttons in `buttons` and release all others on a gamepad.
+    pub fn gamepad_buttons_set(
+        &mut self,
+        joystick_idx: u8,
+        buttons: ButtonSet,
+    ) -> Result<(), Error> {
+        self.inputs
+            .gamepad_buttons_set(self.fpga.spi_mut(), joystick_idx, buttons)
+    }
+
+    /// The buttons currently pressed on a gamepad, or `None` if the index is invalid.
+    pub fn gamepad_buttons(&self, joystick_idx: u8) -> Option<ButtonSet> {
+        self.inputs.gamepad_buttons(joystick_idx)
+    }
+
+    /// Notify the core of an analog axis motion on a gamepad.
+    pub fn gamepad_axis_motion(
+        &mut self,
+        joystick_idx: u8,
+        axis: Axis,
+        value: i16,
+    ) -> Result<(), Error> {
+        self.inputs
+            .gamepad_axis_motion(self.fpga.spi_mut(), joystick_idx, axis, value)
+    }
+
+    pub fn mouse_move(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        self.inputs.mouse_move(self.fpga.spi_mut(), dx, dy)
+    }
+
+    pub fn mouse_wheel(&mut self, delta: i32) -> Result<(), Error> {
+        self.inputs.mouse_wheel(self.fpga.spi_mut(), delta)
+    }
+
+    pub fn mouse_button_down(&mut self, button: MouseButton) -> Result<(), Error> {
+        self.inputs.mouse_button_down(self.fpga.spi_mut(), button)
+    }
+
+    pub fn mouse_button_up(&mut self, button: MouseButton) -> Result<(), Error> {
+        self.inputs.mouse_button_up(self.fpga.spi_mut(), button)
     }
 
     /// Access the internal save state manager, in readonly.
@@ -366,9 +1060,23 @@ impl MisterFpgaCore {
         self.save_states.as_mut()
     }
 
+    /// Change the number of save state slots. The slots are mapped again, so
+    /// this should be done before any save state is loaded.
+    pub fn set_save_state_slots(&mut self, nb_slots: usize) -> Result<(), Error> {
+        let rom_hash = self.save_states.as_ref().and_then(|m| m.rom_hash());
+        let nb_slots = u32::try_from(nb_slots).unwrap_or(u32::MAX);
+        self.save_states = SaveStateManager::with_slots(&self.config, nb_slots)?;
+        if let Some(manager) = self.save_states.as_mut() {
+            manager.set_rom_hash(rom_hash);
+        }
+        Ok(())
+    }
+
     /// Take a screenshot and return the image in memory.
-    pub fn take_screenshot(&self) -> Result<DynamicImage, String> {
-        self.framebuffer.take_screenshot()
+    pub fn take_screenshot(&self) -> Result<DynamicImage, Error> {
+        self.framebuffer
+            .take_screenshot()
+            .map_err(Error::Unsupported)
     }
 
     pub fn framebuffer(&self) -> &crate::framebuffer::FpgaFramebuffer<DevMemMemoryMapper> {
@@ -376,7 +1084,7 @@ impl MisterFpgaCore {
     }
 
     /// Mount an SD card to the core.
-    pub fn mount(&mut self, file: SdCard, index: u8) -> Result<(), String> {
+    pub fn mount(&mut self, file: SdCard, index: u8) -> Result<(), Error> {
         self.fpga.spi_mut().execute(
             SetSdConf::default()
                 .with_wide(self.spi_type.is_wide())
@@ -400,147 +1108,313 @@ impl MisterFpgaCore {
         Ok(())
     }
 
+    /// Eject the SD card mounted on an index, and return it. The core is told
+    /// the card is now empty.
+    pub fn unmount(&mut self, index: u8) -> Result<Option<SdCard>, Error> {
+        let mut card = self
+            .cards
+            .get_mut(index as usize)
+            .ok_or(Error::InvalidSlot(index as usize))?
+            .take();
+
+        if let Some(card) = &mut card {
+            if let Err(error) = card.as_io().flush() {
+                warn!(%error, index, "Could not write back SD card");
+            }
+        }
+
+        // An image of size 0 tells the core there is no image.
+        self.fpga
+            .spi_mut()
+            .execute(SetSdInfo::default().with_io_version(self.io_version))?;
+        self.fpga
+            .spi_mut()
+            .execute(SetSdStat::default().with_writable(false).with_index(index))?;
+
+        info!(index, "Unmounted SD Card");
+        Ok(card)
+    }
+
+    /// Replace the SD card mounted on an index, and return the previous one.
+    /// It is ejected first, so the core sees the disk change.
+    pub fn swap(&mut self, file: SdCard, index: u8) -> Result<Option<SdCard>, Error> {
+        let previous = self.unmount(index)?;
+        self.mount(file, index)?;
+        Ok(previous)
+    }
+
+    /// The playlist of the disks of a card index, if an `.m3u` playlist was
+    /// inserted on it.
+    pub fn playlist(&self, index: u8) -> Option<&Playlist> {
+        self.playlists.get(index as usize).and_then(Option::as_ref)
+    }
+
+    /// The table of contents of the CD image mounted on an index, if any.
+    pub fn cd_toc(&self, index: u8) -> Option<&Toc> {
+        self.cards
+            .get(index as usize)
+            .and_then(Option::as_ref)
+            .and_then(SdCard::cd_image)
+            .map(CdImage::toc)
+    }
+
+    /// Send the table of contents of the CD image mounted on `index` to the
+    /// core on a file index, for cores that need the track layout of the disc
+    /// (see [`Toc::to_bytes`] for the format).
+    pub fn send_cd_toc(&mut self, index: u8, file_index: u8) -> Result<(), Error> {
+        let toc = self
+            .cd_toc(index)
+            .ok_or_else(|| Error::Message(format!("No CD image mounted on {index}")))?
+            .to_bytes();
+
+        debug!(index, file_index, "Sending CD table of contents");
+        self.start_send_file(file_index, "TOC", toc.len() as u32)?;
+        self.send_file_to_buffer_(toc.len() as u32, toc.as_slice(), &mut |_| true)?;
+        self.end_send_file()
+    }
+
     /// Check for updates (read/write) to SD cards. Returns true if any write/read
     /// operations were requested by the core (which means there might be more).
-    pub fn poll_mounts(&mut self) -> Result<bool, String> {
-        let mut result = false;
+    pub fn poll_mounts(&mut self) -> Result<bool, Error> {
+        Ok(self.poll_sd_card_()?.is_some())
+    }
 
-        for (_i, card) in self
-            .cards
-            .iter_mut()
-            .enumerate()
-            .filter_map(|(i, c)| c.as_mut().map(|c| (i, c)))
-        {
-            let mut stat: SdStatOutput = Default::default();
-            self.fpga.spi_mut().execute(GetSdStat(&mut stat))?;
-            trace!(?stat, "SD stat");
+    /// Service a single read or write request from the core, if any. Returns the
+    /// index of the card and whether the operation was a write.
+    fn poll_sd_card_(&mut self) -> Result<Option<(usize, bool)>, Error> {
+        if self.cards.iter().all(Option::is_none) {
+            return Ok(None);
+        }
 
-            if stat.op.is_write() {
-                result = true;
-                let mut buffer = vec![0; stat.size];
+        let mut stat: SdStatOutput = Default::default();
+        self.fpga.spi_mut().execute(GetSdStat(&mut stat))?;
+        trace!(?stat, "SD stat");
 
-                self.fpga.spi_mut().execute(SdWrite::new(
-                    &mut buffer,
-                    self.spi_type.is_wide(),
-                    stat.ack,
-                ))?;
+        let index = stat.disk as usize;
+        let addr = stat.lba * stat.block_size as u64;
+        let mut card = self.cards.get_mut(index).and_then(Option::as_mut);
 
-                let addr = stat.lba * stat.block_size as u64;
+        if let Some(card) = card.as_deref_mut() {
+            card.set_block_size(stat.block_size);
+        }
+
+        if stat.op.is_write() {
+            let mut buffer = vec![0; stat.size];
+
+            self.fpga.spi_mut().execute(SdWrite::new(
+                &mut buffer,
+                self.spi_type.is_wide(),
+                stat.ack,
+            ))?;
+
+            if let Some(card) = card {
                 let io = card.as_io();
-                io.seek(SeekFrom::Start(addr)).map_err(|e| e.to_string())?;
-                io.write_all(&buffer).map_err(|e| e.to_string())?;
-            } else if stat.op.is_read() {
-                result = true;
-                let mut buffer = vec![0; stat.size];
-                let addr = stat.lba * stat.block_size as u64;
+                io.seek(SeekFrom::Start(addr))?;
+                io.write_all(&buffer)?;
+            }
+            Ok(Some((index, true)))
+        } else if stat.op.is_read() {
+            let mut buffer = vec![0; stat.size];
+            if let Some(card) = card {
                 let io = card.as_io();
-                io.seek(SeekFrom::Start(addr)).map_err(|e| e.to_string())?;
-                io.read_exact(&mut buffer).map_err(|e| e.to_string())?;
-
-                // Blocks are now in memory, send them to the core.
-                self.fpga.spi_mut().execute(SdRead::new(
-                    &buffer,
-                    self.spi_type.is_wide(),
-                    stat.ack,
-                ))?;
+                io.seek(SeekFrom::Start(addr))?;
+                io.read_exact(&mut buffer)?;
             }
+
+            // Blocks are now in memory, send them to the core.
+            self.fpga
+                .spi_mut()
+                .execute(SdRead::new(&buffer, self.spi_type.is_wide(), stat.ack))?;
+            Ok(Some((index, false)))
+        } else {
+            Ok(None)
         }
-        Ok(result)
     }
 
+    /// Poll the core for events. This services pending SD card requests and checks
+    /// for changes in the save states, status bits and video mode.
+    pub fn poll_events(&mut self) -> Result<Vec<CoreEvent>, Error> {
+        let mut events = std::mem::take(&mut self.events);
+
+        // Limit the number of SD requests serviced in a single poll, so the
+        // frontend loop stays responsive.
+        for _ in 0..16 {
+            match self.poll_sd_card_() {
+                Ok(Some((slot, write))) => events.push(CoreEvent::SdServiced { slot, write }),
+                Ok(None) => break,
+                Err(error) => {
+                    warn!(%error, "Could not service SD card request");
+                    break;
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for (slot, card) in self.cards.iter_mut().enumerate() {
+            if let Some(card) = card {
+                if let Err(error) = card.flush_if_idle(now) {
+                    warn!(%error, slot, "Could not write back SD card");
+                }
+            }
+        }
+
+        if let Some(manager) = self.save_states() {
+            events.extend(
+                manager
+                    .slots()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, ss)| ss.is_dirty())
+                    .map(|(slot, _)| CoreEvent::SaveStateDirty { slot }),
+            );
+        }
+
+        let counter = self.status_counter;
+        if let Err(error) = self.read_status_bits() {
+            warn!(%error, "Could not read status bits");
+        } else if counter != self.status_counter {
+            events.push(CoreEvent::StatusBitsChanged);
+        }
+
+        match self.video_info() {
+            Ok(info) => {
+                let mode = (info.resolution(), info.interlaced());
+                if self.video_mode.is_some_and(|m| m != mode) {
+                    let (resolution, interlaced) = mode;
+                    info!(?resolution, interlaced, "Video mode changed");
+                    events.push(CoreEvent::VideoModeChanged {
+                        width: resolution.width as u32,
+                        height: resolution.height as u32,
+                        interlaced,
+                    });
+                }
+                self.video_mode = Some(mode);
+            }
+            Err(error) => warn!(%error, "Could not read video info"),
+        }
+
+        Ok(events)
+    }
+
+    /// Write the content of a reader to the DDRAM at `address`, verifying it as
+    /// it is written (see [`ddram::write_verified`]), and return its CRC32.
     fn send_file_to_sdram_(
         &mut self,
         size: u32,
         address: FpgaRamMemoryAddress,
-        mut reader: impl Read,
-    ) -> Result<(), String> {
+        reader: impl Read,
+        progress: &mut dyn FnMut(u32) -> bool,
+    ) -> Result<u32, Error> {
         // Verify invariants.
         if size >= 0x2000_0000 {
-            return Err("File too large.".to_string());
+            return Err(Error::unsupported("File too large"));
         }
-        let mut crc = crc32fast::Hasher::new();
-        let mut mem = DevMemMemoryMapper::create(address.as_usize(), size as usize)?;
-
-        let mut bytes2send = size;
-        while bytes2send > 0 {
-            let sz = reader
-                .read(mem.as_mut_range(..))
-                .map_err(|e| e.to_string())?;
+        let mut mem = DevMemMemoryMapper::create(address.as_usize(), size as usize)
+            .map_err(|e| Error::IoError(std::io::Error::other(e)))?;
 
-            // crc.update(mem.as_range(start..start + len));
-            bytes2send -= sz as u32;
-        }
-        crc.update(mem.as_range(..));
-
-        let crc = crc.finalize();
-        debug!("CRC: {:08X}", crc);
-        Ok(())
+        Ok(ddram::write_verified(
+            &mut mem,
+            reader,
+            size as usize,
+            progress,
+        )?)
     }
 
-    fn send_file_to_buffer_(&mut self, size: u32, mut reader: impl Read) -> Result<(), String> {
+    /// Send the content of a reader to the core, and return its CRC32. `progress`
+    /// is called with the number of bytes sent after each chunk, and cancels the
+    /// transfer if it returns `false`.
+    fn send_file_to_buffer_(
+        &mut self,
+        size: u32,
+        reader: impl Read,
+        progress: &mut dyn FnMut(u32) -> bool,
+    ) -> Result<u32, Error> {
         // Verify invariants.
         if size >= 0x2000_0000 {
-            return Err("File too large.".to_string());
+            return Err(Error::unsupported("File too large"));
         }
 
         let mut crc = crc32fast::Hasher::new();
         let now = std::time::Instant::now();
 
+        let mut reader = reader.take(size as u64);
         let mut buffer = [0u8; 4096];
+        let mut words = [0u16; 2048];
+        let mut sent = 0u32;
         loop {
-            match reader.read(&mut buffer).map_err(|e| e.to_string()) {
-                Ok(0) => break,
-                Ok(size) => {
-                    crc.update(&buffer[..size]);
-
-                    match self.spi_type {
-                        CoreInterfaceType::SpiBus8Bit => {
-                            self.fpga
-                                .spi_mut()
-                                .execute(FileTxData8Bits(&buffer[..size]))?;
-                        }
-                        CoreInterfaceType::SpiBus16Bit => {
-                            // This is safe since size cannot be larger than 4096.
-                            let buf16 = unsafe {
-                                std::slice::from_raw_parts(
-                                    buffer.as_ptr() as *const u16,
-                                    size + size % 2,
-                                )
-                            };
-                            self.fpga
-                                .spi_mut()
-                                .execute(FileTxData16Bits(&buf16[..size / 2]))?;
-                        }
-                    }
+            if !progress(sent) {
+                return Err(Error::Cancelled);
+            }
+
+            // Archives stream their data in short chunks, so fill the buffer.
+            // As it has an even size, only the last chunk of the file can have
+            // an odd length.
+            let mut len = 0;
+            while len < buffer.len() {
+                match reader.read(&mut buffer[len..]) {
+                    Ok(0) => break,
+                    Ok(n) => len += n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if len == 0 {
+                break;
+            }
+            crc.update(&buffer[..len]);
+            sent += len as u32;
+
+            match self.spi_type {
+                CoreInterfaceType::SpiBus8Bit => {
+                    self.fpga
+                        .spi_mut()
+                        .execute(FileTxData8Bits(&buffer[..len]))?;
                 }
-                Err(e) => {
-                    return Err(e);
+                CoreInterfaceType::SpiBus16Bit => {
+                    // The odd byte at the end of the file is padded with 0.
+                    let count = len.div_ceil(2);
+                    for (word, bytes) in words.iter_mut().zip(buffer[..len].chunks(2)) {
+                        *word = u16::from_le_bytes([bytes[0], bytes.get(1).copied().unwrap_or(0)]);
+                    }
+                    self.fpga
+                        .spi_mut()
+                        .execute(FileTxData16Bits(&words[..count]))?;
                 }
             }
         }
 
+        if sent != size {
+            return Err(Error::Protocol(format!(
+                "Sent {sent} bytes of a file of {size} bytes"
+            )));
+        }
+
         debug!("Read {} bytes", size);
         trace!("Took {}ms", now.elapsed().as_millis());
         let crc = crc.finalize();
         debug!("CRC: {:08X}", crc);
 
-        Ok(())
+        Ok(crc)
     }
 
-    pub fn trigger_menu(&mut self, menu: &ConfigMenu) -> Result<bool, String> {
+    pub fn trigger_menu(&mut self, menu: &ConfigMenu) -> Result<bool, Error> {
         match menu {
             ConfigMenu::HideIf(cond, sub) | ConfigMenu::DisableIf(cond, sub) => {
                 if self.config().status_bit_map_mask().get(*cond as usize) {
                     self.trigger_menu(sub)
                 } else {
-                    Err("Cannot trigger menu".to_string())
+                    Err(Error::unsupported(
+                        "Cannot trigger a hidden or disabled menu",
+                    ))
                 }
             }
             ConfigMenu::HideUnless(cond, sub) | ConfigMenu::DisableUnless(cond, sub) => {
                 if !self.config().status_bit_map_mask().get(*cond as usize) {
                     self.trigger_menu(sub)
                 } else {
-                    Err("Cannot trigger menu".to_string())
+                    Err(Error::unsupported(
+                        "Cannot trigger a hidden or disabled menu",
+                    ))
                 }
             }
             ConfigMenu::Option { bits, choices, .. } => {
@@ -549,11 +1423,12 @@ impl MisterFpgaCore {
                 let max = choices.len();
                 let value = bits.get_range(from..to) as usize;
                 bits.set_range(from..to, ((value + 1) % max) as u32);
-                self.send_status_bits(bits);
+                self.send_status_bits(bits)?;
+                self.persist_status_bits()?;
                 Ok(true)
             }
             ConfigMenu::Trigger { index, .. } => {
-                self.status_pulse(*index as usize);
+                self.status_pulse(*index as usize)?;
                 Ok(true)
             }
             ConfigMenu::PageItem(_, sub) => self.trigger_menu(sub),
@@ -566,18 +1441,14 @@ impl MisterFpgaCore {
 
 impl Core for MisterFpgaCore {
     fn init(&mut self) -> Result<(), Error> {
-        self.soft_reset();
-        self.fpga
-            .spi_mut()
-            .execute(user_io::SetMemorySize::from_fpga().unwrap())
-            .map_err(Error::Message)?;
+        self.soft_reset()?;
+        let memory_size =
+            user_io::SetMemorySize::from_fpga().map_err(|e| Error::Protocol(e.to_string()))?;
+        self.fpga.spi_mut().execute(memory_size)?;
 
         // Initialize the framebuffer.
         if !self.is_menu {
-            self.fpga
-                .spi_mut()
-                .execute(user_io::SetFramebufferToCore)
-                .map_err(Error::Message)?;
+            self.fpga.spi_mut().execute(user_io::SetFramebufferToCore)?;
         }
 
         let options = Config::base().into_inner();
@@ -626,9 +1497,24 @@ impl Core for MisterFpgaCore {
         self.config.name.as_str()
     }
 
+    fn capabilities(&self) -> Result<CoreCapabilities, Error> {
+        // The config string only declares that there is no keyboard, when it is
+        // locked to the joystick (`J1`). Other inputs and the clock are unknown.
+        Ok(CoreCapabilities {
+            save_state_slots: self.save_states().map_or(0, |s| s.nb_slots()),
+            mount_slots: self.config.mount_slots(),
+            bios_slots: self.config.bios_slots(),
+            screenshot: self.framebuffer.can_take_screenshot(),
+            keyboard: self.config.keyboard_locked().then_some(false),
+            mouse: None,
+            analog: None,
+            gamepads: GAMEPAD_COUNT,
+            rtc: None,
+        })
+    }
+
     fn reset(&mut self) -> Result<(), Error> {
-        self.soft_reset();
-        Ok(())
+        self.soft_reset()
     }
 
     fn volume(&self) -> Result<u8, Error> {
@@ -637,21 +1523,17 @@ impl Core for MisterFpgaCore {
 
     fn set_volume(&mut self, volume: u8) -> Result<(), Error> {
         self.volume = volume;
-        self.send_volume(Volume::scaled(volume))
-            .map_err(Error::Message)?;
+        self.send_volume(Volume::scaled(volume))?;
         Ok(())
     }
 
     fn set_rtc(&mut self, time: SystemTime) -> Result<(), Error> {
-        self.fpga
-            .spi_mut()
-            .execute(UserIoRtc::from(time))
-            .map_err(Error::Message)?;
+        self.fpga.spi_mut().execute(UserIoRtc::from(time))?;
         Ok(())
     }
 
     fn screenshot(&self) -> Result<DynamicImage, Error> {
-        self.take_screenshot().map_err(Error::Message)
+        self.take_screenshot()
     }
 
     fn save_state_mut(&mut self, slot: usize) -> Result<Option<&mut dyn SaveState>, Error> {
@@ -689,57 +1571,120 @@ impl Core for MisterFpgaCore {
         }
     }
 
-    fn send_rom(&mut self, rom: Rom) -> Result<(), Error> {
-        match rom {
-            Rom::Memory(_, _) => Err(Error::Message(
-                "Memory ROMs are not supported yet.".to_string(),
-            )),
-            Rom::File(path) => self.load_file(&path, None).map_err(Error::Message),
+    fn insert_image(&mut self, slot: usize, path: &Path) -> Result<(), Error> {
+        let index = u8::try_from(slot)
+            .ok()
+            .filter(|i| (*i as usize) < self.cards.len())
+            .ok_or(Error::InvalidSlot(slot))?;
+
+        let (path, playlist) = if Playlist::is_playlist(path) {
+            let playlist = Playlist::from_path(path)?;
+            (playlist.current().to_path_buf(), Some(playlist))
+        } else {
+            (path.to_path_buf(), None)
+        };
+
+        self.swap(SdCard::from_path(&path)?, index)?;
+        self.playlists[slot] = playlist;
+        Ok(())
+    }
+
+    fn eject_image(&mut self, slot: usize) -> Result<(), Error> {
+        let index = u8::try_from(slot).map_err(|_| Error::InvalidSlot(slot))?;
+        self.unmount(index)?;
+        if let Some(playlist) = self.playlists.get_mut(slot) {
+            *playlist = None;
+        }
+        Ok(())
+    }
+
+    fn next_disk(&mut self, slot: usize) -> Result<Option<PathBuf>, Error> {
+        let Some(playlist) = self.playlists.get(slot).and_then(Option::as_ref) else {
+            return Ok(None);
+        };
+        let next = (playlist.index() + 1) % playlist.disks().len();
+        let path = playlist.disks()[next].clone();
+
+        // The playlist only moves to the next disk once it is inserted.
+        info!(slot, ?path, "Inserting next disk");
+        self.swap(SdCard::from_path(&path)?, slot as u8)?;
+        if let Some(playlist) = self.playlists[slot].as_mut() {
+            playlist.select(next);
         }
+        Ok(Some(path))
+    }
+
+    fn send_rom(&mut self, rom: Rom) -> Result<(), Error> {
+        self.send_rom_with_progress(rom, &mut |_| true)
     }
 
-    fn send_bios(&mut self, _bios: Bios) -> Result<(), Error> {
-        todo!()
+    fn send_bios(&mut self, bios: Bios) -> Result<(), Error> {
+        self.send_bios(bios)
     }
 
     fn key_up(&mut self, key: Scancode) -> Result<(), Error> {
-        self.key_up(key);
-        Ok(())
+        self.key_up(key)
     }
 
     fn key_down(&mut self, key: Scancode) -> Result<(), Error> {
-        self.key_down(key);
-        Ok(())
+        self.key_down(key)
     }
 
-    fn keys_set(&mut self, _keys: ScancodeSet) -> Result<(), Error> {
-        todo!()
+    fn keys_set(&mut self, keys: ScancodeSet) -> Result<(), Error> {
+        self.keys_set(&keys)
     }
 
     fn keys(&self) -> Result<ScancodeSet, Error> {
-        todo!()
+        Ok(self.keys().clone())
     }
 
     fn gamepad_button_up(&mut self, index: usize, button: Button) -> Result<(), Error> {
-        self.gamepad_button_up(index as u8, button as u8);
-        Ok(())
+        self.gamepad_button_up(index as u8, button)
     }
 
     fn gamepad_button_down(&mut self, index: usize, button: Button) -> Result<(), Error> {
-        self.gamepad_button_down(index as u8, button as u8);
-        Ok(())
+        self.gamepad_button_down(index as u8, button)
+    }
+
+    fn gamepad_buttons_set(&mut self, index: usize, buttons: ButtonSet) -> Result<(), Error> {
+        self.gamepad_buttons_set(index as u8, buttons)
+    }
+
+    fn gamepad_buttons(&self, index: usize) -> Result<Option<ButtonSet>, Error> {
+        Ok(self.gamepad_buttons(index as u8))
+    }
+
+    fn gamepad_axis_motion(&mut self, index: usize, axis: Axis, value: i16) -> Result<(), Error> {
+        self.gamepad_axis_motion(index as u8, axis, value)
+    }
+
+    fn mouse_move(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        self.mouse_move(dx, dy)
     }
 
-    fn gamepad_buttons_set(&mut self, _index: usize, _buttons: ButtonSet) -> Result<(), Error> {
-        todo!()
+    fn mouse_wheel(&mut self, delta: i32) -> Result<(), Error> {
+        self.mouse_wheel(delta)
     }
 
-    fn gamepad_buttons(&self, _index: usize) -> Result<Option<ButtonSet>, Error> {
-        todo!()
+    fn mouse_button_up(&mut self, button: MouseButton) -> Result<(), Error> {
+        self.mouse_button_up(button)
+    }
+
+    fn mouse_button_down(&mut self, button: MouseButton) -> Result<(), Error> {
+        self.mouse_button_down(button)
     }
 
     fn settings(&self) -> Result<CoreSettings, Error> {
-        Ok(self.config.as_core_settings(self.status_bits()))
+        let mut settings = self.config.as_core_settings(
+            self.status_bits(),
+            &self.dip_switches,
+            &self.cheats,
+            &self.remembered_files,
+        );
+        if let Some(uart) = self.uart.as_core_menu_item(self.config.settings()) {
+            settings.add_item(uart);
+        }
+        Ok(settings)
     }
 
     fn trigger(&mut self, id: SettingId) -> Result<(), Error> {
@@ -749,24 +1694,43 @@ impl Core for MisterFpgaCore {
             .filter_map(ConfigMenu::as_trigger)
             .find(|item| item.setting_id() == Some(id))
         {
-            self.status_pulse(*index as usize);
+            self.status_pulse(*index as usize)?;
+        } else if let Some(index) = self
+            .menu_options()
+            .iter()
+            .filter_map(ConfigMenu::as_remembered_file_info)
+            .find(|info| RememberedFiles::clear_setting_id(info) == id)
+            .map(|info| info.index)
+        {
+            self.set_remembered_file(index, None)?;
         }
 
         Ok(())
     }
 
     fn file_select(&mut self, id: SettingId, path: String) -> Result<(), Error> {
-        if let Some(info) = self
+        if let Some(menu) = self
             .menu_options()
             .iter()
-            .filter_map(ConfigMenu::as_load_file_info)
-            .find(|info| info.setting_id() == id)
+            .find(|menu| {
+                menu.as_load_file()
+                    .and_then(ConfigMenu::as_load_file_info)
+                    .is_some_and(|info| info.setting_id() == id)
+            })
             .cloned()
         {
-            self.load_file(Path::new(&path), Some(info))
-                .map_err(Error::Message)?;
+            let path = PathBuf::from(path);
+            let info = menu
+                .as_load_file()
+                .and_then(ConfigMenu::as_load_file_info)
+                .cloned();
+            self.load_file(&path, info)?;
             self.end_send_file()?;
             self.poll_mounts()?;
+
+            if let Some(info) = menu.as_remembered_file_info() {
+                self.set_remembered_file(info.index, Some(path))?;
+            }
         }
         Ok(())
     }
@@ -783,10 +1747,15 @@ impl Core for MisterFpgaCore {
             let max = choices.len();
             bits.set_range(from..to, (value as usize % max) as u32);
             let new_value = bits.get_range(from..to);
-            self.send_status_bits(bits);
+            self.send_status_bits(bits)?;
+            self.persist_status_bits()?;
             Ok(new_value)
+        } else if let Some(choice) = self.set_uart_option(id, value as usize)? {
+            Ok(choice as u32)
         } else {
-            Ok(0)
+            Ok(self
+                .set_dip_choice(id, value as usize)?
+                .map_or(0, |choice| choice as u32))
         }
     }
 
@@ -801,10 +1770,16 @@ impl Core for MisterFpgaCore {
             let mut bits = *self.status_bits();
             bits.set_range(from..to, if value { 1 } else { 0 });
             let new_value = bits.get_range(from..to) != 0;
-            self.send_status_bits(bits);
+            self.send_status_bits(bits)?;
+            self.persist_status_bits()?;
             Ok(new_value)
         } else {
-            Ok(false)
+            if let Some(enabled) = self.set_cheat_enabled(id, value)? {
+                return Ok(enabled);
+            }
+            Ok(self
+                .set_dip_choice(id, value as usize)?
+                .is_some_and(|choice| choice != 0))
         }
     }
 
@@ -817,10 +1792,285 @@ impl Core for MisterFpgaCore {
     }
 
     fn quit(&mut self) {
-        self.should_quit = true;
+        // Write back the files the core wrote to before it is closed.
+        for (slot, card) in self.cards.iter_mut().enumerate() {
+            if let Some(card) = card {
+                if let Err(error) = card.as_io().flush() {
+                    warn!(%error, slot, "Could not write back SD card");
+                }
+            }
+        }
+        if !self.should_quit {
+            self.should_quit = true;
+            self.events.push(CoreEvent::QuitRequested);
+        }
     }
 
     fn should_quit(&self) -> bool {
         self.should_quit
     }
+
+    fn poll_events(&mut self) -> Result<Vec<CoreEvent>, Error> {
+        self.poll_events()
+    }
+}
+
+#[test]
+fn transfer_size_limit() {
+    assert_eq!(transfer_size(0xFFFF_FFFF).unwrap(), 0xFFFF_FFFF);
+    assert!(matches!(
+        transfer_size(0x1_0000_0000),
+        Err(Error::Unsupported(_))
+    ));
+}
+
+#[test]
+fn send_file_progress() {
+    let (mut core, spi) = MisterFpgaCore::fake("TEST;;F1,BIN,Load;V,v1");
+    let data = vec![0xAA; 10_000];
+
+    let mut reports = Vec::new();
+    core.load_reader_with_progress(data.as_slice(), "BIN", 10_000, None, &mut |p| {
+        reports.push(*p);
+        true
+    })
+    .unwrap();
+    assert!(reports.windows(2).all(|w| w[0].sent <= w[1].sent));
+    assert_eq!(
+        reports.last().map(|p| (p.index, p.sent, p.total)),
+        Some((1, 10_000, 10_000))
+    );
+    assert_eq!(core.file_crc(), Some(crc32fast::hash(&data)));
+
+    // Cancelling after the first chunk stops the transfer and disables it.
+    spi.borrow_mut().commands.clear();
+    let result = core
+        .load_reader_with_progress(data.as_slice(), "BIN", 10_000, None, &mut |p| p.sent < 4096);
+    assert!(matches!(result, Err(Error::Cancelled)));
+    let commands = &spi.borrow().commands;
+    assert_eq!(commands.iter().filter(|(c, _)| *c == 0x54).count(), 1);
+    assert_eq!(commands.last(), Some(&(0x53, vec![0])));
+}
+
+#[cfg(test)]
+struct ShortReads<'a>(&'a [u8]);
+
+#[cfg(test)]
+impl Read for ShortReads<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.0.len().min(buf.len()).min(3);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn send_file_short_reads() {
+    let (mut core, spi) = MisterFpgaCore::fake("TEST;;F1,BIN,Load;V,v1");
+    let data = (0..9_999u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    // Odd-length reads are sent as full words, with only the last byte padded.
+    core.load_reader(ShortReads(&data), "BIN", 9_999, None)
+        .unwrap();
+    let words = spi
+        .borrow()
+        .commands
+        .iter()
+        .filter(|(c, _)| *c == 0x54)
+        .flat_map(|(_, words)| words.iter().flat_map(|w| w.to_le_bytes()))
+        .collect::<Vec<_>>();
+    assert_eq!(words.len(), 10_000);
+    assert_eq!(&words[..9_999], &data[..]);
+    assert_eq!(words[9_999], 0);
+    assert_eq!(core.file_crc(), Some(crc32fast::hash(&data)));
+
+    // A reader shorter than the size of the file fails the transfer.
+    assert!(matches!(
+        core.load_reader(ShortReads(&data[..100]), "BIN", 9_999, None),
+        Err(Error::Protocol(_))
+    ));
+}
+
+#[cfg(test)]
+fn sent_commands(spi: &std::cell::RefCell<crate::fpga::FakeSpi>) -> Vec<u16> {
+    spi.borrow_mut()
+        .commands
+        .drain(..)
+        .map(|(c, _)| c)
+        .collect()
+}
+
+#[test]
+fn core_methods() {
+    let (mut core, spi) =
+        MisterFpgaCore::fake("TEST;UART115200;F1,BIN,Load;O1,Option,Off,On;T2,Reset;V,v1");
+    assert_eq!(Core::name(&core), "TEST");
+    let capabilities = core.capabilities().unwrap();
+    assert_eq!(capabilities.save_state_slots, 0);
+    assert!(capabilities.mount_slots.is_empty());
+    assert_eq!(capabilities.keyboard, None);
+    assert_eq!(
+        (capabilities.mouse, capabilities.analog, capabilities.rtc),
+        (None, None, None)
+    );
+    assert!(!capabilities.screenshot);
+
+    core.set_volume(128).unwrap();
+    assert_eq!(Core::volume(&core).unwrap(), 128);
+    assert_eq!(sent_commands(&spi), [0x26]);
+
+    core.set_rtc(SystemTime::UNIX_EPOCH).unwrap();
+    assert_eq!(sent_commands(&spi), [0x22]);
+
+    // A soft reset pulses bit 0 of the status bits.
+    Core::reset(&mut core).unwrap();
+    let commands = std::mem::take(&mut spi.borrow_mut().commands);
+    assert_eq!(commands.first().map(|(c, _)| *c), Some(0x29));
+    assert_eq!(commands.iter().filter(|(c, _)| *c == 0x1E).count(), 2);
+
+    let a = "A".parse::<Scancode>().unwrap();
+    Core::key_down(&mut core, a).unwrap();
+    assert!(Core::keys(&core).unwrap().contains(a));
+    Core::key_up(&mut core, a).unwrap();
+    assert!(!Core::keys(&core).unwrap().contains(a));
+    assert!(sent_commands(&spi).iter().all(|c| *c == 0x05));
+
+    Core::gamepad_button_down(&mut core, 0, Button::A).unwrap();
+    assert!(Core::gamepad_buttons(&core, 0)
+        .unwrap()
+        .is_some_and(|b| b.contains(Button::A)));
+    Core::gamepad_button_up(&mut core, 0, Button::A).unwrap();
+    assert!(sent_commands(&spi).contains(&0x02));
+
+    Core::mouse_move(&mut core, 3, -2).unwrap();
+    Core::mouse_button_down(&mut core, MouseButton::Left).unwrap();
+    assert!(sent_commands(&spi).iter().all(|c| *c == 0x04));
+
+    assert!(Core::save_state(&core, 0).unwrap().is_none());
+    assert!(core.mounted_file_mut(0).unwrap().is_none());
+    assert!(matches!(
+        core.insert_image(16, Path::new("disk.img")),
+        Err(Error::InvalidSlot(16))
+    ));
+    assert_eq!(Core::next_disk(&mut core, 0).unwrap(), None);
+
+    // Options and triggers are sent as status bits.
+    let option = core
+        .menu_options()
+        .iter()
+        .filter_map(ConfigMenu::as_option)
+        .find_map(ConfigMenu::setting_id)
+        .unwrap();
+    assert!(core.bool_option(option, true).unwrap());
+    assert!(core.status_bits().get(1));
+    let trigger = core
+        .menu_options()
+        .iter()
+        .filter_map(ConfigMenu::as_trigger)
+        .find_map(ConfigMenu::setting_id)
+        .unwrap();
+    core.trigger(trigger).unwrap();
+    assert!(!core.status_bits().get(2));
+    assert!(sent_commands(&spi).contains(&0x1E));
+    let settings = Core::settings(&core).unwrap();
+    assert!(matches!(
+        settings.items().last(),
+        Some(one_fpga::core::CoreSettingItem::Page { id, .. }) if *id == SettingId::from_label("uart")
+    ));
+
+    // ROMs in memory are sent through the file transfer commands, then the core
+    // is released from reset.
+    let rom = Rom::Memory(
+        Some(PathBuf::from("game.bin")),
+        std::io::Cursor::new(vec![1, 2, 3, 4]),
+    );
+    Core::send_rom(&mut core, rom).unwrap();
+    assert_eq!(sent_commands(&spi), [0x55, 0x56, 0x53, 0x54, 0x29, 0x1E]);
+
+    assert!(!core.should_quit());
+    Core::quit(&mut core);
+    assert!(core.should_quit());
+    assert!(core
+        .poll_events()
+        .unwrap()
+        .contains(&CoreEvent::QuitRequested));
+}
+
+#[test]
+fn capabilities_from_config() {
+    // A console core locks the keyboard to the joystick.
+    let (core, _) = MisterFpgaCore::fake("NES;;FS,NES,Load;J1,A,B,Select,Start;V,v1");
+    let capabilities = core.capabilities().unwrap();
+    assert_eq!(capabilities.keyboard, Some(false));
+    assert_eq!(capabilities.analog, None);
+
+    // A computer core doesn't declare its inputs.
+    let (core, _) = MisterFpgaCore::fake("AO486;UART115200;S0,IMG,Floppy;J,Fire;V,v1");
+    let capabilities = core.capabilities().unwrap();
+    assert_eq!(capabilities.keyboard, None);
+    assert_eq!((capabilities.mouse, capabilities.rtc), (None, None));
+    assert_eq!(capabilities.mount_slots.len(), 1);
+}
+
+#[test]
+fn remembered_file_missing() {
+    let (mut core, spi) = MisterFpgaCore::fake("TEST;;FC3,PAL,Palette;FC4,ROM,Alt;V,v1");
+    let dir = std::env::temp_dir().join(format!("mister-fpga-remember-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("unreadable.rom")).unwrap();
+    let remember = dir.join("remember.txt");
+    let missing = dir.join("deleted.pal");
+    let unreadable = dir.join("unreadable.rom");
+    std::fs::write(
+        &remember,
+        format!("3\t{}\n4\t{}\n", missing.display(), unreadable.display()),
+    )
+    .unwrap();
+
+    // Files that are gone or can't be read don't prevent the others from loading.
+    core.load_remember_file(&remember).unwrap();
+    assert!(!sent_commands(&spi).contains(&0x54));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn disk_swap() {
+    let (mut core, spi) = MisterFpgaCore::fake("TEST;;S1,DSK,Mount;V,v1");
+    let dir = std::env::temp_dir().join(format!("mister-fpga-swap-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("disk1.dsk"), [0; 1024]).unwrap();
+    std::fs::write(dir.join("disk2.dsk"), [0; 2048]).unwrap();
+    std::fs::write(dir.join("game.m3u"), "disk1.dsk\ndisk2.dsk\nmissing.cue\n").unwrap();
+
+    // Inserting a disk ejects the previous one first: an empty SetSdInfo and a
+    // SetSdStat, then the new size and SetSdStat again.
+    let sd_commands = |spi: &std::cell::RefCell<crate::fpga::FakeSpi>| {
+        std::mem::take(&mut spi.borrow_mut().commands)
+            .into_iter()
+            .filter(|(c, _)| [0x1C, 0x1D].contains(c))
+            .collect::<Vec<_>>()
+    };
+    Core::insert_image(&mut core, 1, &dir.join("game.m3u")).unwrap();
+    let commands = sd_commands(&spi);
+    assert_eq!(commands[..2], [(0x1D, vec![0; 4]), (0x1C, vec![0x82])]);
+    assert_eq!(commands[2], (0x1D, vec![1024, 0, 0, 0]));
+    assert_eq!(commands[3].0, 0x1C);
+    assert_eq!(commands[3].1[0] & 0x7F, 0x02);
+
+    let next = Core::next_disk(&mut core, 1).unwrap();
+    assert_eq!(next, Some(dir.join("disk2.dsk")));
+    assert_eq!(core.playlist(1).map(Playlist::index), Some(1));
+    assert_eq!(sd_commands(&spi)[2], (0x1D, vec![2048, 0, 0, 0]));
+
+    // A disk that can't be opened leaves the playlist on the current disk.
+    assert!(Core::next_disk(&mut core, 1).is_err());
+    assert_eq!(core.playlist(1).map(Playlist::index), Some(1));
+    assert!(sd_commands(&spi).is_empty());
+
+    Core::eject_image(&mut core, 1).unwrap();
+    assert_eq!(sd_commands(&spi), [(0x1D, vec![0; 4]), (0x1C, vec![0x82])]);
+    assert!(core.playlist(1).is_none());
+
+    std::fs::remove_dir_all(dir).unwrap();
 }
@@ -0,0 +1,140 @@
+use one_fpga::inputs::MouseButton;
+
+/// Largest motion that can be sent in a single PS/2 packet (9 bits, signed).
+const PS2_MAX_MOTION: i32 = 255;
+
+/// Largest wheel motion that can be sent in a single PS/2 packet (4 bits, signed).
+const PS2_MAX_WHEEL: i32 = 7;
+
+/// A single PS/2 mouse packet, as sent to the core. The Y axis and the wheel
+/// follow the PS/2 convention (positive Y is up, positive wheel is down).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Ps2MousePacket {
+    pub buttons: u8,
+    pub dx: i16,
+    pub dy: i16,
+    pub wheel: i8,
+}
+
+impl Ps2MousePacket {
+    /// The first byte of the packet, containing the buttons, sign and overflow bits.
+    pub fn flags(&self) -> u8 {
+        let mut flags = (self.buttons & 0x07) | 0x08;
+        if self.dx < 0 {
+            flags |= 0x10;
+        }
+        if self.dy < 0 {
+            flags |= 0x20;
+        }
+        flags
+    }
+}
+
+/// Accumulate mouse events from the host into PS/2 packets. Motion is divided by
+/// the `mouse_throttle` option, and any motion that does not fit in a packet (or
+/// that is smaller than the throttle) is kept for the next one.
+#[derive(Debug, Clone)]
+pub struct MouseState {
+    buttons: u8,
+    dx: i32,
+    dy: i32,
+    wheel: i32,
+    throttle: i32,
+
+    // Whether the buttons changed since the last packet.
+    dirty: bool,
+}
+
+impl Default for MouseState {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl MouseState {
+    pub fn new(throttle: u8) -> Self {
+        Self {
+            buttons: 0,
+            dx: 0,
+            dy: 0,
+            wheel: 0,
+            throttle: throttle.max(1) as i32,
+            dirty: false,
+        }
+    }
+
+    pub fn move_by(&mut self, dx: i32, dy: i32) {
+        self.dx = self.dx.saturating_add(dx);
+        self.dy = self.dy.saturating_add(dy);
+    }
+
+    /// Add wheel motion. Positive values scroll up.
+    pub fn wheel(&mut self, delta: i32) {
+        self.wheel = self.wheel.saturating_add(delta);
+    }
+
+    pub fn button_down(&mut self, button: MouseButton) {
+        self.dirty |= self.buttons & button.mask() == 0;
+        self.buttons |= button.mask();
+    }
+
+    pub fn button_up(&mut self, button: MouseButton) {
+        self.dirty |= self.buttons & button.mask() != 0;
+        self.buttons &= !button.mask();
+    }
+
+    /// Take the next packet to send to the core, or `None` if there is nothing
+    /// new to send.
+    pub fn next_packet(&mut self) -> Option<Ps2MousePacket> {
+        let dx = (self.dx / self.throttle).clamp(-PS2_MAX_MOTION, PS2_MAX_MOTION);
+        let dy = (self.dy / self.throttle).clamp(-PS2_MAX_MOTION, PS2_MAX_MOTION);
+        let wheel = self.wheel.clamp(-PS2_MAX_WHEEL, PS2_MAX_WHEEL);
+
+        if !self.dirty && dx == 0 && dy == 0 && wheel == 0 {
+            return None;
+        }
+
+        self.dx -= dx * self.throttle;
+        self.dy -= dy * self.throttle;
+        self.wheel -= wheel;
+        self.dirty = false;
+
+        Some(Ps2MousePacket {
+            buttons: self.buttons,
+            dx: dx as i16,
+            dy: -dy as i16,
+            wheel: -wheel as i8,
+        })
+    }
+}
+
+#[test]
+fn mouse_packets() {
+    let mut mouse = MouseState::new(2);
+    assert_eq!(mouse.next_packet(), None);
+
+    mouse.move_by(5, -3);
+    assert_eq!(
+        mouse.next_packet(),
+        Some(Ps2MousePacket {
+            buttons: 0,
+            dx: 2,
+            dy: 1,
+            wheel: 0
+        })
+    );
+    // The remainder is kept until it is larger than the throttle.
+    assert_eq!(mouse.next_packet(), None);
+    mouse.move_by(1, 0);
+    assert_eq!(mouse.next_packet().map(|p| p.dx), Some(1));
+
+    mouse.button_down(MouseButton::Right);
+    let packet = mouse.next_packet().unwrap();
+    assert_eq!(packet.flags(), 0x0A);
+    assert_eq!(mouse.next_packet(), None);
+
+    mouse.move_by(-1000, 0);
+    assert_eq!(mouse.next_packet().map(|p| p.dx), Some(-255));
+    assert_eq!(mouse.next_packet().map(|p| p.dx), Some(-245));
+    assert_eq!(mouse.next_packet(), None);
+}
@@ -0,0 +1,351 @@
+//! Save files (e.g. battery backed SRAM) mounted to cores. Writes from the core
+//! are kept in memory and written back to the file once the core stops writing
+//! for a while, so a save written sector by sector is written once. Files are
+//! replaced atomically (written to a temporary file, then renamed), and the
+//! previous content is kept in timestamped backups next to the save file.
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use tracing::{debug, warn};
+
+/// The name of the directory, next to save files, that holds their backups.
+const BACKUP_DIR: &str = ".backups";
+
+/// Options for writing save files back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveFileOptions {
+    /// How long the core must not write to the save before it is written back.
+    pub flush_delay: Duration,
+
+    /// The number of backups to keep per save file. A backup of the file is
+    /// made when it is first written back after being mounted.
+    pub backups: usize,
+}
+
+impl Default for SaveFileOptions {
+    fn default() -> Self {
+        Self {
+            flush_delay: Duration::from_secs(2),
+            backups: 5,
+        }
+    }
+}
+
+/// A backup of a save file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveBackup {
+    pub path: PathBuf,
+    pub timestamp: SystemTime,
+}
+
+/// Write a file atomically. The content is written to a temporary file next to
+/// it which is synced to disk before it replaces the file.
+pub fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dir.join(format!(".{name}.tmp"));
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+
+    // Sync the directory so the rename itself is persisted.
+    if let Err(error) = File::open(dir).and_then(|d| d.sync_all()) {
+        debug!(%error, ?dir, "Could not sync directory");
+    }
+    Ok(())
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).join(BACKUP_DIR)
+}
+
+/// The backups of a save file, newest first.
+pub fn backups(path: &Path) -> std::io::Result<Vec<SaveBackup>> {
+    let prefix = format!(
+        "{}.",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let dir = match std::fs::read_dir(backup_dir(path)) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut backups = dir
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|e| {
+            Some(SaveBackup {
+                timestamp: e.metadata().ok()?.modified().ok()?,
+                path: e.path(),
+            })
+        })
+        .collect::<Vec<_>>();
+    // Names end with the time of the backup, so they sort chronologically.
+    backups.sort_by(|a, b| b.path.cmp(&a.path));
+    Ok(backups)
+}
+
+/// Copy a save file to a new backup, and remove the oldest backups so only
+/// `keep` remain.
+fn backup(path: &Path, keep: usize) -> std::io::Result<()> {
+    if keep == 0 || !path.exists() {
+        return Ok(());
+    }
+
+    let dir = backup_dir(path);
+    std::fs::create_dir_all(&dir)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+    let target = dir.join(format!("{name}.{timestamp}"));
+    debug!(?path, ?target, "Backing up save file");
+    write_atomic(&target, &std::fs::read(path)?)?;
+
+    for old in backups(path)?.into_iter().skip(keep) {
+        std::fs::remove_file(old.path)?;
+    }
+    Ok(())
+}
+
+/// Replace a save file with one of its backups. The current content of the
+/// save is backed up first, so the restore can be undone. This should not be
+/// done while the save is mounted, as the core would overwrite it.
+pub fn restore_backup(path: &Path, backup_path: &Path, keep: usize) -> std::io::Result<()> {
+    if backup_path.parent() != Some(backup_dir(path).as_path()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{backup_path:?} is not a backup of {path:?}"),
+        ));
+    }
+
+    let data = std::fs::read(backup_path)?;
+    backup(path, keep.max(1))?;
+    write_atomic(path, &data)
+}
+
+/// A save file mounted to a core.
+#[derive(Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    data: Vec<u8>,
+    position: u64,
+    options: SaveFileOptions,
+
+    /// The time of the last write not written back yet.
+    last_write: Option<Instant>,
+
+    /// Whether the file was backed up since it was opened.
+    backed_up: bool,
+}
+
+impl SaveFile {
+    /// Open a save file. A file that does not exist is created when the core
+    /// first writes to it.
+    pub fn open(path: impl AsRef<Path>, options: SaveFileOptions) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            data,
+            position: 0,
+            options,
+            last_write: None,
+            backed_up: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// Whether there are writes that were not written back yet.
+    pub fn is_dirty(&self) -> bool {
+        self.last_write.is_some()
+    }
+
+    /// Write the file back if the core has not written to it for the flush
+    /// delay. Returns whether the file was written.
+    pub fn flush_if_idle(&mut self, now: Instant) -> std::io::Result<bool> {
+        match self.last_write {
+            Some(last) if now.duration_since(last) >= self.options.flush_delay => {
+                self.write_back()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn write_back(&mut self) -> std::io::Result<()> {
+        if self.last_write.is_none() {
+            return Ok(());
+        }
+        if !self.backed_up {
+            backup(&self.path, self.options.backups)?;
+            self.backed_up = true;
+        }
+
+        debug!(path = ?self.path, size = self.data.len(), "Writing save file");
+        write_atomic(&self.path, &self.data)?;
+        self.last_write = None;
+        Ok(())
+    }
+}
+
+impl Read for SaveFile {
+    /// Read from the save. Reading past its end reads zeros, like an SRAM that
+    /// was never written.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = (self.position as usize).min(self.data.len());
+        let len = (self.data.len() - start).min(buf.len());
+        buf[..len].copy_from_slice(&self.data[start..start + len]);
+        buf[len..].fill(0);
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+}
+
+impl Write for SaveFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let start = self.position as usize;
+        if self.data.len() < start + buf.len() {
+            self.data.resize(start + buf.len(), 0);
+        }
+        self.data[start..start + buf.len()].copy_from_slice(buf);
+        self.position += buf.len() as u64;
+        self.last_write = Some(Instant::now());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_back()
+    }
+}
+
+impl Seek for SaveFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(p) => self.size().checked_add_signed(p),
+            SeekFrom::Current(p) => self.position.checked_add_signed(p),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl Drop for SaveFile {
+    fn drop(&mut self) {
+        if let Err(error) = self.write_back() {
+            warn!(%error, path = ?self.path, "Could not write save file");
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mister-fpga-save-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn save_file_write_back() {
+    let dir = test_dir("write-back");
+    let path = dir.join("game.sav");
+    std::fs::write(&path, [1u8; 1024]).unwrap();
+    let options = SaveFileOptions::default();
+    let mut save = SaveFile::open(&path, options).unwrap();
+
+    for sector in 0..2 {
+        save.seek(SeekFrom::Start(sector * 512)).unwrap();
+        save.write_all(&[2; 512]).unwrap();
+    }
+    save.seek(SeekFrom::Start(1024)).unwrap();
+    save.write_all(&[3; 512]).unwrap();
+
+    // Nothing is written until the core stops writing.
+    assert!(!save.flush_if_idle(Instant::now()).unwrap());
+    assert_eq!(std::fs::read(&path).unwrap(), [1; 1024]);
+    assert!(save.is_dirty());
+
+    let later = Instant::now() + options.flush_delay;
+    assert!(save.flush_if_idle(later).unwrap());
+    assert!(!save.is_dirty());
+    let content = std::fs::read(&path).unwrap();
+    assert_eq!(content.len(), 1536);
+    assert_eq!(&content[..1024], &[2; 1024]);
+    assert_eq!(&content[1024..], &[3; 512]);
+
+    // The previous content was backed up.
+    let backups = backups(&path).unwrap();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(std::fs::read(&backups[0].path).unwrap(), [1; 1024]);
+
+    // Reading past the end reads zeros.
+    let mut buffer = [0xFF; 512];
+    save.seek(SeekFrom::Start(1536)).unwrap();
+    save.read_exact(&mut buffer).unwrap();
+    assert_eq!(buffer, [0; 512]);
+
+    // Dropping the file writes it back.
+    save.seek(SeekFrom::Start(0)).unwrap();
+    save.write_all(&[4; 512]).unwrap();
+    drop(save);
+    assert_eq!(&std::fs::read(&path).unwrap()[..512], &[4; 512]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn save_file_backups() {
+    let dir = test_dir("backups");
+    let path = dir.join("game.sav");
+    let options = SaveFileOptions {
+        flush_delay: Duration::ZERO,
+        backups: 2,
+    };
+
+    // A new file has nothing to back up.
+    let mut save = SaveFile::open(&path, options).unwrap();
+    save.write_all(&[0]).unwrap();
+    save.flush().unwrap();
+    drop(save);
+    assert!(backups(&path).unwrap().is_empty());
+
+    for i in 1..=3 {
+        let mut save = SaveFile::open(&path, options).unwrap();
+        save.write_all(&[i]).unwrap();
+        save.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    let list = backups(&path).unwrap();
+    let content = |b: &SaveBackup| std::fs::read(&b.path).unwrap();
+    assert_eq!(list.iter().map(content).collect::<Vec<_>>(), [[2], [1]]);
+    assert_eq!(std::fs::read(&path).unwrap(), [3]);
+
+    restore_backup(&path, &list[1].path, options.backups).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), [1]);
+    let list = backups(&path).unwrap();
+    assert_eq!(list.iter().map(content).collect::<Vec<_>>(), [[3], [2]]);
+
+    assert!(restore_backup(&path, &path, options.backups).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
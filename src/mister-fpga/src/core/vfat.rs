@@ -0,0 +1,1417 @@
+//! A virtual FAT16/FAT32 hard disk backed by a directory of the host, so files
+//! can be shared with computer cores without building disk images.
+//!
+//! The disk has a partition table with a single partition. The boot sector, the
+//! FATs and the directories are generated from the content of the directory
+//! when the disk is created, and the clusters of files are read from the host
+//! files. Sectors the core writes in the clusters of a host file are written
+//! through to it. Other sectors are kept in memory, and the changes are written
+//! back to the host directory when the disk is flushed (or dropped): the file
+//! system is read back, files that changed are rewritten, and files that were
+//! removed are deleted. Nothing is written back while the file system is not
+//! consistent, e.g. while the core is in the middle of an update.
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use tracing::{debug, warn};
+
+/// The size of a sector.
+pub const SECTOR_SIZE: usize = 512;
+
+/// The sector the partition starts at.
+const PARTITION_START: u32 = 63;
+
+/// The size of a directory entry.
+const ENTRY_SIZE: usize = 32;
+
+/// The number of entries of the FAT16 root directory.
+const FAT16_ROOT_ENTRIES: u32 = 512;
+
+/// The free space of disks mounted from a directory without a size.
+pub const DEFAULT_FREE_SPACE: u64 = 512 * 1024 * 1024;
+
+/// The maximum number of written sectors kept in memory (64 MB).
+const MAX_WRITTEN_SECTORS: usize = 128 * 1024;
+
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+type Sector = [u8; SECTOR_SIZE];
+
+#[derive(Debug, thiserror::Error)]
+pub enum VfatError {
+    #[error("A disk of {0} bytes is too small for a FAT16 file system")]
+    TooSmall(u64),
+
+    #[error("A disk of {0} bytes is too large")]
+    TooLarge(u64),
+
+    #[error("Not enough space on the disk: {needed} clusters needed, {available} available")]
+    DiskFull { needed: u64, available: u64 },
+
+    #[error("The root directory has too many entries")]
+    RootDirectoryFull,
+
+    #[error("{0:?} is too large for a FAT file system")]
+    FileTooLarge(PathBuf),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<VfatError> for one_fpga::core::Error {
+    fn from(value: VfatError) -> Self {
+        match value {
+            VfatError::Io(e) => one_fpga::core::Error::IoError(e),
+            e => one_fpga::core::Error::Message(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    /// The smallest FAT entry value that marks the end of a chain.
+    fn end_of_chain(&self) -> u32 {
+        match self {
+            FatType::Fat16 => 0xFFF8,
+            FatType::Fat32 => 0x0FFF_FFF8,
+        }
+    }
+
+    /// The FAT entry value stored for the last cluster of a chain.
+    fn last_cluster(&self) -> u32 {
+        match self {
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+}
+
+/// The layout of the file system, in sectors from the start of the partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Geometry {
+    fat_type: FatType,
+    total_sectors: u32,
+    sectors_per_cluster: u32,
+    reserved_sectors: u32,
+    fat_sectors: u32,
+    root_dir_sectors: u32,
+    clusters: u32,
+}
+
+impl Geometry {
+    fn new(partition_size: u64) -> Result<Self, VfatError> {
+        let total_sectors = u32::try_from(partition_size / SECTOR_SIZE as u64)
+            .map_err(|_| VfatError::TooLarge(partition_size))?;
+        let (fat_type, reserved_sectors, root_dir_sectors, max_clusters, mut spc) =
+            if partition_size >= 512 * 1024 * 1024 {
+                (FatType::Fat32, 32, 0, 0x0FFF_FFF4, 8)
+            } else if partition_size >= 16 * 1024 * 1024 {
+                let root = FAT16_ROOT_ENTRIES * ENTRY_SIZE as u32 / SECTOR_SIZE as u32;
+                (FatType::Fat16, 1, root, 65524, 1)
+            } else {
+                return Err(VfatError::TooSmall(partition_size));
+            };
+        let entry_size = match fat_type {
+            FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        };
+
+        loop {
+            let mut fat_sectors = 1;
+            let clusters = loop {
+                let clusters =
+                    (total_sectors - reserved_sectors - root_dir_sectors - 2 * fat_sectors) / spc;
+                let needed = ((clusters as u64 + 2) * entry_size).div_ceil(SECTOR_SIZE as u64);
+                if needed <= fat_sectors as u64 {
+                    break clusters;
+                }
+                fat_sectors = needed as u32;
+            };
+
+            if clusters <= max_clusters {
+                return Ok(Self {
+                    fat_type,
+                    total_sectors,
+                    sectors_per_cluster: spc,
+                    reserved_sectors,
+                    fat_sectors,
+                    root_dir_sectors,
+                    clusters,
+                });
+            }
+            if spc == 128 {
+                return Err(VfatError::TooLarge(partition_size));
+            }
+            spc *= 2;
+        }
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster as usize * SECTOR_SIZE
+    }
+
+    fn fat_start(&self) -> u32 {
+        self.reserved_sectors
+    }
+
+    fn root_dir_start(&self) -> u32 {
+        self.reserved_sectors + 2 * self.fat_sectors
+    }
+
+    fn data_start(&self) -> u32 {
+        self.root_dir_start() + self.root_dir_sectors
+    }
+
+    /// The first sector of a cluster.
+    fn cluster_sector(&self, cluster: u32) -> u32 {
+        self.data_start() + (cluster - 2) * self.sectors_per_cluster
+    }
+}
+
+/// What a cluster contains, as generated from the host directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    Free,
+
+    /// A cluster of a generated directory.
+    Dir {
+        dir: usize,
+        index: u32,
+    },
+
+    /// A cluster of a host file.
+    File {
+        file: usize,
+        index: u32,
+    },
+}
+
+/// A file of the host directory, and where it is on the disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostFile {
+    /// The path relative to the root directory.
+    path: PathBuf,
+    size: u64,
+    start: u32,
+}
+
+/// A file or directory read from the file system of the disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskEntry {
+    /// The path relative to the root directory.
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub start: u32,
+    pub size: u64,
+}
+
+/// Convert a time to a FAT date and time.
+fn fat_date_time(time: SystemTime) -> (u16, u16) {
+    let time: DateTime<Local> = time.into();
+    if time.year() < 1980 {
+        return ((1 << 5) | 1, 0);
+    }
+    let date = (((time.year() - 1980).min(127) as u16) << 9)
+        | ((time.month() as u16) << 5)
+        | time.day() as u16;
+    let time =
+        ((time.hour() as u16) << 11) | ((time.minute() as u16) << 5) | (time.second() as u16 / 2);
+    (date, time)
+}
+
+/// The checksum of a short name, stored in its long name entries.
+fn short_name_checksum(name: &[u8; 11]) -> u8 {
+    name.iter().fold(0u8, |sum, b| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(*b)
+    })
+}
+
+/// Make a short (8.3) name for a file name. Returns the name and whether a
+/// long name is needed to keep the original name.
+fn short_name(name: &str, taken: &HashSet<[u8; 11]>) -> ([u8; 11], bool) {
+    let (base, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i + 1..]),
+        _ => (name, ""),
+    };
+    let mut lossy = false;
+    let mut convert = |s: &str| {
+        let mut result = Vec::new();
+        for c in s.chars() {
+            let upper = c.to_ascii_uppercase();
+            if c == ' ' || c == '.' {
+                lossy = true;
+            } else if upper.is_ascii_alphanumeric() || "$%'-_@~`!(){}^#&".contains(upper) {
+                result.push(upper as u8);
+            } else {
+                lossy = true;
+                result.push(b'_');
+            }
+        }
+        result
+    };
+    let base = convert(base);
+    let ext = convert(ext);
+    let lossy = lossy || base.len() > 8 || ext.len() > 3 || base.is_empty();
+
+    let make = |base: &[u8], suffix: &[u8]| {
+        let mut short = [b' '; 11];
+        let len = base.len().min(8 - suffix.len());
+        short[..len].copy_from_slice(&base[..len]);
+        short[len..len + suffix.len()].copy_from_slice(suffix);
+        let ext_len = ext.len().min(3);
+        short[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+        short
+    };
+
+    let needs_long = lossy || name.chars().any(|c| c.is_lowercase());
+    let short = make(&base, &[]);
+    if !lossy && !taken.contains(&short) {
+        return (short, needs_long);
+    }
+    let short = (1..)
+        .map(|n| make(&base, format!("~{n}").as_bytes()))
+        .find(|s| !taken.contains(s))
+        .unwrap();
+    (short, true)
+}
+
+/// The long name entries of a name, in the order they are stored.
+fn long_name_entries(name: &str, checksum: u8) -> Vec<[u8; ENTRY_SIZE]> {
+    const OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+    let units = name.encode_utf16().collect::<Vec<_>>();
+    let count = units.len().div_ceil(13);
+
+    (0..count)
+        .rev()
+        .map(|i| {
+            let mut entry = [0u8; ENTRY_SIZE];
+            entry[0] = (i + 1) as u8 | if i == count - 1 { 0x40 } else { 0 };
+            entry[11] = ATTR_LONG_NAME;
+            entry[13] = checksum;
+            for (j, offset) in OFFSETS.iter().enumerate() {
+                let unit = match (i * 13 + j).cmp(&units.len()) {
+                    std::cmp::Ordering::Less => units[i * 13 + j],
+                    std::cmp::Ordering::Equal => 0,
+                    std::cmp::Ordering::Greater => 0xFFFF,
+                };
+                entry[*offset..*offset + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            entry
+        })
+        .collect()
+}
+
+fn dir_entry(name: &[u8; 11], attr: u8, start: u32, size: u32, modified: SystemTime) -> [u8; 32] {
+    let (date, time) = fat_date_time(modified);
+    let mut entry = [0u8; ENTRY_SIZE];
+    entry[..11].copy_from_slice(name);
+    entry[11] = attr;
+    entry[14..16].copy_from_slice(&time.to_le_bytes());
+    entry[16..18].copy_from_slice(&date.to_le_bytes());
+    entry[18..20].copy_from_slice(&date.to_le_bytes());
+    entry[20..22].copy_from_slice(&((start >> 16) as u16).to_le_bytes());
+    entry[22..24].copy_from_slice(&time.to_le_bytes());
+    entry[24..26].copy_from_slice(&date.to_le_bytes());
+    entry[26..28].copy_from_slice(&(start as u16).to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+/// A virtual FAT disk backed by a host directory.
+pub struct VirtualFat {
+    root: PathBuf,
+    geometry: Geometry,
+    serial: u32,
+
+    /// The generated FAT.
+    fat: Vec<u32>,
+
+    /// The content of the generated directories. The first one is the root.
+    dirs: Vec<Vec<u8>>,
+
+    /// The directories of the host, relative to the root.
+    host_dirs: HashSet<PathBuf>,
+    files: Vec<Option<HostFile>>,
+
+    /// What each cluster contains, starting at cluster 2.
+    owners: Vec<Owner>,
+    next_free: u32,
+
+    /// Sectors written by the core that are not on the host yet, by their disk
+    /// sector number.
+    written: HashMap<u64, Box<Sector>>,
+
+    /// Data clusters written since the last flush.
+    dirty_clusters: HashSet<u32>,
+
+    /// Whether sectors outside of the data clusters were written since the
+    /// last flush.
+    dirty: bool,
+
+    position: u64,
+    open_file: Option<(usize, File)>,
+}
+
+impl std::fmt::Debug for VirtualFat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualFat")
+            .field("root", &self.root)
+            .field("geometry", &self.geometry)
+            .field("written", &self.written.len())
+            .finish()
+    }
+}
+
+impl VirtualFat {
+    /// Create a disk of `size` bytes with the content of a host directory.
+    pub fn new(root: impl AsRef<Path>, size: u64) -> Result<Self, VfatError> {
+        let root = root.as_ref().to_path_buf();
+        let partition_size = size.saturating_sub(PARTITION_START as u64 * SECTOR_SIZE as u64);
+        let geometry = Geometry::new(partition_size)?;
+
+        let mut fat = vec![0u32; geometry.clusters as usize + 2];
+        // The media descriptor, and the end of chain marker.
+        fat[0] = geometry.fat_type.end_of_chain();
+        fat[1] = geometry.fat_type.last_cluster();
+
+        let serial = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        let mut this = Self {
+            root,
+            geometry,
+            serial,
+            fat,
+            dirs: Vec::new(),
+            host_dirs: HashSet::new(),
+            files: Vec::new(),
+            owners: vec![Owner::Free; geometry.clusters as usize],
+            next_free: 2,
+            written: HashMap::new(),
+            dirty_clusters: HashSet::new(),
+            dirty: false,
+            position: 0,
+            open_file: None,
+        };
+
+        let root_cluster = match geometry.fat_type {
+            FatType::Fat16 => 0,
+            FatType::Fat32 => this.allocate(1, |index| Owner::Dir { dir: 0, index })?,
+        };
+        this.add_dir(PathBuf::new(), root_cluster, 0)?;
+        debug!(?this, files = this.files.len(), "Created virtual FAT disk");
+        Ok(this)
+    }
+
+    /// Create a disk with the content of a host directory and `free` bytes of
+    /// free space.
+    pub fn with_free_space(root: impl AsRef<Path>, free: u64) -> Result<Self, VfatError> {
+        let cluster = 32 * 1024;
+        let used = walkdir_size(root.as_ref(), cluster)?;
+        Self::new(root, used + free + 1024 * 1024)
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.geometry.fat_type
+    }
+
+    /// The size of the disk, in bytes.
+    pub fn size(&self) -> u64 {
+        (PARTITION_START + self.geometry.total_sectors) as u64 * SECTOR_SIZE as u64
+    }
+
+    /// Allocate a chain of clusters and return its first cluster, or 0 for an
+    /// empty chain.
+    fn allocate(&mut self, count: u32, owner: impl Fn(u32) -> Owner) -> Result<u32, VfatError> {
+        let end = self.geometry.clusters + 2;
+        if count as u64 > (end - self.next_free) as u64 {
+            return Err(VfatError::DiskFull {
+                needed: count as u64,
+                available: (end - self.next_free) as u64,
+            });
+        }
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let start = self.next_free;
+        for i in 0..count {
+            let cluster = start + i;
+            self.fat[cluster as usize] = if i + 1 == count {
+                self.geometry.fat_type.last_cluster()
+            } else {
+                cluster + 1
+            };
+            self.owners[cluster as usize - 2] = owner(i);
+        }
+        self.next_free += count;
+        Ok(start)
+    }
+
+    /// Generate a directory and its content. `cluster` is the first cluster
+    /// allocated to the directory (0 for the FAT16 root directory).
+    fn add_dir(&mut self, path: PathBuf, cluster: u32, parent: u32) -> Result<(), VfatError> {
+        let dir = self.dirs.len();
+        self.dirs.push(Vec::new());
+        self.host_dirs.insert(path.clone());
+
+        let mut entries = std::fs::read_dir(self.root.join(&path))?
+            .filter_map(Result::ok)
+            .filter_map(|e| Some((e.file_name().into_string().ok()?, e.metadata().ok()?)))
+            .filter(|(name, _)| !name.ends_with(".1fpga-tmp"))
+            .collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut bytes = Vec::new();
+        let now = SystemTime::now();
+        if path.as_os_str().is_empty() {
+            bytes.extend_from_slice(&dir_entry(b"1FPGA      ", ATTR_VOLUME_ID, 0, 0, now));
+        } else {
+            bytes.extend_from_slice(&dir_entry(b".          ", ATTR_DIRECTORY, cluster, 0, now));
+            bytes.extend_from_slice(&dir_entry(b"..         ", ATTR_DIRECTORY, parent, 0, now));
+        }
+
+        let mut taken = HashSet::new();
+        let mut subdirs = Vec::new();
+        for (name, metadata) in entries {
+            let (short, needs_long) = short_name(&name, &taken);
+            taken.insert(short);
+            if needs_long {
+                for entry in long_name_entries(&name, short_name_checksum(&short)) {
+                    bytes.extend_from_slice(&entry);
+                }
+            }
+
+            let modified = metadata.modified().unwrap_or(now);
+            let entry_path = path.join(&name);
+            if metadata.is_dir() {
+                let start = self.allocate(1, |_| Owner::Free)?;
+                bytes.extend_from_slice(&dir_entry(&short, ATTR_DIRECTORY, start, 0, modified));
+                subdirs.push((entry_path, start));
+            } else {
+                let size = u32::try_from(metadata.len())
+                    .map_err(|_| VfatError::FileTooLarge(entry_path.clone()))?;
+                let clusters = (size as usize).div_ceil(self.geometry.cluster_size()) as u32;
+                let file = self.files.len();
+                let start = self.allocate(clusters, |index| Owner::File { file, index })?;
+                let mut attr = ATTR_ARCHIVE;
+                if metadata.permissions().readonly() {
+                    attr |= ATTR_READ_ONLY;
+                }
+                bytes.extend_from_slice(&dir_entry(&short, attr, start, size, modified));
+                self.files.push(Some(HostFile {
+                    path: entry_path,
+                    size: size as u64,
+                    start,
+                }));
+            }
+        }
+
+        // Lay out the entries in the clusters of the directory.
+        if cluster == 0 {
+            if bytes.len() > self.geometry.root_dir_sectors as usize * SECTOR_SIZE {
+                return Err(VfatError::RootDirectoryFull);
+            }
+        } else {
+            let cluster_size = self.geometry.cluster_size();
+            let count = bytes.len().div_ceil(cluster_size).max(1) as u32;
+            self.owners[cluster as usize - 2] = Owner::Dir { dir, index: 0 };
+            let mut last = cluster;
+            for index in 1..count {
+                let next = self.allocate(1, |_| Owner::Dir { dir, index })?;
+                self.fat[last as usize] = next;
+                last = next;
+            }
+            bytes.resize(count as usize * cluster_size, 0);
+        }
+        self.dirs[dir] = bytes;
+
+        // The parent of the subdirectories is 0 when it is the root directory.
+        let parent = if path.as_os_str().is_empty() {
+            0
+        } else {
+            cluster
+        };
+        for (path, start) in subdirs {
+            self.add_dir(path, start, parent)?;
+        }
+        Ok(())
+    }
+
+    fn boot_sector(&self) -> Sector {
+        let g = &self.geometry;
+        let mut s = [0u8; SECTOR_SIZE];
+        s[..3].copy_from_slice(match g.fat_type {
+            FatType::Fat16 => &[0xEB, 0x3C, 0x90],
+            FatType::Fat32 => &[0xEB, 0x58, 0x90],
+        });
+        s[3..11].copy_from_slice(b"1FPGA   ");
+        s[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+        s[13] = g.sectors_per_cluster as u8;
+        s[14..16].copy_from_slice(&(g.reserved_sectors as u16).to_le_bytes());
+        s[16] = 2;
+        s[21] = 0xF8;
+        s[24..26].copy_from_slice(&63u16.to_le_bytes());
+        s[26..28].copy_from_slice(&255u16.to_le_bytes());
+        s[28..32].copy_from_slice(&PARTITION_START.to_le_bytes());
+        s[32..36].copy_from_slice(&g.total_sectors.to_le_bytes());
+
+        let ext = match g.fat_type {
+            FatType::Fat16 => {
+                s[17..19].copy_from_slice(&(FAT16_ROOT_ENTRIES as u16).to_le_bytes());
+                s[22..24].copy_from_slice(&(g.fat_sectors as u16).to_le_bytes());
+                36
+            }
+            FatType::Fat32 => {
+                s[36..40].copy_from_slice(&g.fat_sectors.to_le_bytes());
+                s[44..48].copy_from_slice(&2u32.to_le_bytes());
+                s[48..50].copy_from_slice(&1u16.to_le_bytes());
+                s[50..52].copy_from_slice(&6u16.to_le_bytes());
+                64
+            }
+        };
+        s[ext] = 0x80;
+        s[ext + 2] = 0x29;
+        s[ext + 3..ext + 7].copy_from_slice(&self.serial.to_le_bytes());
+        s[ext + 7..ext + 18].copy_from_slice(b"1FPGA      ");
+        s[ext + 18..ext + 26].copy_from_slice(match g.fat_type {
+            FatType::Fat16 => b"FAT16   ",
+            FatType::Fat32 => b"FAT32   ",
+        });
+        s[510] = 0x55;
+        s[511] = 0xAA;
+        s
+    }
+
+    fn fs_info_sector(&self) -> Sector {
+        let mut s = [0u8; SECTOR_SIZE];
+        let free = self.geometry.clusters + 2 - self.next_free;
+        s[..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        s[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        s[488..492].copy_from_slice(&free.to_le_bytes());
+        s[492..496].copy_from_slice(&self.next_free.to_le_bytes());
+        s[508..512].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+        s
+    }
+
+    fn partition_table(&self) -> Sector {
+        let chs = |lba: u32| {
+            let cylinder = (lba / (255 * 63)).min(1023);
+            let head = (lba / 63) % 255;
+            let sector = lba % 63 + 1;
+            [
+                head as u8,
+                (sector as u8) | ((cylinder >> 2) as u8 & 0xC0),
+                cylinder as u8,
+            ]
+        };
+        let end = PARTITION_START + self.geometry.total_sectors - 1;
+
+        let mut s = [0u8; SECTOR_SIZE];
+        let entry = &mut s[446..462];
+        entry[0] = 0x80;
+        entry[1..4].copy_from_slice(&chs(PARTITION_START));
+        entry[4] = match self.geometry.fat_type {
+            FatType::Fat16 => 0x06,
+            FatType::Fat32 => 0x0C,
+        };
+        entry[5..8].copy_from_slice(&chs(end));
+        entry[8..12].copy_from_slice(&PARTITION_START.to_le_bytes());
+        entry[12..16].copy_from_slice(&self.geometry.total_sectors.to_le_bytes());
+        s[510] = 0x55;
+        s[511] = 0xAA;
+        s
+    }
+
+    /// Generate a sector of the partition from the host directory.
+    fn generate_sector(&mut self, sector: u32, buffer: &mut Sector) -> std::io::Result<()> {
+        let g = self.geometry;
+        buffer.fill(0);
+
+        if sector < g.reserved_sectors {
+            match (g.fat_type, sector) {
+                (_, 0) | (FatType::Fat32, 6) => *buffer = self.boot_sector(),
+                (FatType::Fat32, 1) | (FatType::Fat32, 7) => *buffer = self.fs_info_sector(),
+                _ => {}
+            }
+        } else if sector < g.root_dir_start() {
+            let index = (sector - g.fat_start()) % g.fat_sectors;
+            match g.fat_type {
+                FatType::Fat16 => {
+                    let start = index as usize * SECTOR_SIZE / 2;
+                    for (i, entry) in self
+                        .fat
+                        .iter()
+                        .skip(start)
+                        .take(SECTOR_SIZE / 2)
+                        .enumerate()
+                    {
+                        buffer[i * 2..i * 2 + 2].copy_from_slice(&(*entry as u16).to_le_bytes());
+                    }
+                }
+                FatType::Fat32 => {
+                    let start = index as usize * SECTOR_SIZE / 4;
+                    for (i, entry) in self
+                        .fat
+                        .iter()
+                        .skip(start)
+                        .take(SECTOR_SIZE / 4)
+                        .enumerate()
+                    {
+                        buffer[i * 4..i * 4 + 4].copy_from_slice(&entry.to_le_bytes());
+                    }
+                }
+            }
+        } else if sector < g.data_start() {
+            let offset = (sector - g.root_dir_start()) as usize * SECTOR_SIZE;
+            if let Some(bytes) = self.dirs[0].get(offset..) {
+                let len = bytes.len().min(SECTOR_SIZE);
+                buffer[..len].copy_from_slice(&bytes[..len]);
+            }
+        } else if sector < g.total_sectors {
+            let cluster = (sector - g.data_start()) / g.sectors_per_cluster;
+            let in_cluster = ((sector - g.data_start()) % g.sectors_per_cluster) as usize;
+            match self
+                .owners
+                .get(cluster as usize)
+                .copied()
+                .unwrap_or(Owner::Free)
+            {
+                Owner::Free => {}
+                Owner::Dir { dir, index } => {
+                    let offset = index as usize * g.cluster_size() + in_cluster * SECTOR_SIZE;
+                    buffer.copy_from_slice(&self.dirs[dir][offset..offset + SECTOR_SIZE]);
+                }
+                Owner::File { file, index } => {
+                    let offset =
+                        index as u64 * g.cluster_size() as u64 + (in_cluster * SECTOR_SIZE) as u64;
+                    self.read_host_file(file, offset, buffer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Open a host file, for writing too if it is writable. The last file
+    /// opened is kept open.
+    fn host_file(&mut self, file: usize) -> std::io::Result<Option<&mut File>> {
+        let f = match self.open_file.take() {
+            Some((f, handle)) if f == file => handle,
+            _ => {
+                let Some(host) = &self.files[file] else {
+                    return Ok(None);
+                };
+                let path = self.root.join(&host.path);
+                match OpenOptions::new().read(true).write(true).open(&path) {
+                    Ok(f) => f,
+                    Err(_) => File::open(&path)?,
+                }
+            }
+        };
+        Ok(Some(&mut self.open_file.insert((file, f)).1))
+    }
+
+    /// Read a part of a host file. Reading past its end reads zeros.
+    fn read_host_file(
+        &mut self,
+        file: usize,
+        offset: u64,
+        buffer: &mut [u8],
+    ) -> std::io::Result<()> {
+        let Some(f) = self.host_file(file)? else {
+            return Ok(());
+        };
+
+        f.seek(SeekFrom::Start(offset))?;
+        let mut read = 0;
+        while read < buffer.len() {
+            match f.read(&mut buffer[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a sector of the disk, as the core sees it.
+    pub fn read_sector(&mut self, sector: u64, buffer: &mut Sector) -> std::io::Result<()> {
+        if let Some(written) = self.written.get(&sector) {
+            buffer.copy_from_slice(written.as_ref());
+        } else if sector == 0 {
+            *buffer = self.partition_table();
+        } else if sector < PARTITION_START as u64 || sector >= u32::MAX as u64 {
+            buffer.fill(0);
+        } else {
+            self.generate_sector((sector - PARTITION_START as u64) as u32, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Write a sector of a host file, if it is within the file. Returns whether
+    /// the sector was written.
+    fn write_host_file(&mut self, file: usize, offset: u64, buffer: &Sector) -> bool {
+        let fits = self.files[file]
+            .as_ref()
+            .is_some_and(|f| offset + SECTOR_SIZE as u64 <= f.size);
+        if !fits {
+            return false;
+        }
+        let result = self.host_file(file).and_then(|f| match f {
+            Some(f) => {
+                f.seek(SeekFrom::Start(offset))?;
+                f.write_all(buffer)
+            }
+            None => Err(std::io::ErrorKind::NotFound.into()),
+        });
+        if let Err(error) = &result {
+            debug!(%error, file, "Could not write through to host file");
+        }
+        result.is_ok()
+    }
+
+    /// Write a sector of the disk. Sectors of host files are written to them
+    /// directly, other changes are written back to the host directory on the
+    /// next flush.
+    pub fn write_sector(&mut self, sector: u64, buffer: &Sector) -> std::io::Result<()> {
+        let g = self.geometry;
+        let fs_sector = sector.checked_sub(PARTITION_START as u64);
+        match fs_sector {
+            Some(s) if s >= g.data_start() as u64 && s < g.total_sectors as u64 => {
+                let cluster = (s as u32 - g.data_start()) / g.sectors_per_cluster + 2;
+                let in_cluster = (s as u32 - g.data_start()) % g.sectors_per_cluster;
+                if let Some(Owner::File { file, index }) =
+                    self.owners.get(cluster as usize - 2).copied()
+                {
+                    let offset = index as u64 * g.cluster_size() as u64
+                        + in_cluster as u64 * SECTOR_SIZE as u64;
+                    if self.write_host_file(file, offset, buffer) {
+                        self.written.remove(&sector);
+                        return Ok(());
+                    }
+                }
+                self.dirty_clusters.insert(cluster);
+            }
+            _ => self.dirty = true,
+        }
+
+        if !self.written.contains_key(&sector) && self.written.len() >= MAX_WRITTEN_SECTORS {
+            // Writing back the files moves their sectors to the host.
+            self.sync()?;
+            if self.written.len() >= MAX_WRITTEN_SECTORS {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::OutOfMemory,
+                    "Too many sectors written to the virtual FAT disk",
+                ));
+            }
+        }
+        self.written.insert(sector, Box::new(*buffer));
+        Ok(())
+    }
+
+    /// Read the FAT entry of a cluster, as the core sees it.
+    fn fat_entry(&mut self, cluster: u32) -> std::io::Result<u32> {
+        let g = self.geometry;
+        let size = match g.fat_type {
+            FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        };
+        let offset = cluster as usize * size;
+        let sector = PARTITION_START + g.fat_start() + (offset / SECTOR_SIZE) as u32;
+        let mut buffer = [0; SECTOR_SIZE];
+        self.read_sector(sector as u64, &mut buffer)?;
+        let bytes = &buffer[offset % SECTOR_SIZE..];
+        Ok(match g.fat_type {
+            FatType::Fat16 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            FatType::Fat32 => {
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & 0x0FFF_FFFF
+            }
+        })
+    }
+
+    /// The clusters of a chain, as the core sees it.
+    fn chain(&mut self, start: u32) -> std::io::Result<Vec<u32>> {
+        let mut chain = Vec::new();
+        let mut cluster = start;
+        while cluster >= 2 && cluster < self.geometry.clusters + 2 {
+            if chain.len() > self.geometry.clusters as usize {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Loop in a cluster chain",
+                ));
+            }
+            chain.push(cluster);
+            cluster = self.fat_entry(cluster)?;
+        }
+        Ok(chain)
+    }
+
+    /// Read the clusters of a chain, up to `size` bytes.
+    fn read_chain(
+        &mut self,
+        chain: &[u32],
+        size: u64,
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        let mut remaining = size;
+        let mut buffer = [0; SECTOR_SIZE];
+        for &cluster in chain {
+            let first = PARTITION_START + self.geometry.cluster_sector(cluster);
+            for sector in first..first + self.geometry.sectors_per_cluster {
+                if remaining == 0 {
+                    return Ok(());
+                }
+                self.read_sector(sector as u64, &mut buffer)?;
+                let len = remaining.min(SECTOR_SIZE as u64) as usize;
+                out.write_all(&buffer[..len])?;
+                remaining -= len as u64;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the entries of a directory (the root directory if `start` is 0).
+    fn read_dir(&mut self, path: &Path, start: u32) -> std::io::Result<Vec<DiskEntry>> {
+        let mut bytes = Vec::new();
+        if start == 0 && self.geometry.fat_type == FatType::Fat16 {
+            let first = PARTITION_START + self.geometry.root_dir_start();
+            let mut buffer = [0; SECTOR_SIZE];
+            for sector in first..first + self.geometry.root_dir_sectors {
+                self.read_sector(sector as u64, &mut buffer)?;
+                bytes.extend_from_slice(&buffer);
+            }
+        } else {
+            let start = if start == 0 { 2 } else { start };
+            let chain = self.chain(start)?;
+            let size = chain.len() as u64 * self.geometry.cluster_size() as u64;
+            self.read_chain(&chain, size, &mut bytes)?;
+        }
+
+        let mut entries = Vec::new();
+        let mut long_name: Vec<(u8, [u16; 13])> = Vec::new();
+        for entry in bytes.as_chunks::<ENTRY_SIZE>().0 {
+            match entry[0] {
+                0 => break,
+                0xE5 => {
+                    long_name.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            if entry[11] == ATTR_LONG_NAME {
+                let mut units = [0u16; 13];
+                for (j, offset) in [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30]
+                    .iter()
+                    .enumerate()
+                {
+                    units[j] = u16::from_le_bytes([entry[*offset], entry[*offset + 1]]);
+                }
+                if entry[0] & 0x40 != 0 {
+                    long_name.clear();
+                }
+                long_name.push((entry[13], units));
+                continue;
+            }
+            if entry[11] & ATTR_VOLUME_ID != 0 || entry[0] == b'.' {
+                long_name.clear();
+                continue;
+            }
+
+            let short: [u8; 11] = entry[..11].try_into().unwrap();
+            let checksum = short_name_checksum(&short);
+            let name = if !long_name.is_empty() && long_name.iter().all(|(c, _)| *c == checksum) {
+                let units = long_name
+                    .iter()
+                    .rev()
+                    .flat_map(|(_, u)| u.iter().copied())
+                    .take_while(|u| *u != 0)
+                    .collect::<Vec<_>>();
+                String::from_utf16_lossy(&units)
+            } else {
+                let base = String::from_utf8_lossy(&short[..8]).trim_end().to_string();
+                let ext = String::from_utf8_lossy(&short[8..]).trim_end().to_string();
+                if ext.is_empty() {
+                    base
+                } else {
+                    format!("{base}.{ext}")
+                }
+            };
+            long_name.clear();
+
+            let start = ((u16::from_le_bytes([entry[20], entry[21]]) as u32) << 16)
+                | u16::from_le_bytes([entry[26], entry[27]]) as u32;
+            entries.push(DiskEntry {
+                path: path.join(name),
+                is_dir: entry[11] & ATTR_DIRECTORY != 0,
+                start,
+                size: u32::from_le_bytes(entry[28..32].try_into().unwrap()) as u64,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// List all the files and directories of the file system, as the core
+    /// sees it.
+    pub fn entries(&mut self) -> std::io::Result<Vec<DiskEntry>> {
+        let mut result = Vec::new();
+        let mut pending = vec![(PathBuf::new(), 0)];
+        while let Some((path, start)) = pending.pop() {
+            if result.len() > self.geometry.clusters as usize {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Loop in the directories",
+                ));
+            }
+            for entry in self.read_dir(&path, start)? {
+                if entry.is_dir && entry.start >= 2 {
+                    pending.push((entry.path.clone(), entry.start));
+                }
+                result.push(entry);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read the content of a file of the file system, as the core sees it.
+    pub fn read_entry(&mut self, entry: &DiskEntry) -> std::io::Result<Vec<u8>> {
+        let chain = self.chain(entry.start)?;
+        let mut content = Vec::with_capacity(entry.size as usize);
+        self.read_chain(&chain, entry.size, &mut content)?;
+        Ok(content)
+    }
+
+    /// Why the file system is not consistent, if it is not: the copies of the
+    /// FAT differ, a cluster is in several chains, or the chain of a file does
+    /// not match its size.
+    fn inconsistency(
+        &mut self,
+        entries: &[DiskEntry],
+        chains: &[Vec<u32>],
+    ) -> std::io::Result<Option<String>> {
+        let g = self.geometry;
+        let mut first = [0; SECTOR_SIZE];
+        let mut second = [0; SECTOR_SIZE];
+        for index in 0..g.fat_sectors {
+            let sector = (PARTITION_START + g.fat_start() + index) as u64;
+            let copy = sector + g.fat_sectors as u64;
+            if !self.written.contains_key(&sector) && !self.written.contains_key(&copy) {
+                continue;
+            }
+            self.read_sector(sector, &mut first)?;
+            self.read_sector(copy, &mut second)?;
+            if first != second {
+                return Ok(Some(format!("FAT copies differ at sector {index}")));
+            }
+        }
+
+        let mut used = match g.fat_type {
+            FatType::Fat16 => HashSet::new(),
+            FatType::Fat32 => self.chain(2)?.into_iter().collect(),
+        };
+        for (entry, chain) in entries.iter().zip(chains) {
+            if let Some(cluster) = chain.iter().find(|c| !used.insert(**c)) {
+                return Ok(Some(format!("Cluster {cluster} is in several chains")));
+            }
+            let clusters = entry.size.div_ceil(g.cluster_size() as u64);
+            if !entry.is_dir && chain.len() as u64 != clusters {
+                return Ok(Some(format!(
+                    "{:?} has {} clusters for {} bytes",
+                    entry.path,
+                    chain.len(),
+                    entry.size
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Write the changes made by the core back to the host directory.
+    fn sync(&mut self) -> std::io::Result<()> {
+        if !self.dirty && self.dirty_clusters.is_empty() {
+            return Ok(());
+        }
+        let entries = self.entries()?;
+        let chains = entries
+            .iter()
+            .map(|e| self.chain(e.start))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        if let Some(reason) = self.inconsistency(&entries, &chains)? {
+            // Try again on the next flush, once the core is done updating it.
+            warn!(
+                reason,
+                "Virtual FAT disk is not consistent, not writing it back"
+            );
+            return Ok(());
+        }
+        let originals = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| Some((f.as_ref()?.path.clone(), i)))
+            .collect::<HashMap<_, _>>();
+
+        for entry in entries.iter().filter(|e| e.is_dir) {
+            if !self.host_dirs.contains(&entry.path) {
+                std::fs::create_dir_all(self.root.join(&entry.path))?;
+            }
+        }
+
+        // Files that changed are written to temporary files first, as their
+        // content might be read from host files that are replaced.
+        let mut changed = Vec::new();
+        for (entry, chain) in entries.iter().zip(chains).filter(|(e, _)| !e.is_dir) {
+            let original = originals
+                .get(&entry.path)
+                .and_then(|i| self.files[*i].as_ref());
+            if original.is_some_and(|f| f.start == entry.start && f.size == entry.size)
+                && !chain.iter().any(|c| self.dirty_clusters.contains(c))
+            {
+                continue;
+            }
+
+            let path = self.root.join(&entry.path);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let tmp = path.with_file_name(format!(".{name}.1fpga-tmp"));
+            debug!(?path, "Writing back file from virtual FAT disk");
+            let mut file = File::create(&tmp)?;
+            self.read_chain(&chain, entry.size, &mut file)?;
+            changed.push((entry, tmp, chain));
+        }
+
+        let paths = entries.iter().map(|e| &e.path).collect::<HashSet<_>>();
+        let removed = originals
+            .iter()
+            .filter(|(path, _)| !paths.contains(path))
+            .map(|(_, i)| *i)
+            .collect::<Vec<_>>();
+
+        // Now that all files are read, update the host and the clusters.
+        self.open_file = None;
+        for (entry, tmp, _) in &changed {
+            std::fs::rename(tmp, self.root.join(&entry.path))?;
+        }
+        for i in removed.iter().copied() {
+            if let Some(file) = self.files[i].take() {
+                debug!(path = ?file.path, "Removing file deleted from virtual FAT disk");
+                if let Err(error) = std::fs::remove_file(self.root.join(&file.path)) {
+                    warn!(%error, path = ?file.path, "Could not remove file");
+                }
+            }
+        }
+        let mut removed_dirs = self
+            .host_dirs
+            .iter()
+            .filter(|d| !d.as_os_str().is_empty() && !paths.contains(d))
+            .cloned()
+            .collect::<Vec<_>>();
+        removed_dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+        for dir in removed_dirs {
+            if let Err(error) = std::fs::remove_dir(self.root.join(&dir)) {
+                warn!(%error, ?dir, "Could not remove directory");
+            }
+        }
+
+        let changed_originals = changed
+            .iter()
+            .filter_map(|(e, _, _)| originals.get(&e.path).copied())
+            .chain(removed)
+            .collect::<HashSet<_>>();
+        for owner in self.owners.iter_mut() {
+            if matches!(owner, Owner::File { file, .. } if changed_originals.contains(file)) {
+                *owner = Owner::Free;
+            }
+        }
+        let sectors_per_cluster = self.geometry.sectors_per_cluster;
+        for (entry, _, chain) in changed {
+            let file = match originals.get(&entry.path) {
+                Some(i) => *i,
+                None => {
+                    self.files.push(None);
+                    self.files.len() - 1
+                }
+            };
+            self.files[file] = Some(HostFile {
+                path: entry.path.clone(),
+                size: entry.size,
+                start: entry.start,
+            });
+
+            // The data of the file is on the host now.
+            for (index, cluster) in chain.into_iter().enumerate() {
+                self.owners[cluster as usize - 2] = Owner::File {
+                    file,
+                    index: index as u32,
+                };
+                let first = (PARTITION_START + self.geometry.cluster_sector(cluster)) as u64;
+                for sector in first..first + sectors_per_cluster as u64 {
+                    self.written.remove(&sector);
+                }
+            }
+        }
+        self.host_dirs = entries
+            .iter()
+            .filter(|e| e.is_dir)
+            .map(|e| e.path.clone())
+            .chain([PathBuf::new()])
+            .collect();
+
+        self.dirty = false;
+        self.dirty_clusters.clear();
+        Ok(())
+    }
+}
+
+/// The space used by the files of a directory, rounded up to clusters.
+fn walkdir_size(path: &Path, cluster: u64) -> std::io::Result<u64> {
+    let mut size = cluster;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            walkdir_size(&entry.path(), cluster)?
+        } else {
+            metadata.len().div_ceil(cluster) * cluster
+        };
+    }
+    Ok(size)
+}
+
+impl Read for VirtualFat {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut read = 0;
+        let mut sector = [0; SECTOR_SIZE];
+        while read < buf.len() && self.position < self.size() {
+            let offset = (self.position % SECTOR_SIZE as u64) as usize;
+            self.read_sector(self.position / SECTOR_SIZE as u64, &mut sector)?;
+            let len = (SECTOR_SIZE - offset).min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&sector[offset..offset + len]);
+            read += len;
+            self.position += len as u64;
+        }
+        Ok(read)
+    }
+}
+
+impl Write for VirtualFat {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut written = 0;
+        let mut sector = [0; SECTOR_SIZE];
+        while written < buf.len() && self.position < self.size() {
+            let index = self.position / SECTOR_SIZE as u64;
+            let offset = (self.position % SECTOR_SIZE as u64) as usize;
+            let len = (SECTOR_SIZE - offset).min(buf.len() - written);
+            if len < SECTOR_SIZE {
+                self.read_sector(index, &mut sector)?;
+            }
+            sector[offset..offset + len].copy_from_slice(&buf[written..written + len]);
+            self.write_sector(index, &sector)?;
+            written += len;
+            self.position += len as u64;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.sync()
+    }
+}
+
+impl Seek for VirtualFat {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(p) => self.size().checked_add_signed(p),
+            SeekFrom::Current(p) => self.position.checked_add_signed(p),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl Drop for VirtualFat {
+    fn drop(&mut self) {
+        if let Err(error) = self.sync() {
+            warn!(%error, root = ?self.root, "Could not write back the virtual FAT disk");
+        }
+    }
+}
+
+impl one_fpga::core::MountedFile for VirtualFat {}
+
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mister-fpga-vfat-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("GAMES/Saves")).unwrap();
+    std::fs::write(dir.join("README.TXT"), b"Hello, World!\n").unwrap();
+    std::fs::write(dir.join("A long file name.txt"), b"Long name").unwrap();
+    std::fs::write(dir.join("EMPTY"), b"").unwrap();
+    let big = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(dir.join("GAMES/doom.exe"), big).unwrap();
+    dir
+}
+
+#[test]
+fn virtual_fat() {
+    let dir = test_dir("read");
+    let mut disk = VirtualFat::new(&dir, 64 * 1024 * 1024).unwrap();
+    assert_eq!(disk.fat_type(), FatType::Fat16);
+
+    let mut names = disk
+        .entries()
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.path.to_string_lossy().to_string(), e.is_dir, e.size))
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            ("A long file name.txt".to_string(), false, 9),
+            ("EMPTY".to_string(), false, 0),
+            ("GAMES".to_string(), true, 0),
+            ("GAMES/Saves".to_string(), true, 0),
+            ("GAMES/doom.exe".to_string(), false, 100_000),
+            ("README.TXT".to_string(), false, 14),
+        ]
+    );
+    for entry in disk.entries().unwrap().iter().filter(|e| !e.is_dir) {
+        let content = disk.read_entry(entry).unwrap();
+        assert_eq!(content, std::fs::read(dir.join(&entry.path)).unwrap());
+    }
+
+    drop(disk);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+#[ignore = "needs fsck.fat (dosfstools)"]
+fn virtual_fat_fsck() {
+    let dir = test_dir("fsck");
+    for size in [64 * 1024 * 1024, 600 * 1024 * 1024] {
+        let mut disk = VirtualFat::new(&dir, size).unwrap();
+        let image = dir.with_extension("img");
+        let mut partition = File::create(&image).unwrap();
+        disk.seek(SeekFrom::Start(PARTITION_START as u64 * SECTOR_SIZE as u64))
+            .unwrap();
+        std::io::copy(&mut disk, &mut partition).unwrap();
+
+        let output = std::process::Command::new("fsck.fat")
+            .arg("-n")
+            .arg(&image)
+            .output()
+            .expect("fsck.fat is not installed");
+        assert!(output.status.success(), "{output:?}");
+        std::fs::remove_file(image).unwrap();
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn virtual_fat_writes() {
+    let dir = test_dir("write");
+    let mut disk = VirtualFat::new(&dir, 600 * 1024 * 1024).unwrap();
+    assert_eq!(disk.fat_type(), FatType::Fat32);
+    let entries = disk.entries().unwrap();
+    let find = |name: &str| entries.iter().find(|e| e.path == Path::new(name)).unwrap();
+
+    // Sectors within host files are written through to them.
+    let doom = find("GAMES/doom.exe");
+    let sector = PARTITION_START + disk.geometry.cluster_sector(doom.start) + 1;
+    disk.write_sector(sector as u64, &[0xAA; SECTOR_SIZE])
+        .unwrap();
+    assert!(disk.written.is_empty());
+    let content = std::fs::read(dir.join("GAMES/doom.exe")).unwrap();
+    assert_eq!(content.len(), 100_000);
+    assert_eq!(&content[SECTOR_SIZE..SECTOR_SIZE * 2], &[0xAA; SECTOR_SIZE]);
+    assert_eq!(content[SECTOR_SIZE * 2], ((SECTOR_SIZE * 2) % 251) as u8);
+
+    // Change the content of a file, in place.
+    let readme = find("README.TXT");
+    let sector = PARTITION_START + disk.geometry.cluster_sector(readme.start);
+    disk.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))
+        .unwrap();
+    disk.write_all(b"HELLO").unwrap();
+
+    // Delete a file with a long name, by marking its entries as deleted.
+    let root = PARTITION_START + disk.geometry.cluster_sector(2);
+    let mut sector = [0; SECTOR_SIZE];
+    disk.read_sector(root as u64, &mut sector).unwrap();
+    for entry in sector.as_chunks_mut::<ENTRY_SIZE>().0 {
+        let is_long_name = entry[11] == ATTR_LONG_NAME;
+        if is_long_name || entry.starts_with(b"ALONGF~1TXT") {
+            entry[0] = 0xE5;
+        }
+    }
+    disk.write_sector(root as u64, &sector).unwrap();
+    disk.flush().unwrap();
+
+    assert_eq!(
+        std::fs::read(dir.join("README.TXT")).unwrap(),
+        b"HELLO, World!\n"
+    );
+    assert!(!dir.join("A long file name.txt").exists());
+    assert!(dir.join("GAMES/doom.exe").exists());
+
+    // The disk still reads the same after the host was updated.
+    let readme = find("README.TXT").clone();
+    assert_eq!(disk.read_entry(&readme).unwrap(), b"HELLO, World!\n");
+    assert_eq!(disk.entries().unwrap().len(), entries.len() - 1);
+
+    drop(disk);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn virtual_fat_inconsistent() {
+    let dir = test_dir("inconsistent");
+    let mut disk = VirtualFat::new(&dir, 64 * 1024 * 1024).unwrap();
+    let entries = disk.entries().unwrap();
+    let readme = entries
+        .iter()
+        .find(|e| e.path == Path::new("README.TXT"))
+        .unwrap();
+
+    // Delete a file, but only update the first FAT.
+    let root = PARTITION_START + disk.geometry.root_dir_start();
+    let mut sector = [0; SECTOR_SIZE];
+    disk.read_sector(root as u64, &mut sector).unwrap();
+    for entry in sector.as_chunks_mut::<ENTRY_SIZE>().0 {
+        if entry.starts_with(b"README  TXT") {
+            entry[0] = 0xE5;
+        }
+    }
+    disk.write_sector(root as u64, &sector).unwrap();
+
+    let offset = readme.start as usize * 2;
+    let fat = (PARTITION_START + disk.geometry.fat_start()) as u64 + (offset / SECTOR_SIZE) as u64;
+    disk.read_sector(fat, &mut sector).unwrap();
+    sector[offset % SECTOR_SIZE..offset % SECTOR_SIZE + 2].fill(0);
+    disk.write_sector(fat, &sector).unwrap();
+    disk.flush().unwrap();
+    assert!(dir.join("README.TXT").exists());
+
+    // The file is deleted once the file system is consistent again.
+    disk.write_sector(fat + disk.geometry.fat_sectors as u64, &sector)
+        .unwrap();
+    disk.flush().unwrap();
+    assert!(!dir.join("README.TXT").exists());
+    assert!(dir.join("GAMES/doom.exe").exists());
+
+    drop(disk);
+    std::fs::remove_dir_all(dir).unwrap();
+}
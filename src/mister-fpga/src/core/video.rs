@@ -191,6 +191,10 @@ impl VideoInfo {
         Resolution::new(self.fb_width, self.fb_height)
     }
 
+    pub fn interlaced(&self) -> bool {
+        self.interlaced
+    }
+
     pub fn aspect_ratio(&self) -> AspectRatio {
         self.aspect_ratio
     }
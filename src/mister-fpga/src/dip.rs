@@ -0,0 +1,227 @@
+//! DIP switches of arcade cores. Their definitions come from the MRA file of a
+//! game (the `<switches>` element), and their values are sent to the core as a
+//! 64 bits value on a special file index.
+use std::ops::Range;
+
+use one_fpga::core::{CoreSettingItem, SettingId};
+
+/// The file index used to send the DIP switches to the core.
+pub const DIP_SWITCHES_INDEX: u8 = 254;
+
+/// A single DIP switch, which can span multiple bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DipSwitch {
+    /// The name of the switch, shown to the user.
+    pub name: String,
+
+    /// The bits used by this switch in the DIP switches value.
+    pub bits: Range<u8>,
+
+    /// The choices of this switch, with their label and value (relative to
+    /// the first bit).
+    pub choices: Vec<(String, u64)>,
+}
+
+impl DipSwitch {
+    /// Create a DIP switch where each choice's value is its index.
+    pub fn new(name: impl Into<String>, bits: Range<u8>, choices: Vec<String>) -> Self {
+        let choices = choices
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| (label, i as u64))
+            .collect();
+        Self::with_values(name, bits, choices)
+    }
+
+    /// Create a DIP switch with explicit values for each choice.
+    pub fn with_values(
+        name: impl Into<String>,
+        bits: Range<u8>,
+        choices: Vec<(String, u64)>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            bits,
+            choices,
+        }
+    }
+
+    pub fn setting_id(&self) -> SettingId {
+        SettingId::from_label(&format!("dip:{}", self.name))
+    }
+
+    fn mask(&self) -> u64 {
+        self.bits
+            .clone()
+            .filter(|bit| *bit < 64)
+            .fold(0, |acc, bit| acc | (1 << bit))
+    }
+
+    /// Return the index of the choice selected in `value`, if any.
+    pub fn choice(&self, value: u64) -> Option<usize> {
+        let v = (value & self.mask()) >> self.bits.start;
+        self.choices.iter().position(|(_, c)| *c == v)
+    }
+
+    /// Return `value` with this switch set to the choice at `index`.
+    fn with_choice(&self, value: u64, index: usize) -> u64 {
+        let mask = self.mask();
+        let v = self.choices.get(index).map_or(0, |(_, c)| *c);
+        (value & !mask) | ((v << self.bits.start) & mask)
+    }
+
+    fn as_core_menu_item(&self, value: u64) -> Option<CoreSettingItem> {
+        let choice = self.choice(value);
+        match self.choices.len() {
+            0 | 1 => None,
+            2 => Some(CoreSettingItem::bool_option(
+                self.setting_id(),
+                &self.name,
+                choice.map(|c| c != 0),
+            )),
+            _ => Some(CoreSettingItem::int_option(
+                self.setting_id(),
+                &self.name,
+                self.choices
+                    .iter()
+                    .map(|(label, _)| label.clone())
+                    .collect(),
+                choice,
+            )),
+        }
+    }
+}
+
+/// The DIP switches of a game, and their current value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DipSwitches {
+    switches: Vec<DipSwitch>,
+    default: u64,
+    value: u64,
+}
+
+impl DipSwitches {
+    pub fn new(switches: Vec<DipSwitch>, default: u64) -> Self {
+        Self {
+            switches,
+            default,
+            value: default,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.switches.is_empty()
+    }
+
+    pub fn switches(&self) -> &[DipSwitch] {
+        &self.switches
+    }
+
+    /// The current value of all switches.
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// The default value of all switches.
+    pub fn default_value(&self) -> u64 {
+        self.default
+    }
+
+    /// Reset all switches to their default value.
+    pub fn reset(&mut self) {
+        self.value = self.default;
+    }
+
+    pub fn find(&self, id: SettingId) -> Option<&DipSwitch> {
+        self.switches.iter().find(|s| s.setting_id() == id)
+    }
+
+    /// Set the switch with the setting `id` to the choice at `index` (modulo
+    /// the number of choices). Returns the new choice, or `None` if there is
+    /// no such switch.
+    pub fn set_choice(&mut self, id: SettingId, index: usize) -> Option<usize> {
+        let switch = self.find(id)?;
+        if switch.choices.is_empty() {
+            return None;
+        }
+
+        let index = index % switch.choices.len();
+        self.value = switch.with_choice(self.value, index);
+        Some(index)
+    }
+
+    /// Return the value as bytes, in the format sent to the core and saved in
+    /// MiSTer's `.dip` files.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        self.value.to_le_bytes()
+    }
+
+    /// Set the value from bytes (see [`Self::to_bytes`]). Shorter inputs leave the
+    /// upper bits cleared.
+    pub fn load_bytes(&mut self, bytes: &[u8]) {
+        let mut buffer = [0; 8];
+        let len = bytes.len().min(8);
+        buffer[..len].copy_from_slice(&bytes[..len]);
+        self.value = u64::from_le_bytes(buffer);
+    }
+
+    pub fn as_core_menu_items(&self) -> Vec<CoreSettingItem> {
+        self.switches
+            .iter()
+            .filter_map(|s| s.as_core_menu_item(self.value))
+            .collect()
+    }
+}
+
+#[test]
+fn dip_switches() {
+    let mut dips = DipSwitches::new(
+        vec![
+            DipSwitch::new("Lives", 0..2, vec!["3".into(), "4".into(), "5".into()]),
+            DipSwitch::with_values(
+                "Demo Sounds",
+                7..8,
+                vec![("Off".into(), 1), ("On".into(), 0)],
+            ),
+        ],
+        0b1000_0001,
+    );
+
+    assert_eq!(
+        dips.find(SettingId::from_label("dip:Lives"))
+            .unwrap()
+            .choice(dips.value()),
+        Some(1)
+    );
+    assert_eq!(
+        dips.set_choice(SettingId::from_label("dip:Lives"), 5),
+        Some(2)
+    );
+    assert_eq!(dips.value(), 0b1000_0010);
+    assert_eq!(
+        dips.set_choice(SettingId::from_label("dip:Demo Sounds"), 1),
+        Some(1)
+    );
+    assert_eq!(dips.value(), 0b0000_0010);
+    assert_eq!(
+        dips.set_choice(SettingId::from_label("dip:Unknown"), 1),
+        None
+    );
+
+    assert_eq!(dips.to_bytes(), [0b0000_0010, 0, 0, 0, 0, 0, 0, 0]);
+    dips.load_bytes(&[0b1000_0000]);
+    assert_eq!(dips.value(), 0b1000_0000);
+    dips.reset();
+    assert_eq!(dips.value(), 0b1000_0001);
+
+    let items = dips.as_core_menu_items();
+    assert_eq!(items.len(), 2);
+    assert!(matches!(
+        &items[0],
+        CoreSettingItem::IntOption { value: 1, choices, .. } if choices.len() == 3
+    ));
+    assert!(matches!(
+        &items[1],
+        CoreSettingItem::BoolOption { value: false, .. }
+    ));
+}
@@ -50,6 +50,17 @@ impl From<&FpgaError> for &'static str {
     }
 }
 
+impl From<FpgaError> for one_fpga::core::Error {
+    fn from(value: FpgaError) -> Self {
+        let message: &'static str = value.into();
+        match value {
+            FpgaError::Timeout => Self::Timeout(message.to_string()),
+            FpgaError::IoError => Self::IoError(std::io::Error::other(message)),
+            _ => Self::Protocol(message.to_string()),
+        }
+    }
+}
+
 static INITIALIZED: AtomicBool = AtomicBool::new(false);
 
 // TODO: Remove this when we're done re-writing fpga_io.cpp
@@ -107,10 +118,8 @@ pub struct MisterFpga {
     spi: Spi<DevMemMemoryMapper>,
 }
 
-// SAFETY:
-// Since the FPGA is using memory-mapped I/O, it is not safe to send it to another thread.
-unsafe impl Send for MisterFpga {}
-unsafe impl Sync for MisterFpga {}
+// The FPGA is using memory-mapped I/O, so it is not safe to send it to another
+// thread. Use `one_fpga::core::CoreHandle` to talk to a core from another thread.
 
 // OSD specific functions.
 impl MisterFpga {
@@ -170,6 +179,20 @@ impl MisterFpga {
         }
     }
 
+    /// Create an FPGA over anonymous memory, whose SPI commands are recorded in
+    /// the returned [`FakeSpi`] instead of being sent to a core.
+    #[cfg(test)]
+    pub(crate) fn fake() -> (Self, std::rc::Rc<std::cell::RefCell<FakeSpi>>) {
+        let fake = std::rc::Rc::new(std::cell::RefCell::new(FakeSpi::default()));
+        #[allow(clippy::arc_with_non_send_sync)]
+        let soc = Arc::new(UnsafeCell::new(cyclone_v::SocFpga::create_anonymous()));
+        let fpga = Self {
+            soc: soc.clone(),
+            spi: Spi::with_bus(soc, fake.clone()),
+        };
+        (fpga, fake)
+    }
+
     pub fn spi(&self) -> &Spi<DevMemMemoryMapper> {
         &self.spi
     }
@@ -3,6 +3,7 @@ use cyclone_v::memory::MemoryMapper;
 use cyclone_v::SocFpga;
 use std::cell::{RefCell, UnsafeCell};
 use std::fmt::Debug;
+use std::rc::Rc;
 use std::sync::Arc;
 use tracing::trace;
 
@@ -20,6 +21,28 @@ pub mod file_io;
 pub mod osd_io;
 pub mod user_io;
 
+/// An error returned when executing an SPI command.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SpiError {
+    /// The core answered with an unexpected value.
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+}
+
+impl From<SpiError> for String {
+    fn from(value: SpiError) -> Self {
+        value.to_string()
+    }
+}
+
+impl From<SpiError> for one_fpga::core::Error {
+    fn from(value: SpiError) -> Self {
+        match value {
+            SpiError::Protocol(message) => one_fpga::core::Error::Protocol(message),
+        }
+    }
+}
+
 pub trait SpiCommandExt: Sized {
     fn command(&mut self, command: impl IntoLowLevelSpiCommand) -> SpiCommandGuard<'_, Self> {
         self.command_read(command, &mut 0)
@@ -48,7 +71,7 @@ pub trait IntoLowLevelSpiCommand {
 pub trait SpiCommand: Debug {
     const NAME: &'static str;
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String>;
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError>;
 }
 
 pub struct SpiCommandGuard<'a, S: SpiCommandExt> {
@@ -62,7 +85,7 @@ impl<'a, S: SpiCommandExt> SpiCommandGuard<'a, S> {
         Self { spi, feature }
     }
 
-    pub fn execute<Cmd: SpiCommand>(&mut self, mut command: Cmd) -> Result<(), String> {
+    pub fn execute<Cmd: SpiCommand>(&mut self, mut command: Cmd) -> Result<(), SpiError> {
         let _span = tracing::trace_span!("spi::execute", name = Cmd::NAME).entered();
         trace!(?command, "Command");
         command.execute(self.spi)
@@ -201,17 +224,133 @@ impl<S: SpiCommandExt> Drop for SpiCommandGuard<'_, S> {
     }
 }
 
+/// The words level transport of an SPI bus. [`Spi`] keeps track of the features
+/// and commands, and sends the words through its bus.
+pub(crate) trait SpiBus: Debug {
+    /// Set the feature bits in `mask` to `enabled`.
+    fn set_features(&self, mask: u32, enabled: bool);
+
+    /// Send the first word of a command. Returns the word received from the core.
+    fn command(&self, command: u16) -> u16 {
+        self.write(command)
+    }
+
+    /// Send a 16-bit word to the core. Returns the 16-bit word received from the core.
+    fn write(&self, word: u16) -> u16;
+
+    /// Send bytes to the core without waiting for it to acknowledge them.
+    fn write_block_8(&self, buffer: &[u8]);
+
+    /// Send words to the core without waiting for it to acknowledge them.
+    fn write_block_16(&self, buffer: &[u16]);
+}
+
+/// The SPI bus of the FPGA, driven through the GPO and GPI registers.
+struct FpgaSpiBus<M: MemoryMapper> {
+    soc: Arc<UnsafeCell<SocFpga<M>>>,
+}
+
+impl<M: MemoryMapper> Debug for FpgaSpiBus<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FpgaSpiBus").finish_non_exhaustive()
+    }
+}
+
+impl<M: MemoryMapper> FpgaSpiBus<M> {
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn soc_mut(&self) -> &mut SocFpga<M> {
+        unsafe { &mut *self.soc.get() }
+    }
+}
+
+impl<M: MemoryMapper> SpiBus for FpgaSpiBus<M> {
+    #[inline]
+    fn set_features(&self, mask: u32, enabled: bool) {
+        let regs = self.soc_mut().regs_mut();
+        let gpo = (regs.gpo() & SpiFeatureSet::ALL.as_u32()) | 0x8000_0000;
+        if enabled {
+            regs.set_gpo(gpo | mask);
+        } else {
+            regs.set_gpo(gpo & !mask);
+        }
+    }
+
+    #[inline]
+    fn write(&self, word: u16) -> u16 {
+        let regs = self.soc_mut().regs_mut();
+
+        // Remove the strobe bit and set the data bits.
+        let gpo = (regs.gpo() & !(SSPI_DATA_MASK | SSPI_STROBE)) | (word as u32);
+
+        regs.set_gpo(gpo);
+        regs.set_gpo(gpo | SSPI_STROBE);
+
+        // Wait for the ACK bit to be unset to give time to the core to get some work.
+        loop {
+            let gpi = regs.gpi();
+            if gpi & SSPI_ACK != 0 {
+                break;
+            }
+        }
+
+        // Send the actual data without the strobe, then wait for the core to get done.
+        regs.set_gpo(gpo);
+        let gpi = loop {
+            let gpi = regs.gpi();
+            if gpi & SSPI_ACK == 0 {
+                break gpi as u16;
+            }
+        };
+        trace!(
+            word = format!("0x{word:04X} ({word})"),
+            gpi = format!("0x{gpi:04X} ({gpi})"),
+            "write"
+        );
+        gpi
+    }
+
+    #[inline]
+    fn write_block_8(&self, buffer: &[u8]) {
+        let regs = self.soc_mut().regs_mut();
+        let gpo_h = (regs.gpo() & !(SSPI_DATA_MASK | SSPI_STROBE)) | 0x8000_0000;
+        let mut gpo = gpo_h;
+
+        buffer.iter().for_each(|b| {
+            gpo = gpo_h | (*b as u32);
+            regs.set_gpo(gpo);
+            regs.set_gpo(gpo | SSPI_STROBE);
+        });
+        regs.set_gpo(gpo);
+    }
+
+    #[inline]
+    fn write_block_16(&self, buffer: &[u16]) {
+        let regs = self.soc_mut().regs_mut();
+        let gpo_h = regs.gpo() & !(SSPI_DATA_MASK | SSPI_STROBE);
+        let mut gpo = gpo_h;
+
+        buffer.iter().for_each(|b| {
+            gpo = gpo_h | (*b as u32);
+            regs.set_gpo(gpo);
+            regs.set_gpo(gpo | SSPI_STROBE);
+        });
+        regs.set_gpo(gpo);
+    }
+}
+
 #[derive(Debug)]
 pub struct Spi<M: MemoryMapper> {
     soc: Arc<UnsafeCell<SocFpga<M>>>,
 
+    // The bus the words are sent through.
+    bus: Rc<dyn SpiBus>,
+
     // Ref counting features to prevent double enable (performance) and double
     // disable (error). We only actually enable if the refcount is 0, and disable
     // if the refcount is 1.
     features: Arc<RefCell<fixed_map::Map<SpiFeature, u32>>>,
 }
-unsafe impl<M: MemoryMapper> Send for Spi<M> {}
-unsafe impl<M: MemoryMapper> Sync for Spi<M> {}
 
 // We don't store the memory mapper, so it's safe to implement Clone.
 impl<M: MemoryMapper> Clone for Spi<M> {
@@ -219,15 +358,26 @@ impl<M: MemoryMapper> Clone for Spi<M> {
     fn clone(&self) -> Self {
         Self {
             soc: self.soc.clone(),
+            bus: self.bus.clone(),
             features: self.features.clone(),
         }
     }
 }
 
-impl<M: MemoryMapper> Spi<M> {
+impl<M: MemoryMapper + 'static> Spi<M> {
     pub fn new(soc: Arc<UnsafeCell<SocFpga<M>>>) -> Self {
+        let bus = Rc::new(FpgaSpiBus { soc: soc.clone() });
+        Self::with_bus(soc, bus)
+    }
+}
+
+impl<M: MemoryMapper> Spi<M> {
+    /// Create an SPI that sends its words through `bus` instead of the GPO/GPI
+    /// registers of `soc`.
+    pub(crate) fn with_bus(soc: Arc<UnsafeCell<SocFpga<M>>>, bus: Rc<dyn SpiBus>) -> Self {
         Self {
             soc,
+            bus,
             features: Default::default(),
         }
     }
@@ -238,7 +388,7 @@ impl<M: MemoryMapper> Spi<M> {
     }
 
     #[inline]
-    pub fn execute<Cmd: SpiCommand>(&mut self, mut command: Cmd) -> Result<(), String> {
+    pub fn execute<Cmd: SpiCommand>(&mut self, mut command: Cmd) -> Result<(), SpiError> {
         let _span = tracing::trace_span!("spi::execute", name = Cmd::NAME).entered();
         trace!(?command, "Command");
         command.execute(self)
@@ -259,9 +409,7 @@ impl<M: MemoryMapper> Spi<M> {
             return;
         }
 
-        let regs = self.soc_mut().regs_mut();
-        let gpo = (regs.gpo() & SpiFeatureSet::ALL.as_u32()) | 0x8000_0000;
-        regs.set_gpo(gpo | new_mask);
+        self.bus.set_features(new_mask, true);
     }
 
     #[inline]
@@ -283,9 +431,7 @@ impl<M: MemoryMapper> Spi<M> {
             return;
         }
 
-        let regs = self.soc_mut().regs_mut();
-        let gpo: u32 = (regs.gpo() & SpiFeatureSet::ALL.as_u32()) | 0x8000_0000;
-        regs.set_gpo(gpo & !new_mask);
+        self.bus.set_features(new_mask, false);
     }
 
     #[inline]
@@ -316,7 +462,7 @@ impl<M: MemoryMapper> Spi<M> {
     ) -> SpiFeatureSet {
         let (feature, command) = command.into_ll_spi_command();
         self.enable(feature);
-        *out = self.write(command);
+        *out = self.bus.command(command);
         feature
     }
 
@@ -343,55 +489,16 @@ impl<M: MemoryMapper> Spi<M> {
     /// Send a 16-bit word to the core. Returns the 16-bit word received from the core.
     #[inline]
     pub fn write(&mut self, word: u16) -> u16 {
-        let regs = self.soc_mut().regs_mut();
-
-        // Remove the strobe bit and set the data bits.
-        let gpo = (regs.gpo() & !(SSPI_DATA_MASK | SSPI_STROBE)) | (word as u32);
-
-        regs.set_gpo(gpo);
-        regs.set_gpo(gpo | SSPI_STROBE);
-
-        // Wait for the ACK bit to be unset to give time to the core to get some work.
-        loop {
-            let gpi = regs.gpi();
-            if gpi & SSPI_ACK != 0 {
-                break;
-            }
-        }
-
-        // Send the actual data without the strobe, then wait for the core to get done.
-        regs.set_gpo(gpo);
-        let gpi = loop {
-            let gpi = regs.gpi();
-            if gpi & SSPI_ACK == 0 {
-                break gpi as u16;
-            }
-        };
-        trace!(
-            word = format!("0x{word:04X} ({word})"),
-            gpi = format!("0x{gpi:04X} ({gpi})"),
-            "write"
-        );
-        gpi
+        self.bus.write(word)
     }
 
     #[inline]
-    pub fn write_block_8(&mut self, buffer: &[u8]) -> Result<usize, String> {
+    pub fn write_block_8(&mut self, buffer: &[u8]) -> Result<usize, SpiError> {
         if buffer.is_empty() {
             return Ok(0);
         }
 
-        let regs = self.soc_mut().regs_mut();
-        let gpo_h = (regs.gpo() & !(SSPI_DATA_MASK | SSPI_STROBE)) | 0x8000_0000;
-        let mut gpo = gpo_h;
-
-        buffer.iter().for_each(|b| {
-            gpo = gpo_h | (*b as u32);
-            regs.set_gpo(gpo);
-            regs.set_gpo(gpo | SSPI_STROBE);
-        });
-        regs.set_gpo(gpo);
-
+        self.bus.write_block_8(buffer);
         Ok(buffer.len())
     }
 
@@ -406,16 +513,7 @@ impl<M: MemoryMapper> Spi<M> {
             return;
         }
 
-        let regs = self.soc_mut().regs_mut();
-        let gpo_h = regs.gpo() & !(SSPI_DATA_MASK | SSPI_STROBE);
-        let mut gpo = gpo_h;
-
-        buffer.iter().for_each(|b| {
-            gpo = gpo_h | (*b as u32);
-            regs.set_gpo(gpo);
-            regs.set_gpo(gpo | SSPI_STROBE);
-        });
-        regs.set_gpo(gpo);
+        self.bus.write_block_16(buffer);
     }
 }
 
@@ -496,6 +594,117 @@ impl<M: MemoryMapper> SpiCommandExt for Spi<M> {
     }
 }
 
+/// An SPI bus that records the commands and words written to it instead of talking
+/// to the FPGA, to test commands without hardware. All reads return 0.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct FakeSpi {
+    /// The commands sent, with the words written after each of them.
+    pub commands: Vec<(u16, Vec<u16>)>,
+}
+
+#[cfg(test)]
+impl FakeSpi {
+    fn push(&mut self, word: u16) {
+        if let Some((_, words)) = self.commands.last_mut() {
+            words.push(word);
+        }
+    }
+}
+
+#[cfg(test)]
+impl SpiBus for RefCell<FakeSpi> {
+    fn set_features(&self, _mask: u32, _enabled: bool) {}
+
+    fn command(&self, command: u16) -> u16 {
+        self.borrow_mut().commands.push((command, vec![]));
+        0
+    }
+
+    fn write(&self, word: u16) -> u16 {
+        self.borrow_mut().push(word);
+        0
+    }
+
+    fn write_block_8(&self, buffer: &[u8]) {
+        buffer
+            .iter()
+            .for_each(|b| self.borrow_mut().push(*b as u16));
+    }
+
+    fn write_block_16(&self, buffer: &[u16]) {
+        buffer.iter().for_each(|w| self.borrow_mut().push(*w));
+    }
+}
+
+#[cfg(test)]
+impl SpiCommandExt for FakeSpi {
+    fn command_read(
+        &mut self,
+        command: impl IntoLowLevelSpiCommand,
+        out: &mut u16,
+    ) -> SpiCommandGuard<'_, Self> {
+        let (feature, command) = command.into_ll_spi_command();
+        self.commands.push((command, vec![]));
+        *out = 0;
+        SpiCommandGuard::new(self, feature)
+    }
+
+    fn write(&mut self, word: u16) -> &mut Self {
+        self.push(word);
+        self
+    }
+
+    fn write_read(&mut self, word: u16, out: &mut u16) -> &mut Self {
+        self.push(word);
+        *out = 0;
+        self
+    }
+
+    fn write_read_b(&mut self, byte: u8, out: &mut u8) -> &mut Self {
+        self.push(byte as u16);
+        *out = 0;
+        self
+    }
+
+    fn write_cond(&mut self, cond: bool, word: u16) -> &mut Self {
+        if cond {
+            self.push(word);
+        }
+        self
+    }
+
+    fn write_cond_b(&mut self, cond: bool, byte: u8) -> &mut Self {
+        if cond {
+            self.push(byte as u16);
+        }
+        self
+    }
+
+    fn write_buffer(&mut self, buffer: &[u16]) -> &mut Self {
+        buffer.iter().for_each(|w| self.push(*w));
+        self
+    }
+
+    fn write_buffer_b(&mut self, buffer: &[u8]) -> &mut Self {
+        buffer.iter().for_each(|b| self.push(*b as u16));
+        self
+    }
+
+    fn write_b(&mut self, byte: u8) -> &mut Self {
+        self.push(byte as u16);
+        self
+    }
+
+    fn enable(&mut self, _feature: SpiFeatureSet) -> &mut Self {
+        self
+    }
+
+    fn disable(&mut self, _feature: SpiFeatureSet) -> &mut Self {
+        self
+    }
+}
+
 #[test]
 pub fn features_refcount() {
     let soc = SocFpga::create_for_test();
@@ -1,5 +1,5 @@
 use crate::fpga::feature::SpiFeatureSet;
-use crate::fpga::{IntoLowLevelSpiCommand, SpiCommand, SpiCommandExt};
+use crate::fpga::{IntoLowLevelSpiCommand, SpiCommand, SpiCommandExt, SpiError};
 use std::fmt::{Debug, Formatter};
 
 #[derive(Debug, Clone, Copy, PartialEq, strum::Display)]
@@ -26,7 +26,7 @@ impl SpiCommand for FileIndex {
     const NAME: &'static str = "FileIndex";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(Commands::FileIndex).write_b(self.0);
 
         Ok(())
@@ -55,7 +55,7 @@ impl SpiCommand for FileExtension<'_> {
     const NAME: &'static str = "FileExtension";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let ext_bytes = self.0.as_bytes();
         // Extend to 4 characters with the dot.
         let ext: [u8; 4] = [
@@ -81,7 +81,7 @@ impl SpiCommand for FileTxEnabled {
     const NAME: &'static str = "FileTxEnabled";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(Commands::FileTx);
         command.write_b(0xff);
 
@@ -101,7 +101,7 @@ impl SpiCommand for FileTxDisabled {
     const NAME: &'static str = "FileTxDisabled";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(Commands::FileTx).write_b(0);
 
         Ok(())
@@ -116,7 +116,7 @@ impl SpiCommand for FileTxData8Bits<'_> {
     const NAME: &'static str = "FileTxData8Bits";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(Commands::FileTxDat).write_buffer_b(self.0);
         Ok(())
     }
@@ -137,7 +137,7 @@ impl SpiCommand for FileTxData16Bits<'_> {
     const NAME: &'static str = "FileTxData16Bits";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(Commands::FileTxDat).write_buffer_w(self.0);
         Ok(())
     }
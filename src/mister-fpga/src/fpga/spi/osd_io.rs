@@ -1,5 +1,5 @@
 use crate::fpga::feature::SpiFeatureSet;
-use crate::fpga::{IntoLowLevelSpiCommand, SpiCommand, SpiCommandExt};
+use crate::fpga::{IntoLowLevelSpiCommand, SpiCommand, SpiCommandExt, SpiError};
 use std::fmt::{Debug, Formatter};
 
 /// OSD SPI commands.
@@ -44,7 +44,7 @@ impl SpiCommand for OsdIoWriteLine<'_> {
     const NAME: &'static str = "OsdIoWriteLine";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(OsdCommands::WriteLine(self.0))
             .write_buffer_b(self.1);
 
@@ -58,7 +58,7 @@ pub struct OsdEnable;
 impl SpiCommand for OsdEnable {
     const NAME: &'static str = "OsdEnable";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(OsdCommands::Enable);
         Ok(())
     }
@@ -70,7 +70,7 @@ pub struct OsdDisable;
 impl SpiCommand for OsdDisable {
     const NAME: &'static str = "OsdDisable";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(OsdCommands::Disable);
         Ok(())
     }
@@ -1,8 +1,9 @@
 use crate::core::buttons::ButtonMap;
 use crate::core::file::SdCard;
+use crate::core::mouse::Ps2MousePacket;
 use crate::core::video::edid::CustomVideoMode;
 use crate::fpga::feature::SpiFeatureSet;
-use crate::fpga::{IntoLowLevelSpiCommand, SpiCommand, SpiCommandExt};
+use crate::fpga::{IntoLowLevelSpiCommand, SpiCommand, SpiCommandExt, SpiError};
 use crate::keyboard::Ps2Scancode;
 use crate::types::StatusBitMap;
 use bitfield::bitfield;
@@ -36,7 +37,7 @@ pub(crate) enum UserIoCommands {
     UserIoButtonSwitch = 0x01,
     UserIoJoystick0 = 0x02,
     UserIoJoystick1 = 0x03,
-    // UserIoMouse = 0x04,
+    UserIoMouse = 0x04,
     UserIoKeyboard = 0x05,
     // UserIoKeyboardOsd = 0x06,
     UserIoJoystick2 = 0x10,
@@ -55,6 +56,9 @@ pub(crate) enum UserIoCommands {
 
     UserIoSetSdConf = 0x19,
 
+    /// Position of the left analog stick of a joystick.
+    UserIoAnalogStick = 0x1A,
+
     /// Set sd card status
     UserIoSetSdStat = 0x1C,
 
@@ -90,6 +94,12 @@ pub(crate) enum UserIoCommands {
     // Set a custom aspect ratio.
     UserIoSetArCust = 0x3A,
 
+    /// Set the UART mode and speed.
+    UserIoSetUart = 0x3B,
+
+    /// Position of the right analog stick of a joystick.
+    UserIoAnalogStick2 = 0x3D,
+
     UserIoGetFbParams = 0x40,
 }
 
@@ -144,7 +154,7 @@ impl SpiCommand for UserIoButtonSwitch {
     const NAME: &'static str = "UserIoButtonSwitch";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(UserIoCommands::UserIoButtonSwitch)
             .write(self.0);
         Ok(())
@@ -179,7 +189,7 @@ impl SpiCommand for UserIoJoystick {
     const NAME: &'static str = "UserIoJoystick";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let command = match self.0 {
             0 => UserIoCommands::UserIoJoystick0,
             1 => UserIoCommands::UserIoJoystick1,
@@ -209,6 +219,90 @@ impl UserIoJoystick {
     }
 }
 
+/// An analog stick on a joystick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalogStick {
+    Left,
+    Right,
+}
+
+/// Send the position of an analog stick to the core. Values are signed,
+/// centered on 0.
+#[derive(Debug)]
+pub struct UserIoAnalogJoystick {
+    index: u8,
+    stick: AnalogStick,
+    x: i8,
+    y: i8,
+    io_version: u8,
+}
+
+impl SpiCommand for UserIoAnalogJoystick {
+    const NAME: &'static str = "UserIoAnalogJoystick";
+
+    #[inline]
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
+        let command = match self.stick {
+            AnalogStick::Left => UserIoCommands::UserIoAnalogStick,
+            AnalogStick::Right => UserIoCommands::UserIoAnalogStick2,
+        };
+
+        let mut command = spi.command(command);
+        command.write_b(self.index);
+        if self.io_version != 0 {
+            command.write(((self.y as u8 as u16) << 8) | self.x as u8 as u16);
+        } else {
+            command.write_b(self.x as u8).write_b(self.y as u8);
+        }
+
+        Ok(())
+    }
+}
+
+impl UserIoAnalogJoystick {
+    pub const fn new(index: u8, stick: AnalogStick, x: i8, y: i8) -> Self {
+        Self {
+            index,
+            stick,
+            x,
+            y,
+            io_version: 0,
+        }
+    }
+
+    pub const fn with_io_version(self, io_version: u8) -> Self {
+        Self { io_version, ..self }
+    }
+}
+
+/// Send a PS/2 mouse packet to the core. Buttons 4 and 5 and the wheel are
+/// sent in the upper bytes, for cores that support IntelliMouse extensions.
+#[derive(Debug)]
+pub struct UserIoMouse(Ps2MousePacket);
+
+impl From<Ps2MousePacket> for UserIoMouse {
+    fn from(value: Ps2MousePacket) -> Self {
+        Self(value)
+    }
+}
+
+impl SpiCommand for UserIoMouse {
+    const NAME: &'static str = "UserIoMouse";
+
+    #[inline]
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
+        let packet = &self.0;
+        let extra_buttons = ((packet.buttons & 0x18) as u16) << 5;
+
+        spi.command(UserIoCommands::UserIoMouse)
+            .write(packet.flags() as u16 | ((packet.wheel as u8 as u16) << 8))
+            .write((packet.dx as u8 as u16) | extra_buttons)
+            .write(packet.dy as u8 as u16);
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct UserIoKeyboardKeyDown(u32);
 
@@ -228,7 +322,7 @@ impl SpiCommand for UserIoKeyboardKeyDown {
     const NAME: &'static str = "UserIoKeyboardKeyDown";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(UserIoCommands::UserIoKeyboard)
             .write_cond_b(self.0 & 0x080000 != 0, 0xE0)
             .write_b((self.0 & 0xFF) as u8);
@@ -256,7 +350,7 @@ impl SpiCommand for UserIoKeyboardKeyUp {
     const NAME: &'static str = "UserIoKeyboardKeyUp";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(UserIoCommands::UserIoKeyboard)
             .write_b(0xF0)
             .write_b(self.0 as u8);
@@ -272,7 +366,7 @@ impl SpiCommand for UserIoGetString<'_> {
     const NAME: &'static str = "UserIoGetString";
 
     #[inline]
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoCommands::UserIoGetString);
 
         let mut i = 0;
@@ -307,7 +401,7 @@ impl From<SystemTime> for UserIoRtc {
 impl SpiCommand for UserIoRtc {
     const NAME: &'static str = "UserIoRtc";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         // MSM6242B layout, with 4 bits per digit of sec, min, hour, day, month, year (2 digits),
         // and the weekday.
         let rtc = [
@@ -352,7 +446,7 @@ impl From<SystemTime> for Timestamp {
 impl SpiCommand for Timestamp {
     const NAME: &'static str = "Timestamp";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let timestamp = self.0.and_utc().timestamp();
         spi.command(UserIoCommands::UserIoRtc)
             .write(timestamp as u16)
@@ -369,7 +463,7 @@ pub struct GetStatusBits<'a>(pub &'a mut StatusBitMap, pub &'a mut u8);
 impl SpiCommand for GetStatusBits<'_> {
     const NAME: &'static str = "GetStatusBits";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut stchg = 0;
         let mut command = spi.command_read(UserIoCommands::UserIoGetStatusBits, &mut stchg);
 
@@ -396,7 +490,7 @@ pub struct SetStatusBits<'a>(pub &'a StatusBitMap);
 impl SpiCommand for SetStatusBits<'_> {
     const NAME: &'static str = "SetStatusBits";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let bits16 = self.0.as_raw_slice();
 
         spi.command(UserIoCommands::UserIoSetStatus32Bits)
@@ -424,7 +518,7 @@ pub struct SetSdConf {
 impl SpiCommand for SetSdConf {
     const NAME: &'static str = "SetSdConf";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoCommands::UserIoSetSdConf);
 
         if self.wide {
@@ -492,7 +586,7 @@ impl From<&SdCard> for SetSdInfo {
 impl SpiCommand for SetSdInfo {
     const NAME: &'static str = "SetSdInfo";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoCommands::UserIoSetSdInfo);
 
         trace!(?self, "SetSdInfo");
@@ -538,7 +632,7 @@ pub struct SetSdStat {
 impl SpiCommand for SetSdStat {
     const NAME: &'static str = "SetSdStat";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(UserIoCommands::UserIoSetSdStat)
             .write_b((1 << self.index) | if self.writable { 0 } else { 0x80 });
         Ok(())
@@ -650,7 +744,7 @@ pub struct GetSdStat<'a>(pub &'a mut SdStatOutput);
 impl SpiCommand for GetSdStat<'_> {
     const NAME: &'static str = "GetSdStat";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut c = 0;
         let mut command = spi.command_read(UserIoCommands::UserIoGetSdStat, &mut c);
 
@@ -693,7 +787,7 @@ impl SpiCommand for GetSdStat<'_> {
             } else if c & 0x2400 != 0 {
                 (3, SdOp::from(c & 0x0400 == 0))
             } else {
-                return Err(format!("Invalid status: {c:04X}"));
+                return Err(SpiError::Protocol(format!("Invalid SD status: {c:04X}")));
             };
 
             self.0.ack = if c & 4 != 0 {
@@ -719,7 +813,7 @@ pub struct SdRead<'a> {
 impl SpiCommand for SdRead<'_> {
     const NAME: &'static str = "SdRead";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoSectorRead::Read(self.ack));
 
         if self.wide {
@@ -747,7 +841,7 @@ pub struct SdWrite<'a> {
 impl SpiCommand for SdWrite<'_> {
     const NAME: &'static str = "SdWrite";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoSectorRead::Write(self.ack));
 
         if self.wide {
@@ -774,7 +868,7 @@ pub struct SetMemorySize(pub u16);
 impl SpiCommand for SetMemorySize {
     const NAME: &'static str = "SetMemorySize";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(UserIoCommands::UserIoSetMemSz).write(self.0);
         Ok(())
     }
@@ -803,13 +897,32 @@ impl SetMemorySize {
     }
 }
 
+/// Set the UART mode (see [`crate::uart::UartMode`]) and speed of the core.
+#[derive(Debug)]
+pub struct SetUartMode {
+    pub mode: u16,
+    pub speed: u32,
+}
+
+impl SpiCommand for SetUartMode {
+    const NAME: &'static str = "SetUartMode";
+
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
+        spi.command(UserIoCommands::UserIoSetUart)
+            .write(self.mode)
+            .write(self.speed as u16)
+            .write((self.speed >> 16) as u16);
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct SetFramebufferToCore;
 
 impl SpiCommand for SetFramebufferToCore {
     const NAME: &'static str = "SetFramebufferToCore";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         debug!("Setting framebuffer to core");
         spi.command(UserIoCommands::UserIoSetFramebuffer).write(0);
         Ok(())
@@ -830,7 +943,7 @@ pub struct SetFramebufferToHpsOutput {
 impl SpiCommand for SetFramebufferToHpsOutput {
     const NAME: &'static str = "SetFramebufferToHpsOutput";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         debug!(?self, "Setting framebuffer to HPS output");
 
         let mut out = 0;
@@ -868,7 +981,7 @@ pub struct IsGammaSupported<'a>(pub &'a mut bool);
 impl SpiCommand for IsGammaSupported<'_> {
     const NAME: &'static str = "IsGammaSupported";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut out = 0;
         spi.command_read(UserIoCommands::UserIoSetGamma, &mut out);
         *self.0 = out != 0;
@@ -882,7 +995,7 @@ pub struct DisableGamma;
 impl SpiCommand for DisableGamma {
     const NAME: &'static str = "DisableGamma";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(UserIoCommands::UserIoSetGamma).write_b(0);
         Ok(())
     }
@@ -894,7 +1007,7 @@ pub struct EnableGamma<'a>(pub &'a [(u8, u8, u8)]);
 impl SpiCommand for EnableGamma<'_> {
     const NAME: &'static str = "EnableGamma";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoCommands::UserIoSetGamma);
 
         for (i, (r, g, b)) in self.0.iter().enumerate() {
@@ -926,7 +1039,7 @@ impl From<(u16, u16)> for SetCustomAspectRatio {
 impl SpiCommand for SetCustomAspectRatio {
     const NAME: &'static str = "SetCustomAspectRatio";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoCommands::UserIoSetArCust);
 
         command
@@ -945,7 +1058,7 @@ pub struct SetVideoMode<'a>(pub &'a CustomVideoMode);
 impl SpiCommand for SetVideoMode<'_> {
     const NAME: &'static str = "SetVideoMode";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         let mut command = spi.command(UserIoCommands::UserIoSetVideo);
         let m = self.0;
         let p = m.param;
@@ -986,7 +1099,7 @@ pub struct SetAudioVolume(pub u8);
 impl SpiCommand for SetAudioVolume {
     const NAME: &'static str = "SetAudioVolume";
 
-    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), String> {
+    fn execute<S: SpiCommandExt>(&mut self, spi: &mut S) -> Result<(), SpiError> {
         spi.command(UserIoCommands::UserIoAudioVolume)
             .write_b(self.0);
         Ok(())
@@ -221,7 +221,7 @@ impl Default for FpgaFramebuffer<DevMemMemoryMapper> {
 }
 
 impl<M: MemoryMapper> FpgaFramebuffer<M> {
-    fn new(memory: M) -> Result<Self, &'static str> {
+    pub(crate) fn new(memory: M) -> Result<Self, &'static str> {
         Ok(Self { memory, ty_: None })
     }
 
@@ -276,6 +276,13 @@ impl<M: MemoryMapper> FpgaFramebuffer<M> {
         Ok(())
     }
 
+    /// Whether the scaler outputs a frame in a format [`Self::take_screenshot`]
+    /// supports.
+    pub fn can_take_screenshot(&self) -> bool {
+        unsafe { self.header_offset(0) }
+            .is_some_and(|header| header.scaler_pixel_format() == ScalerPixelFormat::RGB24)
+    }
+
     pub fn take_screenshot(&self) -> Result<DynamicImage, String> {
         // Bytes are in big endian, but ARM is in little endian.
         let header = self.first_header();
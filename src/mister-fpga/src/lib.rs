@@ -1,8 +1,14 @@
+pub mod archive;
+pub mod cheats;
 pub mod config_string;
 pub mod core;
+pub mod dip;
 pub mod fpga;
 pub mod framebuffer;
 pub mod keyboard;
+pub mod mra;
 pub mod osd;
+pub mod remember;
 pub mod savestate;
 pub mod types;
+pub mod uart;
@@ -0,0 +1,790 @@
+//! MRA files (MiSTer ROM Arcade) describe how to start an arcade game: the core
+//! (RBF) to load, how to build the ROMs of the game from the files of MAME ROM
+//! archives, and the DIP switches and buttons of the game.
+//!
+//! Each `<rom>` element is assembled in memory from its `<part>`, `<interleave>`
+//! and `<patch>` elements, and sent to the core on its index.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use tracing::debug;
+use zip::ZipArchive;
+
+use crate::core::buttons::ButtonMap;
+use crate::dip::{DipSwitch, DipSwitches};
+
+/// An error while reading an MRA file or assembling its ROMs.
+#[derive(Debug, thiserror::Error)]
+pub enum MraError {
+    #[error("Invalid MRA file: {0}")]
+    Invalid(String),
+
+    #[error("Invalid value {value:?} for {attribute:?}")]
+    InvalidValue {
+        attribute: &'static str,
+        value: String,
+    },
+
+    #[error("Core {0:?} not found")]
+    CoreNotFound(String),
+
+    #[error("ROM archive {0:?} not found")]
+    ArchiveNotFound(String),
+
+    #[error("ROM part {name:?} not found in {archives:?}")]
+    PartNotFound { name: String, archives: String },
+
+    #[error("ROM part {name:?} has CRC {actual:08x}, expected {expected:08x}")]
+    CrcMismatch {
+        name: String,
+        expected: u32,
+        actual: u32,
+    },
+
+    #[error("ROM part {name:?} is {size} bytes, expected at least {expected}")]
+    PartTooShort {
+        name: String,
+        size: usize,
+        expected: usize,
+    },
+
+    #[error("Invalid ROM archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<MraError> for one_fpga::core::Error {
+    fn from(value: MraError) -> Self {
+        match value {
+            MraError::Io(e) => one_fpga::core::Error::IoError(e),
+            MraError::Invalid(_) | MraError::InvalidValue { .. } => {
+                one_fpga::core::Error::InvalidConfig(value.to_string())
+            }
+            MraError::CoreNotFound(_)
+            | MraError::ArchiveNotFound(_)
+            | MraError::PartNotFound { .. } => {
+                one_fpga::core::Error::MissingFile(value.to_string())
+            }
+            MraError::CrcMismatch { .. } | MraError::PartTooShort { .. } | MraError::Zip(_) => {
+                one_fpga::core::Error::InvalidFile(value.to_string())
+            }
+        }
+    }
+}
+
+/// Parse a number attribute, either decimal or hexadecimal with a `0x` prefix.
+fn parse_number(attribute: &'static str, value: &str) -> Result<usize, MraError> {
+    let value = value.trim();
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|_| MraError::InvalidValue {
+        attribute,
+        value: value.to_string(),
+    })
+}
+
+/// Parse a hexadecimal attribute (e.g. a CRC), with or without a `0x` prefix.
+fn parse_hex(attribute: &'static str, value: &str) -> Result<u32, MraError> {
+    let value = value.trim();
+    let hex = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u32::from_str_radix(hex, 16).map_err(|_| MraError::InvalidValue {
+        attribute,
+        value: value.to_string(),
+    })
+}
+
+/// Parse inline bytes, written in hexadecimal and optionally separated by spaces.
+fn parse_bytes(attribute: &'static str, value: &str) -> Result<Vec<u8>, MraError> {
+    let hex: String = value.split_whitespace().collect();
+    hex::decode(&hex).map_err(|_| MraError::InvalidValue {
+        attribute,
+        value: value.trim().to_string(),
+    })
+}
+
+/// Split a list of archives separated by `|`, in search order.
+fn split_zips(zips: &str) -> Vec<&str> {
+    zips.split('|')
+        .map(str::trim)
+        .filter(|z| !z.is_empty())
+        .collect()
+}
+
+/// The content of an element without children (e.g. `<rbf>`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraText {
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+/// A part of a ROM, either a file (or a slice of a file) of a ROM archive, or
+/// bytes written inline in hexadecimal.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraPart {
+    #[serde(rename = "@name")]
+    pub name: Option<String>,
+
+    #[serde(rename = "@crc")]
+    pub crc: Option<String>,
+
+    /// The archives to look for this part in, overriding the ones of the ROM.
+    #[serde(rename = "@zip")]
+    pub zip: Option<String>,
+
+    /// The offset of this part in the file.
+    #[serde(rename = "@offset")]
+    pub offset: Option<String>,
+
+    /// The number of bytes of the file to use, from the offset.
+    #[serde(rename = "@length")]
+    pub length: Option<String>,
+
+    /// The number of times the part is repeated.
+    #[serde(rename = "@repeat")]
+    pub repeat: Option<String>,
+
+    /// Inside an interleave, the (1-based) byte of this part used for each
+    /// output byte, right to left. `0` means the output byte is not from this part.
+    #[serde(rename = "@map")]
+    pub map: Option<String>,
+
+    /// The inline bytes, if the part has no name.
+    #[serde(rename = "$text")]
+    pub data: Option<String>,
+}
+
+impl MraPart {
+    fn assemble(&self, zips: &[&str], archives: &mut RomArchives) -> Result<Vec<u8>, MraError> {
+        let mut data = match &self.name {
+            Some(name) => {
+                let zips = self
+                    .zip
+                    .as_deref()
+                    .map_or_else(|| zips.to_vec(), split_zips);
+                let crc = self
+                    .crc
+                    .as_deref()
+                    .map(|c| parse_hex("crc", c))
+                    .transpose()?;
+                let data = archives.read_part(&zips, name, crc)?;
+
+                let offset = self
+                    .offset
+                    .as_deref()
+                    .map_or(Ok(0), |o| parse_number("offset", o))?;
+                let length = match &self.length {
+                    Some(length) => parse_number("length", length)?,
+                    None => data.len().saturating_sub(offset),
+                };
+                let end = offset
+                    .checked_add(length)
+                    .ok_or_else(|| MraError::InvalidValue {
+                        attribute: "length",
+                        value: self.length.clone().unwrap_or_default(),
+                    })?;
+                data.get(offset..end)
+                    .ok_or_else(|| MraError::PartTooShort {
+                        name: name.clone(),
+                        size: data.len(),
+                        expected: end,
+                    })?
+                    .to_vec()
+            }
+            None => parse_bytes("part", self.data.as_deref().unwrap_or_default())?,
+        };
+
+        if let Some(repeat) = &self.repeat {
+            let count = parse_number("repeat", repeat)?;
+            if data.len().checked_mul(count).is_none() {
+                return Err(MraError::InvalidValue {
+                    attribute: "repeat",
+                    value: repeat.clone(),
+                });
+            }
+            data = data.repeat(count);
+        }
+        Ok(data)
+    }
+}
+
+/// Parts interleaved into words of `output` bits.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraInterleave {
+    #[serde(rename = "@output")]
+    pub output: String,
+
+    #[serde(rename = "part", default)]
+    pub parts: Vec<MraPart>,
+}
+
+impl MraInterleave {
+    /// Return, for each output byte, the (1-based) byte of a part's unit, or 0.
+    fn parse_map(map: &str, width: usize) -> Result<Vec<usize>, MraError> {
+        let invalid = || MraError::InvalidValue {
+            attribute: "map",
+            value: map.to_string(),
+        };
+        if map.len() != width {
+            return Err(invalid());
+        }
+        map.chars()
+            .rev()
+            .map(|c| c.to_digit(10).map(|d| d as usize).ok_or_else(invalid))
+            .collect()
+    }
+
+    fn assemble(&self, zips: &[&str], archives: &mut RomArchives) -> Result<Vec<u8>, MraError> {
+        let bits = parse_number("output", &self.output)?;
+        let width = bits / 8;
+        if width == 0 || bits % 8 != 0 || self.parts.is_empty() || self.parts.len() > width {
+            return Err(MraError::InvalidValue {
+                attribute: "output",
+                value: self.output.clone(),
+            });
+        }
+
+        // Without maps, each part gets an equal share of the output word, in order.
+        let share = width / self.parts.len();
+        let mut inputs = Vec::with_capacity(self.parts.len());
+        for (i, part) in self.parts.iter().enumerate() {
+            let map = match &part.map {
+                Some(map) => Self::parse_map(map, width)?,
+                None => (0..width)
+                    .map(|b| if b / share == i { b % share + 1 } else { 0 })
+                    .collect(),
+            };
+            let unit = map.iter().copied().max().unwrap_or(0);
+            if unit == 0 {
+                return Err(MraError::InvalidValue {
+                    attribute: "map",
+                    value: part.map.clone().unwrap_or_default(),
+                });
+            }
+            inputs.push((part.assemble(zips, archives)?, map, unit));
+        }
+
+        // Stop at the end of the shortest part.
+        let words = inputs
+            .iter()
+            .map(|(data, _, unit)| data.len() / unit)
+            .min()
+            .unwrap_or(0);
+        let mut output = vec![0; words * width];
+        for (data, map, unit) in &inputs {
+            for (word, input) in output.chunks_exact_mut(width).zip(data.chunks_exact(*unit)) {
+                for (byte, m) in word.iter_mut().zip(map) {
+                    if *m != 0 {
+                        *byte = input[m - 1];
+                    }
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Bytes written over the assembled ROM.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraPatch {
+    #[serde(rename = "@offset")]
+    pub offset: String,
+
+    #[serde(rename = "$text", default)]
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum MraRomEntry {
+    #[serde(rename = "part")]
+    Part(MraPart),
+
+    #[serde(rename = "interleave")]
+    Interleave(MraInterleave),
+
+    #[serde(rename = "patch")]
+    Patch(MraPatch),
+}
+
+/// A ROM to send to the core.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraRom {
+    /// The file index to send the ROM on.
+    #[serde(rename = "@index", default)]
+    pub index: u8,
+
+    /// The archives to look for parts in, separated by `|`.
+    #[serde(rename = "@zip", default)]
+    pub zip: String,
+
+    #[serde(rename = "$value", default)]
+    pub entries: Vec<MraRomEntry>,
+}
+
+impl MraRom {
+    /// Assemble the ROM in memory. The CRC of every part is verified.
+    pub fn assemble(&self, archives: &mut RomArchives) -> Result<Vec<u8>, MraError> {
+        let zips = split_zips(&self.zip);
+        let mut rom = Vec::new();
+        for entry in &self.entries {
+            match entry {
+                MraRomEntry::Part(part) => rom.extend(part.assemble(&zips, archives)?),
+                MraRomEntry::Interleave(i) => rom.extend(i.assemble(&zips, archives)?),
+                MraRomEntry::Patch(_) => {}
+            }
+        }
+
+        for entry in &self.entries {
+            if let MraRomEntry::Patch(patch) = entry {
+                let offset = parse_number("offset", &patch.offset)?;
+                let data = parse_bytes("patch", &patch.data)?;
+                offset
+                    .checked_add(data.len())
+                    .and_then(|end| rom.get_mut(offset..end))
+                    .ok_or_else(|| MraError::InvalidValue {
+                        attribute: "offset",
+                        value: patch.offset.clone(),
+                    })?
+                    .copy_from_slice(&data);
+            }
+        }
+        Ok(rom)
+    }
+}
+
+/// A DIP switch of the game.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraDip {
+    #[serde(rename = "@name")]
+    pub name: String,
+
+    /// The bit, or the first and last bits (inclusive) separated by a comma.
+    #[serde(rename = "@bits")]
+    pub bits: String,
+
+    /// The labels of the choices, separated by commas.
+    #[serde(rename = "@ids", default)]
+    pub ids: String,
+
+    /// The values of the choices. Defaults to the index of each choice.
+    #[serde(rename = "@values")]
+    pub values: Option<String>,
+}
+
+impl MraDip {
+    pub fn as_dip_switch(&self) -> Result<DipSwitch, MraError> {
+        let (first, last) = self
+            .bits
+            .split_once(',')
+            .unwrap_or((&self.bits, &self.bits));
+        let first = parse_number("bits", first)?;
+        let last = parse_number("bits", last)?;
+        if first > last || last >= 64 {
+            return Err(MraError::InvalidValue {
+                attribute: "bits",
+                value: self.bits.clone(),
+            });
+        }
+
+        let ids: Vec<String> = self.ids.split(',').map(|s| s.trim().to_string()).collect();
+        let values = match &self.values {
+            Some(values) => values
+                .split(',')
+                .map(|v| parse_number("values", v).map(|v| v as u64))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => (0..ids.len() as u64).collect(),
+        };
+
+        Ok(DipSwitch::with_values(
+            self.name.trim(),
+            first as u8..last as u8 + 1,
+            ids.into_iter().zip(values).collect(),
+        ))
+    }
+}
+
+/// The DIP switches of the game.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraSwitches {
+    /// The default value, as hexadecimal bytes separated by commas (first byte
+    /// is the lowest).
+    #[serde(rename = "@default")]
+    pub default: Option<String>,
+
+    #[serde(rename = "dip", default)]
+    pub dips: Vec<MraDip>,
+}
+
+impl MraSwitches {
+    pub fn as_dip_switches(&self) -> Result<DipSwitches, MraError> {
+        let mut default = 0u64;
+        for (i, byte) in self.default.iter().flat_map(|d| d.split(',')).enumerate() {
+            let byte = parse_hex("default", byte)?;
+            if i >= 8 || byte > 0xFF {
+                return Err(MraError::InvalidValue {
+                    attribute: "default",
+                    value: self.default.clone().unwrap_or_default(),
+                });
+            }
+            default |= (byte as u64) << (i * 8);
+        }
+
+        let switches = self
+            .dips
+            .iter()
+            .map(MraDip::as_dip_switch)
+            .collect::<Result<_, _>>()?;
+        Ok(DipSwitches::new(switches, default))
+    }
+}
+
+/// The buttons of the game.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MraButtons {
+    /// The names of the buttons, separated by commas.
+    #[serde(rename = "@names", default)]
+    pub names: String,
+
+    /// The gamepad button mapped to each button by default, separated by commas.
+    #[serde(rename = "@default")]
+    pub default: Option<String>,
+}
+
+impl MraButtons {
+    pub fn names(&self) -> Vec<&str> {
+        self.names.split(',').map(str::trim).collect()
+    }
+
+    /// The default map of gamepad buttons to the game buttons, if any.
+    pub fn default_map(&self) -> Option<ButtonMap> {
+        let default = self.default.as_deref()?;
+        Some(ButtonMap::map_from_snes_list(
+            &default.split(',').collect::<Vec<_>>(),
+        ))
+    }
+}
+
+/// An MRA file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename = "misterromdescription")]
+pub struct Mra {
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default)]
+    pub setname: String,
+
+    /// The name of the core, without its date or extension.
+    #[serde(default)]
+    pub rbf: MraText,
+
+    #[serde(rename = "rom", default)]
+    pub roms: Vec<MraRom>,
+
+    pub switches: Option<MraSwitches>,
+
+    pub buttons: Option<MraButtons>,
+}
+
+impl FromStr for Mra {
+    type Err = MraError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        quick_xml::de::from_str(s).map_err(|e| MraError::Invalid(e.to_string()))
+    }
+}
+
+impl Mra {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MraError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Find the core of this game in `dirs`. Cores are named after the `<rbf>`
+    /// element, optionally prefixed with `Arcade-` and followed by a date (e.g.
+    /// `Arcade-Galaga_20240101.rbf`). The latest core of the first directory
+    /// that contains one is returned.
+    pub fn find_rbf(&self, dirs: &[impl AsRef<Path>]) -> Result<PathBuf, MraError> {
+        let name = self.rbf.value.trim().to_lowercase();
+        let matches = |path: &Path| {
+            if !path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("rbf"))
+            {
+                return false;
+            }
+            let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().to_lowercase()) else {
+                return false;
+            };
+            let stem = stem.strip_prefix("arcade-").unwrap_or(&stem);
+            stem.strip_prefix(name.as_str()).is_some_and(|rest| {
+                rest.is_empty()
+                    || rest
+                        .strip_prefix('_')
+                        .is_some_and(|date| date.chars().all(|c| c.is_ascii_digit()))
+            })
+        };
+
+        if !name.is_empty() {
+            for dir in dirs {
+                let Ok(entries) = std::fs::read_dir(dir) else {
+                    continue;
+                };
+                if let Some(path) = entries
+                    .filter_map(Result::ok)
+                    .map(|e| e.path())
+                    .filter(|p| matches(p))
+                    .max()
+                {
+                    return Ok(path);
+                }
+            }
+        }
+        Err(MraError::CoreNotFound(self.rbf.value.trim().to_string()))
+    }
+
+    /// The DIP switches of the game (empty if it has none).
+    pub fn dip_switches(&self) -> Result<DipSwitches, MraError> {
+        self.switches
+            .as_ref()
+            .map_or(Ok(DipSwitches::default()), MraSwitches::as_dip_switches)
+    }
+}
+
+/// The ROM archives (MAME zip files) used to assemble ROMs, looked up by name
+/// in a list of directories.
+pub struct RomArchives {
+    dirs: Vec<PathBuf>,
+    archives: HashMap<String, Option<ZipArchive<File>>>,
+}
+
+impl RomArchives {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self {
+            dirs,
+            archives: HashMap::new(),
+        }
+    }
+
+    fn archive(&mut self, name: &str) -> Result<Option<&mut ZipArchive<File>>, MraError> {
+        if !self.archives.contains_key(name) {
+            let archive = match self.dirs.iter().map(|d| d.join(name)).find(|p| p.is_file()) {
+                Some(path) => {
+                    debug!(?path, "Opening ROM archive");
+                    Some(ZipArchive::new(File::open(path)?)?)
+                }
+                None => None,
+            };
+            self.archives.insert(name.to_string(), archive);
+        }
+        Ok(self.archives.get_mut(name).and_then(Option::as_mut))
+    }
+
+    /// Read a file from the first archive in `zips` that contains it, by name or
+    /// by CRC. If a CRC is given, the content of the file is verified.
+    fn read_part(
+        &mut self,
+        zips: &[&str],
+        name: &str,
+        crc: Option<u32>,
+    ) -> Result<Vec<u8>, MraError> {
+        let mut found_archive = false;
+        let mut mismatch = None;
+
+        for zip in zips {
+            let Some(archive) = self.archive(zip)? else {
+                continue;
+            };
+            found_archive = true;
+
+            // Clones can have files with the same name as their parent but a
+            // different content, so a name with the wrong CRC is not enough.
+            let by_name = archive.index_for_name(name).filter(|i| {
+                crc.is_none_or(|crc| archive.by_index_raw(*i).is_ok_and(|f| f.crc32() == crc))
+            });
+            let by_crc = || {
+                let crc = crc?;
+                (0..archive.len())
+                    .find(|i| archive.by_index_raw(*i).is_ok_and(|f| f.crc32() == crc))
+            };
+            let Some(index) = by_name.or_else(by_crc) else {
+                if let (Some(i), Some(expected)) = (archive.index_for_name(name), crc) {
+                    let actual = archive.by_index_raw(i)?.crc32();
+                    mismatch.get_or_insert((expected, actual));
+                }
+                continue;
+            };
+
+            let mut file = archive.by_index(index)?;
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+
+            let actual = crc32fast::hash(&data);
+            match crc {
+                Some(expected) if expected != actual => {
+                    mismatch = Some((expected, actual));
+                }
+                _ => return Ok(data),
+            }
+        }
+
+        let archives = zips.join("|");
+        match mismatch {
+            Some((expected, actual)) => Err(MraError::CrcMismatch {
+                name: name.to_string(),
+                expected,
+                actual,
+            }),
+            None if !found_archive => Err(MraError::ArchiveNotFound(archives)),
+            None => Err(MraError::PartNotFound {
+                name: name.to_string(),
+                archives,
+            }),
+        }
+    }
+}
+
+#[test]
+fn mra() {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let dir = std::env::temp_dir().join(format!("mister-fpga-mra-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut zip = zip::ZipWriter::new(File::create(dir.join("game.zip")).unwrap());
+    for (name, content) in [("hi.bin", b"ACEG"), ("lo.bin", b"BDFH")] {
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content).unwrap();
+    }
+    zip.finish().unwrap();
+    std::fs::write(dir.join("Arcade-Game_20240101.rbf"), b"").unwrap();
+    std::fs::write(dir.join("Arcade-Game_20230101.rbf"), b"").unwrap();
+
+    let hi = crc32fast::hash(b"ACEG");
+    let lo = crc32fast::hash(b"BDFH");
+    let mra: Mra = format!(
+        r#"<misterromdescription>
+            <name>Game</name>
+            <rbf alt="other">game</rbf>
+            <switches default="05,FF">
+                <dip bits="0,1" name="Lives" ids="1,2,3,5" values="0,1,2,3"/>
+                <dip bits="8" name="Demo Sound" ids="Off,On"/>
+            </switches>
+            <buttons names="Fire,Start,Coin" default="A,Start,Select"/>
+            <rom index="1"><part>01 02</part></rom>
+            <rom index="0" zip="clone.zip|game.zip">
+                <interleave output="16">
+                    <part name="hi.bin" crc="{hi:08x}" map="01"/>
+                    <part name="renamed.bin" crc="{lo:08x}" map="10"/>
+                </interleave>
+                <part name="hi.bin" offset="2" length="2" repeat="2"/>
+                <part repeat="2">FF</part>
+                <patch offset="0x1">58</patch>
+            </rom>
+        </misterromdescription>"#
+    )
+    .parse()
+    .unwrap();
+
+    assert_eq!(mra.name, "Game");
+    assert_eq!(
+        mra.find_rbf(&[&dir]).unwrap(),
+        dir.join("Arcade-Game_20240101.rbf")
+    );
+
+    let dips = mra.dip_switches().unwrap();
+    assert_eq!(dips.default_value(), 0xFF05);
+    assert_eq!(dips.switches()[0].choice(dips.value()), Some(1));
+    assert_eq!(dips.switches()[1].bits, 8..9);
+    assert_eq!(
+        mra.buttons.as_ref().unwrap().names(),
+        ["Fire", "Start", "Coin"]
+    );
+
+    let mut archives = RomArchives::new(vec![dir.clone()]);
+    assert_eq!(mra.roms[0].index, 1);
+    assert_eq!(mra.roms[0].assemble(&mut archives).unwrap(), [1, 2]);
+    assert_eq!(
+        mra.roms[1].assemble(&mut archives).unwrap(),
+        b"AXCDEFGHEGEG\xFF\xFF"
+    );
+
+    let rom = |part: &str| -> MraRom {
+        format!(r#"<misterromdescription><rom zip="game.zip">{part}</rom></misterromdescription>"#)
+            .parse::<Mra>()
+            .unwrap()
+            .roms
+            .remove(0)
+    };
+    assert!(matches!(
+        rom(r#"<part name="hi.bin" crc="12345678"/>"#).assemble(&mut archives),
+        Err(MraError::CrcMismatch { expected: 0x12345678, actual, .. }) if actual == hi
+    ));
+    assert!(matches!(
+        rom(r#"<part name="missing.bin"/>"#).assemble(&mut archives),
+        Err(MraError::PartNotFound { .. })
+    ));
+    assert!(matches!(
+        rom(r#"<part name="hi.bin" length="8"/>"#).assemble(&mut archives),
+        Err(MraError::PartTooShort {
+            size: 4,
+            expected: 8,
+            ..
+        })
+    ));
+
+    assert!(matches!(
+        rom(r#"<part name="hi.bin" offset="1" length="0xFFFFFFFFFFFFFFFF"/>"#)
+            .assemble(&mut archives),
+        Err(MraError::InvalidValue {
+            attribute: "length",
+            ..
+        })
+    ));
+    assert!(matches!(
+        rom(r#"<part repeat="0xFFFFFFFFFFFFFFFF">01 02</part>"#).assemble(&mut archives),
+        Err(MraError::InvalidValue {
+            attribute: "repeat",
+            ..
+        })
+    ));
+    assert!(matches!(
+        rom(r#"<part>01</part><patch offset="0xFFFFFFFFFFFFFFFF">01</patch>"#)
+            .assemble(&mut archives),
+        Err(MraError::InvalidValue {
+            attribute: "offset",
+            ..
+        })
+    ));
+    assert!(matches!(
+        one_fpga::core::Error::from(MraError::PartNotFound {
+            name: "hi.bin".to_string(),
+            archives: "game.zip".to_string()
+        }),
+        one_fpga::core::Error::MissingFile(_)
+    ));
+
+    let mut missing = RomArchives::new(vec![dir.join("missing")]);
+    assert!(matches!(
+        rom(r#"<part name="hi.bin"/>"#).assemble(&mut missing),
+        Err(MraError::ArchiveNotFound(name)) if name == "game.zip"
+    ));
+    assert!(matches!(
+        "<misterromdescription><rom".parse::<Mra>(),
+        Err(MraError::Invalid(_))
+    ));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,139 @@
+//! Files remembered by `FC` entries of the config string (e.g. palettes, boot
+//! ROMs or configuration files). The file chosen by the user is stored per file
+//! index, and sent again to the core the next time it starts.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use one_fpga::core::{CoreSettingItem, Error, SettingId};
+
+use crate::config_string::LoadFileInfo;
+
+/// The files remembered for a core, by file index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RememberedFiles {
+    files: BTreeMap<u8, PathBuf>,
+}
+
+impl RememberedFiles {
+    /// Parse remembered files from a string, with one `{index}\t{path}` entry
+    /// per line. Invalid lines are ignored.
+    pub fn parse(content: &str) -> Self {
+        let files = content
+            .lines()
+            .filter_map(|line| {
+                let (index, path) = line.split_once('\t')?;
+                Some((index.trim().parse().ok()?, PathBuf::from(path)))
+            })
+            .filter(|(_, path): &(u8, PathBuf)| !path.as_os_str().is_empty())
+            .collect();
+        Self { files }
+    }
+
+    /// Load remembered files from a file (see [`Self::parse`]).
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// Save the remembered files, in the format read by [`Self::from_file`].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn get(&self, index: u8) -> Option<&Path> {
+        self.files.get(&index).map(PathBuf::as_path)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &Path)> {
+        self.files.iter().map(|(i, p)| (*i, p.as_path()))
+    }
+
+    /// Remember a file for an index, or forget it if `path` is `None`. Returns
+    /// whether anything changed.
+    pub fn set(&mut self, index: u8, path: Option<PathBuf>) -> bool {
+        match path {
+            Some(path) => self.files.insert(index, path.clone()) != Some(path),
+            None => self.files.remove(&index).is_some(),
+        }
+    }
+
+    /// The ID of the setting that clears the remembered file of an entry.
+    pub fn clear_setting_id(info: &LoadFileInfo) -> SettingId {
+        SettingId::from_label(&format!("remember:{}", info.index))
+    }
+
+    /// Return the menu items of an `FC` entry: the file select, and a trigger
+    /// to clear the remembered file if there is one.
+    pub fn as_core_menu_items(&self, info: &LoadFileInfo) -> Vec<CoreSettingItem> {
+        let label = info.label.as_deref().unwrap_or("Load File");
+        let mut items = vec![CoreSettingItem::file_select(
+            info.setting_id(),
+            label,
+            info.extensions.iter().map(|e| e.to_string()).collect(),
+        )];
+
+        if let Some(path) = self.get(info.index) {
+            let name = path
+                .file_name()
+                .map_or_else(|| path.to_string_lossy(), |n| n.to_string_lossy());
+            items.push(CoreSettingItem::trigger(
+                Self::clear_setting_id(info),
+                &format!("Clear {label} ({name})"),
+            ));
+        }
+        items
+    }
+}
+
+impl std::fmt::Display for RememberedFiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, path) in &self.files {
+            writeln!(f, "{index}\t{}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn remembered_files() {
+    use std::str::FromStr;
+
+    let mut files = RememberedFiles::parse("3\t/media/fat/palettes/Smooth.pal\ninvalid\n4\t\n");
+    assert_eq!(
+        files.get(3),
+        Some(Path::new("/media/fat/palettes/Smooth.pal"))
+    );
+    assert_eq!(files.iter().count(), 1);
+
+    assert!(files.set(1, Some(PathBuf::from("/boot.rom"))));
+    assert!(!files.set(1, Some(PathBuf::from("/boot.rom"))));
+    assert_eq!(RememberedFiles::parse(&files.to_string()), files);
+
+    let config = crate::config_string::Config::from_str("TEST;;FC3,PAL,Palette;FC2,ROM;").unwrap();
+    let infos: Vec<_> = config
+        .menu
+        .iter()
+        .filter_map(|m| m.as_load_file_info())
+        .collect();
+    let items = files.as_core_menu_items(infos[0]);
+    assert_eq!(items.len(), 2);
+    assert!(matches!(
+        &items[1],
+        CoreSettingItem::Trigger { id, label, .. }
+            if *id == RememberedFiles::clear_setting_id(infos[0])
+                && label == "Clear Palette (Smooth.pal)"
+    ));
+    assert_eq!(files.as_core_menu_items(infos[1]).len(), 1);
+
+    assert!(files.set(3, None));
+    assert!(!files.set(3, None));
+    assert_eq!(files.as_core_menu_items(infos[0]).len(), 1);
+}
@@ -1,11 +1,20 @@
 use crate::config_string::Config;
 use cyclone_v::memory::{DevMemMemoryMapper, MemoryMapper};
+use image::DynamicImage;
 use one_fpga::core::Error;
-use std::io::{Read, Write};
+use std::io::{Cursor, Read, Write};
 use std::ptr::NonNull;
 use std::slice;
+use std::time::SystemTime;
 
-const DEFAULT_MISTER_SAVESTATE_SLOTS: u32 = 4;
+pub mod container;
+
+pub use container::{SaveStateContainer, SaveStateError, SaveStateIdentity};
+
+pub const DEFAULT_MISTER_SAVESTATE_SLOTS: u32 = 4;
+
+/// The size of the thumbnails stored in save states.
+const THUMBNAIL_SIZE: (u32, u32) = (160, 120);
 
 pub struct SaveStateManager<M: MemoryMapper> {
     /// Memory Mapper. The Manager needs to own it to avoid it being dropped
@@ -17,37 +26,85 @@ pub struct SaveStateManager<M: MemoryMapper> {
 
     /// The savestate slots.
     slots: Vec<SaveState>,
+
+    /// The hash of the ROM loaded in the core, if any.
+    rom_hash: Option<u32>,
 }
 
 impl SaveStateManager<DevMemMemoryMapper> {
+    /// Create the save states of a core with the default number of slots (or
+    /// fewer if they don't all fit in memory).
     pub fn from_config_string(config: &Config) -> Option<Self> {
+        let nb_slots = DEFAULT_MISTER_SAVESTATE_SLOTS.min(Self::max_slots(config)?);
+        Self::with_slots(config, nb_slots).ok().flatten()
+    }
+
+    /// Create the save states of a core with `nb_slots` slots, each the size set
+    /// by the `SS` entry of the config string. Returns `None` if the core does
+    /// not support save states.
+    pub fn with_slots(config: &Config, nb_slots: u32) -> Result<Option<Self>, SaveStateError> {
+        let Some((base, size)) = config.settings().save_state else {
+            return Ok(None);
+        };
+        let max = Self::max_slots(config).unwrap_or(0);
+        if nb_slots == 0 || nb_slots > max {
+            return Err(SaveStateError::TooManySlots {
+                slots: nb_slots,
+                max,
+            });
+        }
+
+        let memory = DevMemMemoryMapper::create(base.as_usize(), size * (nb_slots as usize))
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(Some(Self::from_memory(
+            memory,
+            size,
+            nb_slots,
+            SaveStateIdentity::from_config(config),
+        )))
+    }
+
+    /// The maximum number of save state slots that fit in the host memory
+    /// after the base address of the `SS` entry of the config string.
+    pub fn max_slots(config: &Config) -> Option<u32> {
         let (base, size) = config.settings().save_state?;
-        let nb_slots = DEFAULT_MISTER_SAVESTATE_SLOTS;
+        let available = cyclone_v::ranges::HOST_MEMORY
+            .end
+            .saturating_sub(base.as_usize());
+        Some((available / size).min(u32::MAX as usize) as u32)
+    }
+}
 
+impl<M: MemoryMapper> SaveStateManager<M> {
+    fn from_memory(mut memory: M, size: usize, nb_slots: u32, identity: SaveStateIdentity) -> Self {
         // The memory setup is:
         //   0x00: u32 change detector.     A value that changes when the savestate changes.
         //   0x04: u32 size                 Size of the savestate, in 32-bits words.
         //   0x08..0x08 + (size * 4)        The savestate data.
-
-        let mut memory =
-            DevMemMemoryMapper::create(base.as_usize(), size * (nb_slots as usize)).unwrap();
-
         let slots = (0..nb_slots)
             .map(|i| {
                 let offset = (i as usize) * size;
-                SaveState::from_base(&mut memory, offset)
+                SaveState::from_base(&mut memory, offset, i, size, identity.clone())
             })
             .collect();
 
-        Some(Self {
+        Self {
             nb_slots,
             _memory: memory,
             slots,
-        })
+            rom_hash: identity.rom_hash,
+        }
+    }
+
+    /// Set the hash of the ROM loaded in the core, so save states of another
+    /// game are not loaded.
+    pub fn set_rom_hash(&mut self, rom_hash: Option<u32>) {
+        self.rom_hash = rom_hash;
+        for slot in &mut self.slots {
+            slot.identity.rom_hash = rom_hash;
+        }
     }
-}
 
-impl<M: MemoryMapper> SaveStateManager<M> {
     #[inline]
     pub fn slots(&self) -> &[SaveState] {
         &self.slots[..(self.nb_slots as usize)]
@@ -62,6 +119,11 @@ impl<M: MemoryMapper> SaveStateManager<M> {
     pub fn nb_slots(&self) -> usize {
         self.nb_slots as usize
     }
+
+    #[inline]
+    pub fn rom_hash(&self) -> Option<u32> {
+        self.rom_hash
+    }
 }
 
 #[repr(C)]
@@ -89,13 +151,6 @@ impl SaveStateInner {
         }
     }
 
-    fn all_mut(&mut self) -> &mut [u8] {
-        unsafe {
-            let s = std::ptr::addr_of!(*self) as *mut u8;
-            slice::from_raw_parts_mut(s, self.size_adjusted())
-        }
-    }
-
     fn reset(&mut self) {
         self.counter = 0xFFFFFFFF
     }
@@ -107,6 +162,15 @@ pub struct SaveState {
 
     /// The last counter known, used to detect any changes to the savestate data.
     counter: u32,
+
+    /// The index of this slot.
+    slot: u32,
+
+    /// The size of the slot in memory, in bytes.
+    capacity: usize,
+
+    /// The core and game of this save state.
+    identity: SaveStateIdentity,
 }
 
 impl one_fpga::core::SaveState for SaveState {
@@ -115,13 +179,71 @@ impl one_fpga::core::SaveState for SaveState {
     }
 
     fn save(&mut self, writer: &mut dyn Write) -> Result<(), Error> {
-        writer.write_all(self.inner().all())?;
-        self.counter = self.inner().counter;
+        self.save_with_screenshot(writer, None)
+    }
+
+    fn save_with_screenshot(
+        &mut self,
+        writer: &mut dyn Write,
+        screenshot: Option<&DynamicImage>,
+    ) -> Result<(), Error> {
+        let thumbnail = match screenshot {
+            Some(screenshot) => {
+                let (width, height) = THUMBNAIL_SIZE;
+                let mut png = Vec::new();
+                screenshot
+                    .thumbnail(width, height)
+                    .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+                    .map_err(|e| Error::Message(e.to_string()))?;
+                Some(png)
+            }
+            None => None,
+        };
+
+        let counter = self.inner().counter();
+        let container = SaveStateContainer {
+            identity: self.identity.clone(),
+            slot: self.slot,
+            timestamp: SystemTime::now(),
+            thumbnail,
+            payload: self.data()?.to_vec(),
+        };
+        container.write(writer)?;
+        self.counter = counter;
         Ok(())
     }
 
     fn load(&mut self, reader: &mut dyn Read) -> Result<(), Error> {
-        reader.read_exact(self.inner_mut().all_mut())?;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        // Raw memory dumps, as saved by MiSTer, have nothing to identify them
+        // and are loaded as long as their size matches their header.
+        let payload = if bytes.starts_with(&container::MAGIC) {
+            let container = SaveStateContainer::read(&mut bytes.as_slice())?;
+            self.identity.check(&container.identity)?;
+            container.payload
+        } else {
+            bytes
+        };
+        if payload.len() > self.capacity {
+            return Err(SaveStateError::SizeMismatch {
+                size: payload.len(),
+                capacity: self.capacity,
+            }
+            .into());
+        }
+        let words = payload
+            .get(4..8)
+            .map(|s| u32::from_le_bytes(s.try_into().unwrap()) as usize);
+        if words.and_then(|w| w.checked_add(2)?.checked_mul(4)) != Some(payload.len()) {
+            return Err(
+                SaveStateError::Invalid("Size does not match its header".to_string()).into(),
+            );
+        }
+
+        unsafe { slice::from_raw_parts_mut(self.inner.as_ptr() as *mut u8, payload.len()) }
+            .copy_from_slice(&payload);
         self.inner_mut().reset();
         self.counter = self.inner().counter;
         Ok(())
@@ -129,13 +251,36 @@ impl one_fpga::core::SaveState for SaveState {
 }
 
 impl SaveState {
-    fn from_base(memory: &mut impl MemoryMapper, offset: usize) -> Self {
+    fn from_base(
+        memory: &mut impl MemoryMapper,
+        offset: usize,
+        slot: u32,
+        capacity: usize,
+        identity: SaveStateIdentity,
+    ) -> Self {
         let inner = unsafe { NonNull::new(memory.as_mut_ptr::<u8>().add(offset) as _).unwrap() };
 
         Self {
             inner,
             counter: unsafe { inner.as_ref() }.counter,
+            slot,
+            capacity,
+            identity,
+        }
+    }
+
+    /// The memory of the save state, including its header. Fails if the size
+    /// written by the core is larger than the slot.
+    fn data(&self) -> Result<&[u8], SaveStateError> {
+        let words = unsafe { core::ptr::read_volatile(core::ptr::addr_of!(self.inner().size)) };
+        let size = (words as usize + 2).saturating_mul(4);
+        if size > self.capacity {
+            return Err(SaveStateError::SizeMismatch {
+                size,
+                capacity: self.capacity,
+            });
         }
+        Ok(self.inner().all())
     }
 
     fn inner(&self) -> &SaveStateInner {
@@ -157,3 +302,119 @@ impl SaveState {
         self.inner().counter()
     }
 }
+
+#[cfg(test)]
+fn test_manager(nb_slots: u32) -> SaveStateManager<cyclone_v::memory::BufferMemoryMapper> {
+    use std::str::FromStr;
+
+    let config = Config::from_str("SNES;SS3E000000:1000;O1,Option,Off,On;V,v1").unwrap();
+    let memory = cyclone_v::memory::BufferMemoryMapper::new(0x1000 * nb_slots as usize);
+    SaveStateManager::from_memory(
+        memory,
+        0x1000,
+        nb_slots,
+        SaveStateIdentity::from_config(&config),
+    )
+}
+
+#[test]
+fn savestate_save_load() {
+    use one_fpga::core::SaveState as _;
+
+    let mut manager = test_manager(2);
+    manager.set_rom_hash(Some(0x1234));
+    let [first, second] = manager.slots_mut() else {
+        unreachable!()
+    };
+
+    // The core writes a state of 16 words in the first slot.
+    let state = unsafe { slice::from_raw_parts_mut(first.inner.as_ptr() as *mut u8, 0x1000) };
+    state[..4].copy_from_slice(&1u32.to_le_bytes());
+    state[4..8].copy_from_slice(&16u32.to_le_bytes());
+    state[8..72].fill(0xAB);
+    assert!(first.is_dirty());
+
+    let mut buffer = Vec::new();
+    let screenshot = DynamicImage::new_rgb8(640, 480);
+    first
+        .save_with_screenshot(&mut buffer, Some(&screenshot))
+        .unwrap();
+    assert!(!first.is_dirty());
+
+    let container = SaveStateContainer::read(&mut buffer.as_slice()).unwrap();
+    assert_eq!(container.slot, 0);
+    assert_eq!(container.identity.core_name, "SNES");
+    assert_eq!(container.identity.rom_hash, Some(0x1234));
+    assert_eq!(container.payload.len(), 72);
+    let thumbnail = image::load_from_memory(container.thumbnail.as_ref().unwrap()).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), THUMBNAIL_SIZE);
+
+    second.load(&mut buffer.as_slice()).unwrap();
+    assert_eq!(&second.inner().all()[8..], &[0xAB; 64]);
+    assert_eq!(second.live_counter(), 0xFFFFFFFF);
+    assert!(!second.is_dirty());
+
+    // A state of another game is rejected, and the slot is not modified.
+    second.identity.rom_hash = Some(0x5678);
+    assert!(matches!(
+        second.load(&mut buffer.as_slice()),
+        Err(Error::IncompatibleSaveState(_))
+    ));
+    // So is a raw memory dump whose size doesn't match its header.
+    assert!(matches!(
+        second.load(&mut [0u8; 72].as_slice()),
+        Err(Error::IncompatibleSaveState(_))
+    ));
+}
+
+#[test]
+fn savestate_legacy() {
+    use one_fpga::core::SaveState as _;
+
+    let mut manager = test_manager(1);
+    let [slot] = manager.slots_mut() else {
+        unreachable!()
+    };
+
+    // A MiSTer save state is the memory of the slot, without a header.
+    let mut dump = Vec::new();
+    dump.extend_from_slice(&3u32.to_le_bytes());
+    dump.extend_from_slice(&16u32.to_le_bytes());
+    dump.extend_from_slice(&[0xCD; 64]);
+    slot.load(&mut dump.as_slice()).unwrap();
+    assert_eq!(&slot.inner().all()[8..], &[0xCD; 64]);
+    assert!(!slot.is_dirty());
+
+    // A dump larger than the slot is rejected.
+    let mut large = dump[..4].to_vec();
+    large.extend_from_slice(&0x1000u32.to_le_bytes());
+    large.resize(0x4008, 0);
+    assert!(matches!(
+        slot.load(&mut large.as_slice()),
+        Err(Error::IncompatibleSaveState(_))
+    ));
+}
+
+#[test]
+fn savestate_slots() {
+    use std::str::FromStr;
+
+    let manager = test_manager(6);
+    assert_eq!(manager.nb_slots(), 6);
+    assert_eq!(manager.slots()[5].slot, 5);
+
+    // Only 7 slots of 64 MiB fit in the host memory from 0x20000000.
+    let config = Config::from_str("SNES;SS20000000:4000000;V,v1").unwrap();
+    assert_eq!(
+        SaveStateManager::<DevMemMemoryMapper>::max_slots(&config),
+        Some(7)
+    );
+    assert!(matches!(
+        SaveStateManager::with_slots(&config, 8),
+        Err(SaveStateError::TooManySlots { slots: 8, max: 7 })
+    ));
+    assert!(matches!(
+        SaveStateManager::with_slots(&config, 0),
+        Err(SaveStateError::TooManySlots { .. })
+    ));
+}
@@ -0,0 +1,304 @@
+//! The file format of save states. The memory of the save state is compressed
+//! and stored after a header that identifies the core and game it was made
+//! from, so it is not loaded in a core that would not understand it.
+//!
+//! All numbers are little endian:
+//!
+//! | Size     | Content                                                  |
+//! |----------|----------------------------------------------------------|
+//! | 8        | Magic, `1FPGASS\0`                                       |
+//! | 2        | Version of the format ([`VERSION`])                      |
+//! | 2        | Flags (bit 0: has a ROM hash)                            |
+//! | 4        | Slot the state was saved from                            |
+//! | 8        | Time of the save, in seconds since the Unix epoch        |
+//! | 4        | CRC32 of the config string of the core                   |
+//! | 4        | CRC32 of the ROM (0 if none)                             |
+//! | 4        | Size of the memory of the save state                     |
+//! | 2 + n    | Name of the core (UTF-8)                                 |
+//! | 4 + n    | Thumbnail (PNG), empty if none                           |
+//! | 4 + n    | Memory of the save state, compressed with zstd           |
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+use crate::config_string::Config;
+
+/// The first bytes of a save state file.
+pub const MAGIC: [u8; 8] = *b"1FPGASS\0";
+
+/// The version of the save state format.
+pub const VERSION: u16 = 1;
+
+const FLAG_ROM_HASH: u16 = 1;
+
+/// The maximum size of the core name and thumbnail, to reject corrupted files
+/// before allocating.
+const MAX_NAME_SIZE: usize = 1024;
+const MAX_THUMBNAIL_SIZE: usize = 16 * 1024 * 1024;
+
+/// The maximum size of the memory of a save state (the limit of `SS` in the
+/// config string).
+const MAX_PAYLOAD_SIZE: usize = 128 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveStateError {
+    #[error("Not a save state file")]
+    InvalidMagic,
+
+    #[error("Unsupported save state version {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("Invalid save state: {0}")]
+    Invalid(String),
+
+    #[error("Save state is for core {found:?}, not {expected:?}")]
+    CoreMismatch { expected: String, found: String },
+
+    #[error("Save state was made with a different version of the core")]
+    ConfigMismatch { expected: u32, found: u32 },
+
+    #[error("Save state is for a different game")]
+    RomMismatch {
+        expected: Option<u32>,
+        found: Option<u32>,
+    },
+
+    #[error("Save state of {size} bytes does not fit in a slot of {capacity} bytes")]
+    SizeMismatch { size: usize, capacity: usize },
+
+    #[error("{slots} save state slots do not fit in memory (maximum {max})")]
+    TooManySlots { slots: u32, max: u32 },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<SaveStateError> for one_fpga::core::Error {
+    fn from(value: SaveStateError) -> Self {
+        match value {
+            SaveStateError::Io(e) => one_fpga::core::Error::IoError(e),
+            SaveStateError::TooManySlots { .. } => {
+                one_fpga::core::Error::InvalidConfig(value.to_string())
+            }
+            e => one_fpga::core::Error::IncompatibleSaveState(e.to_string()),
+        }
+    }
+}
+
+/// What a save state was made from. A save state can only be loaded in the same
+/// core (with the same config string) and game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveStateIdentity {
+    pub core_name: String,
+
+    /// The CRC32 of the config string, which changes with the core version.
+    pub config_hash: u32,
+
+    /// The CRC32 of the ROM loaded, if any.
+    pub rom_hash: Option<u32>,
+}
+
+impl SaveStateIdentity {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            core_name: config.name.clone(),
+            config_hash: crc32fast::hash(config.to_string().as_bytes()),
+            rom_hash: None,
+        }
+    }
+
+    /// Check that a save state made from `other` can be loaded.
+    pub fn check(&self, other: &SaveStateIdentity) -> Result<(), SaveStateError> {
+        if self.core_name != other.core_name {
+            return Err(SaveStateError::CoreMismatch {
+                expected: self.core_name.clone(),
+                found: other.core_name.clone(),
+            });
+        }
+        if self.config_hash != other.config_hash {
+            return Err(SaveStateError::ConfigMismatch {
+                expected: self.config_hash,
+                found: other.config_hash,
+            });
+        }
+        if self.rom_hash != other.rom_hash {
+            return Err(SaveStateError::RomMismatch {
+                expected: self.rom_hash,
+                found: other.rom_hash,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A save state file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveStateContainer {
+    pub identity: SaveStateIdentity,
+    pub slot: u32,
+    pub timestamp: SystemTime,
+
+    /// A PNG image of the screen when the state was saved.
+    pub thumbnail: Option<Vec<u8>>,
+
+    /// The memory of the save state.
+    pub payload: Vec<u8>,
+}
+
+fn read_array<const N: usize>(reader: &mut dyn Read) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Read a block prefixed by its `u32` size.
+fn read_block(reader: &mut dyn Read, max: usize) -> Result<Vec<u8>, SaveStateError> {
+    let size = u32::from_le_bytes(read_array(reader)?) as usize;
+    if size > max {
+        return Err(SaveStateError::Invalid(format!("Block of {size} bytes")));
+    }
+    let mut block = vec![0; size];
+    reader.read_exact(&mut block)?;
+    Ok(block)
+}
+
+impl SaveStateContainer {
+    pub fn write(&self, writer: &mut dyn Write) -> Result<(), SaveStateError> {
+        let name = self.identity.core_name.as_bytes();
+        if name.len() > MAX_NAME_SIZE {
+            return Err(SaveStateError::Invalid("Core name too long".to_string()));
+        }
+        let timestamp = self
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let flags = if self.identity.rom_hash.is_some() {
+            FLAG_ROM_HASH
+        } else {
+            0
+        };
+        let thumbnail = self.thumbnail.as_deref().unwrap_or_default();
+        let compressed =
+            zstd::encode_all(self.payload.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&flags.to_le_bytes())?;
+        writer.write_all(&self.slot.to_le_bytes())?;
+        writer.write_all(&timestamp.to_le_bytes())?;
+        writer.write_all(&self.identity.config_hash.to_le_bytes())?;
+        writer.write_all(&self.identity.rom_hash.unwrap_or(0).to_le_bytes())?;
+        writer.write_all(&(self.payload.len() as u32).to_le_bytes())?;
+        writer.write_all(&(name.len() as u16).to_le_bytes())?;
+        writer.write_all(name)?;
+        writer.write_all(&(thumbnail.len() as u32).to_le_bytes())?;
+        writer.write_all(thumbnail)?;
+        writer.write_all(&(compressed.len() as u32).to_le_bytes())?;
+        writer.write_all(&compressed)?;
+        Ok(())
+    }
+
+    pub fn read(reader: &mut dyn Read) -> Result<Self, SaveStateError> {
+        if read_array(reader)? != MAGIC {
+            return Err(SaveStateError::InvalidMagic);
+        }
+        let version = u16::from_le_bytes(read_array(reader)?);
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        let flags = u16::from_le_bytes(read_array(reader)?);
+        let slot = u32::from_le_bytes(read_array(reader)?);
+        let timestamp = u64::from_le_bytes(read_array(reader)?);
+        let config_hash = u32::from_le_bytes(read_array(reader)?);
+        let rom_hash = u32::from_le_bytes(read_array(reader)?);
+        let size = u32::from_le_bytes(read_array(reader)?) as usize;
+        if size > MAX_PAYLOAD_SIZE {
+            return Err(SaveStateError::Invalid(format!(
+                "Save state of {size} bytes"
+            )));
+        }
+
+        let name_size = u16::from_le_bytes(read_array(reader)?) as usize;
+        if name_size > MAX_NAME_SIZE {
+            return Err(SaveStateError::Invalid("Core name too long".to_string()));
+        }
+        let mut name = vec![0; name_size];
+        reader.read_exact(&mut name)?;
+        let core_name = String::from_utf8(name)
+            .map_err(|_| SaveStateError::Invalid("Core name is not UTF-8".to_string()))?;
+
+        let thumbnail = read_block(reader, MAX_THUMBNAIL_SIZE)?;
+        let compressed = read_block(reader, MAX_PAYLOAD_SIZE)?;
+        let payload = zstd::decode_all(compressed.as_slice())?;
+        if payload.len() != size {
+            return Err(SaveStateError::Invalid(format!(
+                "Expected {size} bytes, decompressed {}",
+                payload.len()
+            )));
+        }
+
+        Ok(Self {
+            identity: SaveStateIdentity {
+                core_name,
+                config_hash,
+                rom_hash: (flags & FLAG_ROM_HASH != 0).then_some(rom_hash),
+            },
+            slot,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp),
+            thumbnail: (!thumbnail.is_empty()).then_some(thumbnail),
+            payload,
+        })
+    }
+}
+
+#[test]
+fn container() {
+    let identity = SaveStateIdentity {
+        core_name: "SNES".to_string(),
+        config_hash: 0x1234_5678,
+        rom_hash: Some(0xCAFE_BABE),
+    };
+    let container = SaveStateContainer {
+        identity: identity.clone(),
+        slot: 2,
+        timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        thumbnail: Some(vec![0x89, b'P', b'N', b'G']),
+        payload: (0..10_000u32).map(|i| (i / 100) as u8).collect(),
+    };
+
+    let mut bytes = Vec::new();
+    container.write(&mut bytes).unwrap();
+    assert_eq!(&bytes[..8], b"1FPGASS\0");
+    let read = SaveStateContainer::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read, container);
+    identity.check(&read.identity).unwrap();
+
+    let other_game = SaveStateIdentity {
+        rom_hash: None,
+        ..identity.clone()
+    };
+    assert!(matches!(
+        other_game.check(&read.identity),
+        Err(SaveStateError::RomMismatch { expected: None, .. })
+    ));
+    let other_core = SaveStateIdentity {
+        core_name: "NES".to_string(),
+        ..identity
+    };
+    assert!(matches!(
+        other_core.check(&read.identity),
+        Err(SaveStateError::CoreMismatch { .. })
+    ));
+
+    // Raw memory dumps (without a header) and newer versions are rejected.
+    assert!(matches!(
+        SaveStateContainer::read(&mut [0u8; 64].as_slice()),
+        Err(SaveStateError::InvalidMagic)
+    ));
+    bytes[8] = 2;
+    assert!(matches!(
+        SaveStateContainer::read(&mut bytes.as_slice()),
+        Err(SaveStateError::UnsupportedVersion(2))
+    ));
+}
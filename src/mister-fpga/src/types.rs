@@ -80,6 +80,43 @@ impl StatusBitMap {
         self
     }
 
+    /// Create a bit map from little-endian bytes, as stored in MiSTer's
+    /// `config/<CORE>.CFG` files. Shorter inputs leave the upper bits cleared,
+    /// and bytes past the 128th bit are ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut result = Self::new();
+        for (word, chunk) in result.as_mut_raw_slice().iter_mut().zip(bytes.chunks(2)) {
+            *word = chunk
+                .iter()
+                .rev()
+                .fold(0, |acc, byte| (acc << 8) | *byte as u16);
+        }
+        result
+    }
+
+    /// Return the bit map as little-endian bytes, compatible with MiSTer's
+    /// `config/<CORE>.CFG` files.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut result = [0; 16];
+        for (chunk, word) in result.chunks_mut(2).zip(self.as_raw_slice()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        result
+    }
+
+    /// Return a copy of this bit map with only the bits set in `mask`.
+    pub fn masked(&self, mask: &StatusBitMap) -> Self {
+        let mut result = *self;
+        for (word, mask) in result
+            .as_mut_raw_slice()
+            .iter_mut()
+            .zip(mask.as_raw_slice())
+        {
+            *word &= mask;
+        }
+        result
+    }
+
     pub fn as_raw_slice(&self) -> &[u16] {
         self.0.as_raw_slice()
     }
@@ -95,8 +132,9 @@ impl StatusBitMap {
         self.0.set(idx, value);
     }
 
+    /// Return the bit at `idx`. Bits past the end of the map are always cleared.
     pub fn get(&self, idx: usize) -> bool {
-        self.0[idx]
+        self.0.get(idx).is_some_and(|b| *b)
     }
 
     pub fn len(&self) -> usize {
@@ -164,18 +202,19 @@ impl StatusBitMap {
         ));
 
         if raw[4] != 0 || raw[5] != 0 || raw[6] != 0 || raw[7] != 0 {
+            result.push('\n');
             if header {
-                result += "\n\
+                result += "\
                     0     0         0         0          1         1         1       \n\
                     6     7         8         9          0         1         2       \n\
                     45678901234567890123456789012345 67890123456789012345678901234567\n\
                     ";
             }
             result.push_str(&output_u64(
-                ((raw[0] as u64) << 48)
-                    | ((raw[1] as u64) << 32)
-                    | ((raw[2] as u64) << 16)
-                    | (raw[3] as u64),
+                (raw[4] as u64)
+                    | ((raw[5] as u64) << 16)
+                    | ((raw[6] as u64) << 32)
+                    | ((raw[7] as u64) << 48),
             ));
         }
 
@@ -221,3 +260,22 @@ fn status_bits() {
     assert_eq!(status_bits.get_range(32..34), 3);
     assert_eq!(status_bits.get_range(64..67), 3);
 }
+
+#[test]
+fn status_bits_bytes() {
+    let status_bits = StatusBitMap::new()
+        .with(1)
+        .with_range(8..12, 0b1010)
+        .with(33)
+        .with(127);
+    let bytes = status_bits.to_bytes();
+    assert_eq!(&bytes[..5], &[0b0000_0010, 0b0000_1010, 0, 0, 0b0000_0010]);
+    assert_eq!(bytes[15], 0b1000_0000);
+    assert_eq!(StatusBitMap::from_bytes(&bytes), status_bits);
+
+    // Short (and odd sized) inputs only set the lower bits.
+    let short = StatusBitMap::from_bytes(&[0xFF, 0x01, 0x03]);
+    assert_eq!(short.get_range(0..10), 0x1FF);
+    assert_eq!(short.get_range(16..18), 0b11);
+    assert!(!short.get(18));
+}
@@ -0,0 +1,657 @@
+//! Bridging of the core's UART to Linux. Cores with `UART` or `MIDI` in the
+//! settings of their config string have their serial port connected to the HPS
+//! UART. The user selects a mode and a speed, which are sent to the core, and the
+//! HPS UART is bridged to an endpoint on the Linux side (a serial device, a
+//! pseudo-terminal, a TCP connection or an ALSA sequencer port for MIDI).
+use std::ffi::CStr;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use alsa::seq::{Addr, MidiEvent, PortCap, PortSubscribe, PortType, Seq};
+use alsa::Direction;
+use tracing::{debug, trace, warn};
+
+use one_fpga::core::{CoreSettingItem, Error, SettingId};
+
+use crate::config_string::settings::Settings;
+
+/// The HPS UART connected to the core.
+pub const HPS_UART_PATH: &str = "/dev/ttyS1";
+
+/// How long the bridge waits for data before checking if it should stop.
+const POLL_TIMEOUT_MS: i32 = 100;
+
+/// The names of the ALSA sequencer client and port created for MIDI.
+const SEQ_CLIENT_NAME: &CStr = c"1FPGA";
+const SEQ_PORT_NAME: &CStr = c"MIDI";
+
+/// The UART modes, as sent to the core (`uart_mode` in `hps_io`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum UartMode {
+    #[default]
+    None = 0,
+    Ppp = 1,
+    Console = 2,
+    Midi = 3,
+    Modem = 4,
+}
+
+impl UartMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            UartMode::None => "None",
+            UartMode::Ppp => "PPP",
+            UartMode::Console => "Console",
+            UartMode::Midi => "MIDI",
+            UartMode::Modem => "Modem",
+        }
+    }
+
+    /// The modes supported by a core, or an empty list if the core does not
+    /// have a UART.
+    pub fn available(settings: &Settings) -> Vec<UartMode> {
+        let mut modes = Vec::new();
+        if !settings.uart_mode.is_empty() {
+            modes.extend([UartMode::Ppp, UartMode::Console, UartMode::Modem]);
+        }
+        if !settings.midi_mode.is_empty() {
+            modes.push(UartMode::Midi);
+        }
+        if !modes.is_empty() {
+            modes.insert(0, UartMode::None);
+        }
+        modes
+    }
+
+    /// The speeds supported by a core for this mode, with their labels.
+    pub fn speeds(&self, settings: &Settings) -> Vec<(u32, String)> {
+        match self {
+            UartMode::None => Vec::new(),
+            UartMode::Midi => settings
+                .midi_mode
+                .iter()
+                .map(|s| (s.speed, s.label.clone()))
+                .collect(),
+            _ => settings
+                .uart_mode
+                .iter()
+                .map(|s| (s.speed, s.label.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Where the HPS UART is bridged to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UartEndpoint {
+    /// A serial device, e.g. a USB serial adapter (`/dev/ttyUSB0`).
+    Tty(PathBuf),
+
+    /// A new pseudo-terminal, for programs like `pppd`, a modem emulator or a
+    /// terminal emulator. Its path is available from [`UartBridge::pty_path`].
+    Pty,
+
+    /// A TCP connection to an address (`host:port`), e.g. a telnet BBS.
+    Tcp(String),
+
+    /// A new ALSA sequencer port, which programs (e.g. a synthesizer) can
+    /// connect to. If an address (`client:port`) is given, the port is also
+    /// connected to it.
+    Midi(Option<String>),
+}
+
+impl UartEndpoint {
+    /// The default endpoint for a mode, or `None` if the UART should not be
+    /// bridged.
+    pub fn for_mode(mode: UartMode) -> Option<Self> {
+        match mode {
+            UartMode::None => None,
+            UartMode::Midi => Some(UartEndpoint::Midi(None)),
+            UartMode::Ppp | UartMode::Console | UartMode::Modem => Some(UartEndpoint::Pty),
+        }
+    }
+}
+
+/// Parse an endpoint from `pty`, `tty:{path}`, `tcp:{host}:{port}` or
+/// `midi[:{client}:{port}]`.
+impl FromStr for UartEndpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+        match (kind, arg) {
+            ("pty", "") => Ok(UartEndpoint::Pty),
+            ("tty", path) if !path.is_empty() => Ok(UartEndpoint::Tty(PathBuf::from(path))),
+            ("tcp", address) if !address.is_empty() => Ok(UartEndpoint::Tcp(address.to_string())),
+            ("midi", "") => Ok(UartEndpoint::Midi(None)),
+            ("midi", address) if address.parse::<Addr>().is_ok() => {
+                Ok(UartEndpoint::Midi(Some(address.to_string())))
+            }
+            _ => Err(Error::InvalidConfig(format!("Invalid UART endpoint {s:?}"))),
+        }
+    }
+}
+
+impl fmt::Display for UartEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UartEndpoint::Tty(path) => write!(f, "tty:{}", path.display()),
+            UartEndpoint::Pty => write!(f, "pty"),
+            UartEndpoint::Tcp(address) => write!(f, "tcp:{address}"),
+            UartEndpoint::Midi(None) => write!(f, "midi"),
+            UartEndpoint::Midi(Some(address)) => write!(f, "midi:{address}"),
+        }
+    }
+}
+
+fn last_os_error() -> Error {
+    Error::IoError(std::io::Error::last_os_error())
+}
+
+/// Set a terminal in raw mode, at any speed (including non-standard ones like
+/// MIDI's 31250 bauds).
+fn configure_tty(fd: RawFd, speed: Option<u32>) -> Result<(), Error> {
+    // SAFETY: `fd` is a valid file descriptor, and `termios` is fully written by
+    //         `tcgetattr` before being read.
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(last_os_error());
+        }
+        libc::cfmakeraw(&mut termios);
+        termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(last_os_error());
+        }
+
+        if let Some(speed) = speed {
+            let mut termios2: libc::termios2 = std::mem::zeroed();
+            if libc::ioctl(fd, libc::TCGETS2, &mut termios2) != 0 {
+                return Err(last_os_error());
+            }
+            termios2.c_cflag &= !libc::CBAUD;
+            termios2.c_cflag |= libc::BOTHER;
+            termios2.c_ispeed = speed;
+            termios2.c_ospeed = speed;
+            if libc::ioctl(fd, libc::TCSETS2, &termios2) != 0 {
+                return Err(last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Open a device for reading and writing, without making it the controlling
+/// terminal.
+fn open_device(path: &Path) -> Result<File, Error> {
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(path)?)
+}
+
+/// Create a pseudo-terminal in raw mode. Returns the master side and the path of
+/// the slave side.
+fn open_pty() -> Result<(File, PathBuf), Error> {
+    // SAFETY: the file descriptor returned by `posix_openpt` is owned by the
+    //         returned `File`, and `ptsname_r` writes a NUL-terminated string
+    //         within the buffer.
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK);
+        if fd < 0 {
+            return Err(last_os_error());
+        }
+        let master = <File as std::os::fd::FromRawFd>::from_raw_fd(fd);
+        if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+            return Err(last_os_error());
+        }
+
+        let mut name = [0 as libc::c_char; 128];
+        if libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) != 0 {
+            return Err(last_os_error());
+        }
+        let name = std::ffi::CStr::from_ptr(name.as_ptr());
+        Ok((master, PathBuf::from(name.to_string_lossy().into_owned())))
+    }
+}
+
+/// A stream that can be bridged.
+trait Stream: Read + Write + AsRawFd + Send {}
+
+impl<T: Read + Write + AsRawFd + Send> Stream for T {}
+
+fn alsa_error(error: alsa::Error) -> std::io::Error {
+    std::io::Error::from_raw_os_error(error.errno())
+}
+
+/// An ALSA sequencer port. The MIDI bytes written are encoded to sequencer
+/// events sent to the subscribers of the port, and the events received are
+/// decoded back to MIDI bytes.
+struct SeqStream {
+    seq: Seq,
+    port: i32,
+    coder: MidiEvent,
+    fd: RawFd,
+}
+
+// SAFETY: the MIDI coder is owned by the stream, and only used by the thread
+//         the stream is moved to.
+unsafe impl Send for SeqStream {}
+
+impl SeqStream {
+    fn open(address: Option<&str>) -> Result<Self, Error> {
+        let open = || -> alsa::Result<(Seq, i32)> {
+            let seq = Seq::open(None, None, true)?;
+            seq.set_client_name(SEQ_CLIENT_NAME)?;
+            let port = seq.create_simple_port(
+                SEQ_PORT_NAME,
+                PortCap::READ | PortCap::WRITE | PortCap::SUBS_READ | PortCap::SUBS_WRITE,
+                PortType::MIDI_GENERIC | PortType::APPLICATION,
+            )?;
+            Ok((seq, port))
+        };
+        let (seq, port) = open().map_err(alsa_error)?;
+
+        if let Some(address) = address {
+            let other = address
+                .parse::<Addr>()
+                .map_err(|e| Error::InvalidConfig(e.to_string()))?;
+            let this = Addr {
+                client: seq.client_id().map_err(alsa_error)?,
+                port,
+            };
+            for (sender, dest) in [(this, other), (other, this)] {
+                let subscription = PortSubscribe::empty().map_err(alsa_error)?;
+                subscription.set_sender(sender);
+                subscription.set_dest(dest);
+                seq.subscribe_port(&subscription).map_err(alsa_error)?;
+            }
+        }
+
+        let fds =
+            alsa::poll::Descriptors::get(&(&seq, Some(Direction::Capture))).map_err(alsa_error)?;
+        let fd = fds
+            .first()
+            .ok_or_else(|| Error::IoError(std::io::Error::other("No sequencer descriptor")))?
+            .fd;
+        let coder = MidiEvent::new(256).map_err(alsa_error)?;
+        coder.enable_running_status(false);
+
+        Ok(Self {
+            seq,
+            port,
+            coder,
+            fd,
+        })
+    }
+}
+
+impl Read for SeqStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut input = self.seq.input();
+        loop {
+            let mut event = input.event_input().map_err(alsa_error)?;
+            match self.coder.decode(buf, &mut event) {
+                Ok(size) if size > 0 => return Ok(size),
+                // Events that are not MIDI messages (e.g. port subscriptions).
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Write for SeqStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut data = buf;
+        while !data.is_empty() {
+            let (size, event) = self.coder.encode(data).map_err(alsa_error)?;
+            if let Some(mut event) = event {
+                event.set_source(self.port);
+                event.set_subs();
+                event.set_direct();
+                self.seq
+                    .event_output_direct(&mut event)
+                    .map_err(alsa_error)?;
+            }
+            if size == 0 {
+                break;
+            }
+            data = &data[size..];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for SeqStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+/// Forward the data available on `from` to `to`. Returns false if `from` was
+/// closed.
+fn forward(from: &mut dyn Stream, to: &mut dyn Stream, buffer: &mut [u8]) -> bool {
+    let size = match from.read(buffer) {
+        Ok(0) => return false,
+        Ok(size) => size,
+        // A pseudo-terminal returns EIO while nothing is connected to it.
+        Err(e) if e.kind() == ErrorKind::WouldBlock || e.raw_os_error() == Some(libc::EIO) => {
+            return true
+        }
+        Err(e) => {
+            warn!(?e, "Error reading from UART bridge");
+            return false;
+        }
+    };
+
+    let mut data = &buffer[..size];
+    while !data.is_empty() {
+        match to.write(data) {
+            Ok(written) => data = &data[written..],
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                trace!(size = data.len(), "UART bridge is full, dropping data");
+                break;
+            }
+            Err(e) => {
+                warn!(?e, "Error writing to UART bridge");
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn run_bridge(mut device: File, mut endpoint: Box<dyn Stream>, stop: Arc<AtomicBool>) {
+    let mut buffer = [0u8; 1024];
+    while !stop.load(Ordering::Relaxed) {
+        let mut fds = [
+            libc::pollfd {
+                fd: device.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: endpoint.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+
+        // SAFETY: `fds` is a valid array of `pollfd` of the length given.
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, POLL_TIMEOUT_MS) };
+        if ready < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            warn!(?error, "UART bridge stopped");
+            return;
+        }
+
+        let ok = (fds[0].revents & libc::POLLIN == 0
+            || forward(&mut device, endpoint.as_mut(), &mut buffer))
+            && (fds[1].revents & libc::POLLIN == 0
+                || forward(endpoint.as_mut(), &mut device, &mut buffer));
+        if !ok || (fds[1].revents & (libc::POLLERR | libc::POLLNVAL)) != 0 {
+            debug!("UART bridge endpoint closed");
+            return;
+        }
+    }
+}
+
+/// A running bridge between the HPS UART and an endpoint. The bridge is stopped
+/// when dropped.
+#[derive(Debug)]
+pub struct UartBridge {
+    endpoint: UartEndpoint,
+    pty_path: Option<PathBuf>,
+
+    // The slave side of the pseudo-terminal is kept open so the master side
+    // does not hang up when programs disconnect.
+    _pty_slave: Option<File>,
+
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UartBridge {
+    /// Configure the UART `device` at `speed` and start bridging it to `endpoint`.
+    pub fn start(
+        device: impl AsRef<Path>,
+        speed: u32,
+        endpoint: UartEndpoint,
+    ) -> Result<Self, Error> {
+        let device = open_device(device.as_ref())?;
+        configure_tty(device.as_raw_fd(), Some(speed))?;
+
+        let mut pty_path = None;
+        let mut pty_slave = None;
+        let stream: Box<dyn Stream> = match &endpoint {
+            UartEndpoint::Tty(path) => {
+                let tty = open_device(path)?;
+                configure_tty(tty.as_raw_fd(), Some(speed))?;
+                Box::new(tty)
+            }
+            UartEndpoint::Pty => {
+                let (master, path) = open_pty()?;
+                let slave = open_device(&path)?;
+                configure_tty(slave.as_raw_fd(), None)?;
+                pty_slave = Some(slave);
+                pty_path = Some(path);
+                Box::new(master)
+            }
+            UartEndpoint::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_nonblocking(true)?;
+                stream.set_nodelay(true)?;
+                Box::new(stream)
+            }
+            UartEndpoint::Midi(address) => Box::new(SeqStream::open(address.as_deref())?),
+        };
+
+        debug!(?endpoint, ?pty_path, speed, "Starting UART bridge");
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::Builder::new()
+            .name("uart-bridge".to_string())
+            .spawn({
+                let stop = stop.clone();
+                move || run_bridge(device, stream, stop)
+            })?;
+
+        Ok(Self {
+            endpoint,
+            pty_path,
+            _pty_slave: pty_slave,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn endpoint(&self) -> &UartEndpoint {
+        &self.endpoint
+    }
+
+    /// The path of the pseudo-terminal programs can connect to, if the endpoint
+    /// is [`UartEndpoint::Pty`].
+    pub fn pty_path(&self) -> Option<&Path> {
+        self.pty_path.as_deref()
+    }
+}
+
+impl Drop for UartBridge {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The UART mode and speed selected for a core.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UartSettings {
+    pub mode: UartMode,
+    pub speed: u32,
+}
+
+impl UartSettings {
+    pub fn mode_setting_id() -> SettingId {
+        SettingId::from_label("uart:mode")
+    }
+
+    pub fn speed_setting_id() -> SettingId {
+        SettingId::from_label("uart:speed")
+    }
+
+    /// Return the settings with the mode at `index` in the modes available, and
+    /// its first speed. Returns `None` if the index is out of range.
+    pub fn with_mode_index(&self, settings: &Settings, index: usize) -> Option<Self> {
+        let mode = *UartMode::available(settings).get(index)?;
+        let speed = mode.speeds(settings).first().map_or(0, |(s, _)| *s);
+        Some(Self { mode, speed })
+    }
+
+    /// Return the settings with the speed at `index` in the speeds of the current
+    /// mode. Returns `None` if the index is out of range.
+    pub fn with_speed_index(&self, settings: &Settings, index: usize) -> Option<Self> {
+        let (speed, _) = self.mode.speeds(settings).get(index)?.clone();
+        Some(Self { speed, ..*self })
+    }
+
+    /// Return a page with the UART options of the core, or `None` if the core
+    /// does not have a UART.
+    pub fn as_core_menu_item(&self, settings: &Settings) -> Option<CoreSettingItem> {
+        let modes = UartMode::available(settings);
+        if modes.is_empty() {
+            return None;
+        }
+
+        let mut items = vec![CoreSettingItem::int_option(
+            Self::mode_setting_id(),
+            "UART Mode",
+            modes.iter().map(|m| m.label().to_string()).collect(),
+            modes.iter().position(|m| *m == self.mode),
+        )];
+
+        let speeds = self.mode.speeds(settings);
+        if speeds.len() > 1 {
+            items.push(CoreSettingItem::int_option(
+                Self::speed_setting_id(),
+                "UART Speed",
+                speeds.iter().map(|(_, label)| label.clone()).collect(),
+                speeds.iter().position(|(s, _)| *s == self.speed),
+            ));
+        }
+
+        Some(CoreSettingItem::page("uart", "UART", "UART", items))
+    }
+}
+
+#[test]
+fn uart_settings() {
+    use std::str::FromStr;
+
+    let settings = Settings::from_str("UART31250:115200(Fast),MIDI").unwrap();
+    assert_eq!(
+        UartMode::available(&settings),
+        [
+            UartMode::None,
+            UartMode::Ppp,
+            UartMode::Console,
+            UartMode::Modem,
+            UartMode::Midi
+        ]
+    );
+    assert!(UartMode::available(&Settings::default()).is_empty());
+
+    let uart = UartSettings::default()
+        .with_mode_index(&settings, 2)
+        .unwrap();
+    assert_eq!(uart.mode, UartMode::Console);
+    assert_eq!(uart.speed, 31250);
+    let uart = uart.with_speed_index(&settings, 1).unwrap();
+    assert_eq!(uart.speed, 115200);
+    assert_eq!(uart.with_speed_index(&settings, 2), None);
+
+    let page = uart.as_core_menu_item(&settings).unwrap();
+    let items = page.items().unwrap();
+    assert!(matches!(
+        &items[0],
+        CoreSettingItem::IntOption { value: 2, .. }
+    ));
+    assert!(matches!(
+        &items[1],
+        CoreSettingItem::IntOption { value: 1, choices, .. } if choices[1] == "Fast"
+    ));
+}
+
+#[test]
+fn uart_endpoint_parse() {
+    for endpoint in [
+        UartEndpoint::Pty,
+        UartEndpoint::Tty(PathBuf::from("/dev/ttyUSB0")),
+        UartEndpoint::Tcp("bbs.example.com:23".to_string()),
+        UartEndpoint::Midi(None),
+        UartEndpoint::Midi(Some("128:0".to_string())),
+    ] {
+        assert_eq!(
+            endpoint.to_string().parse::<UartEndpoint>().unwrap(),
+            endpoint
+        );
+    }
+    assert!("tcp".parse::<UartEndpoint>().is_err());
+    assert!("midi:synth".parse::<UartEndpoint>().is_err());
+    assert!("serial".parse::<UartEndpoint>().is_err());
+}
+
+#[test]
+fn uart_bridge_pty_loopback() {
+    use std::time::{Duration, Instant};
+
+    fn read_until(file: &mut File, expected: &[u8]) -> Vec<u8> {
+        let start = Instant::now();
+        let mut result = Vec::new();
+        let mut buffer = [0u8; 64];
+        while result.len() < expected.len() && start.elapsed() < Duration::from_secs(5) {
+            match file.read(&mut buffer) {
+                Ok(size) => result.extend_from_slice(&buffer[..size]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                Err(e) => panic!("{e}"),
+            }
+        }
+        result
+    }
+
+    // A pseudo-terminal stands in for the HPS UART, with the test on the core side.
+    let (mut core, device) = open_pty().unwrap();
+    let bridge = UartBridge::start(&device, 31250, UartEndpoint::Pty).unwrap();
+    let mut host = open_device(bridge.pty_path().unwrap()).unwrap();
+
+    core.write_all(b"ATDT5551234\r").unwrap();
+    assert_eq!(read_until(&mut host, b"ATDT5551234\r"), b"ATDT5551234\r");
+
+    host.write_all(b"CONNECT 31250\r\n").unwrap();
+    assert_eq!(
+        read_until(&mut core, b"CONNECT 31250\r\n"),
+        b"CONNECT 31250\r\n"
+    );
+
+    drop(bridge);
+}
@@ -19,3 +19,194 @@ fn load_config_string(#[files("tests/assets/config_string/*")] root: PathBuf) {
         assert_eq!(data, expected);
     }
 }
+
+#[rstest]
+fn render_config_string(#[files("tests/assets/config_string/*")] root: PathBuf) {
+    let config = std::fs::read_to_string(root.join("config")).unwrap();
+    let config = Config::from_str(config.trim_end()).unwrap();
+
+    let rendered = config.to_string();
+    let parsed = Config::from_str(&rendered);
+    assert!(parsed.is_ok(), "{:?}\n{rendered}", parsed);
+    assert_eq!(parsed.unwrap(), config);
+}
+
+mod strategies {
+    use mister_fpga::config_string::midi::MidiSpeed;
+    use mister_fpga::config_string::settings::Settings;
+    use mister_fpga::config_string::uart::UartSpeed;
+    use mister_fpga::config_string::{
+        Config, ConfigMenu, FileExtension, FpgaRamMemoryAddress, LoadFileInfo,
+    };
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    /// Text that can be used as a label or a choice (no separators).
+    pub fn text() -> impl Strategy<Value = String> {
+        "[A-Za-z0-9 ()+&/-]{1,12}"
+    }
+
+    /// Text that can contain commas, for entries where it is the last field.
+    pub fn tail_text() -> impl Strategy<Value = String> {
+        "[A-Za-z0-9 ,()+&/-]{1,16}"
+    }
+
+    fn extensions() -> impl Strategy<Value = Vec<FileExtension>> {
+        prop::collection::vec("[A-Z0-9]{1,3}", 1..4).prop_map(|exts| {
+            exts.iter()
+                .map(|e| FileExtension::from_str(e).unwrap())
+                .collect()
+        })
+    }
+
+    fn address() -> impl Strategy<Value = FpgaRamMemoryAddress> {
+        (0x2000_0000u32..0x3FFF_FFFF).prop_map(|a| FpgaRamMemoryAddress::try_from(a).unwrap())
+    }
+
+    fn load_file_info() -> impl Strategy<Value = LoadFileInfo> {
+        (
+            any::<bool>(),
+            0u8..64,
+            extensions(),
+            prop::option::of(text()),
+            prop::option::of(address()),
+        )
+            .prop_map(
+                |(save_support, index, extensions, label, address)| LoadFileInfo {
+                    save_support,
+                    index,
+                    extensions,
+                    label,
+                    address,
+                },
+            )
+    }
+
+    fn bits() -> impl Strategy<Value = std::ops::Range<u8>> {
+        (0u8..127, 1u8..8).prop_map(|(start, len)| start..(start + len).min(128))
+    }
+
+    fn leaf() -> impl Strategy<Value = ConfigMenu> {
+        prop_oneof![
+            prop::option::of(tail_text()).prop_map(ConfigMenu::Empty),
+            prop::option::of(tail_text()).prop_map(ConfigMenu::Cheat),
+            Just(ConfigMenu::Dip),
+            load_file_info().prop_map(|i| ConfigMenu::LoadFile(Box::new(i))),
+            load_file_info().prop_map(|i| ConfigMenu::LoadFileAndRemember(Box::new(i))),
+            (0u8..16, extensions(), prop::option::of(tail_text())).prop_map(
+                |(slot, extensions, label)| ConfigMenu::MountSdCard {
+                    slot,
+                    extensions,
+                    label,
+                }
+            ),
+            (bits(), text(), prop::collection::vec(text(), 0..6)).prop_map(
+                |(bits, label, choices)| ConfigMenu::Option {
+                    bits,
+                    label,
+                    choices,
+                }
+            ),
+            (any::<bool>(), 0u8..128, tail_text()).prop_map(|(close_osd, index, label)| {
+                ConfigMenu::Trigger {
+                    close_osd,
+                    index,
+                    label,
+                }
+            }),
+            (any::<bool>(), prop::collection::vec(text(), 1..8))
+                .prop_map(|(keyboard, buttons)| ConfigMenu::JoystickButtons { keyboard, buttons }),
+            prop::collection::vec(text(), 1..8)
+                .prop_map(|buttons| ConfigMenu::SnesButtonDefaultList { buttons }),
+            prop::collection::vec(text(), 1..8)
+                .prop_map(|buttons| ConfigMenu::SnesButtonDefaultPositionalList { buttons }),
+            (0u8..10, tail_text()).prop_map(|(index, label)| ConfigMenu::Page { index, label }),
+            prop::collection::vec(text(), 0..8).prop_map(ConfigMenu::Info),
+            tail_text().prop_map(ConfigMenu::Version),
+        ]
+    }
+
+    pub fn config_menu() -> impl Strategy<Value = ConfigMenu> {
+        leaf().prop_recursive(3, 8, 1, |inner| {
+            prop_oneof![
+                (0u32..128, inner.clone()).prop_map(|(b, s)| ConfigMenu::DisableIf(b, Box::new(s))),
+                (0u32..128, inner.clone())
+                    .prop_map(|(b, s)| ConfigMenu::DisableUnless(b, Box::new(s))),
+                (0u32..128, inner.clone()).prop_map(|(b, s)| ConfigMenu::HideIf(b, Box::new(s))),
+                (0u32..128, inner.clone())
+                    .prop_map(|(b, s)| ConfigMenu::HideUnless(b, Box::new(s))),
+                (0u8..10, inner).prop_map(|(p, s)| ConfigMenu::PageItem(p, Box::new(s))),
+            ]
+        })
+    }
+
+    fn speeds() -> impl Strategy<Value = Vec<(u32, Option<String>)>> {
+        let label = "[A-Za-z0-9 +&/-]{1,12}";
+        prop::collection::vec((1u32..10_000_000, prop::option::of(label)), 1..4)
+    }
+
+    fn settings() -> impl Strategy<Value = Settings> {
+        (
+            prop::option::of((address(), 1usize..0x800_0000)),
+            prop::option::of(speeds()),
+            prop::option::of(speeds()),
+        )
+            .prop_map(|(save_state, uart, midi)| {
+                let label =
+                    |speed: u32, label: Option<String>| label.unwrap_or_else(|| speed.to_string());
+                Settings {
+                    save_state,
+                    uart_mode: uart
+                        .into_iter()
+                        .flatten()
+                        .map(|(speed, l)| UartSpeed {
+                            speed,
+                            label: label(speed, l),
+                        })
+                        .collect(),
+                    midi_mode: midi
+                        .into_iter()
+                        .flatten()
+                        .map(|(speed, l)| MidiSpeed {
+                            speed,
+                            label: label(speed, l),
+                        })
+                        .collect(),
+                }
+            })
+    }
+
+    pub fn config() -> impl Strategy<Value = Config> {
+        (
+            "[A-Za-z0-9_]{1,12}",
+            settings(),
+            prop::collection::vec(config_menu(), 0..16),
+        )
+            .prop_map(|(name, settings, menu)| Config {
+                name,
+                settings,
+                menu,
+            })
+    }
+}
+
+proptest::proptest! {
+    #[test]
+    fn config_menu_round_trip(item in strategies::config_menu()) {
+        let config = Config {
+            name: "Test".to_string(),
+            settings: Default::default(),
+            menu: vec![item],
+        };
+        let rendered = config.to_string();
+        let parsed = Config::from_str(&rendered);
+        proptest::prop_assert_eq!(parsed, Ok(config), "{}", rendered);
+    }
+
+    #[test]
+    fn config_round_trip(config in strategies::config()) {
+        let rendered = config.to_string();
+        let parsed = Config::from_str(&rendered);
+        proptest::prop_assert_eq!(parsed, Ok(config), "{}", rendered);
+    }
+}
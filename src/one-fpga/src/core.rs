@@ -1,19 +1,29 @@
 use std::any::Any;
 use std::cell::UnsafeCell;
 use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc;
 use std::time::SystemTime;
 
 pub use bios::Bios;
+pub use capabilities::{CoreCapabilities, FileSlot};
+pub use events::CoreEvent;
+pub use handle::CoreHandle;
 use image::DynamicImage;
 pub use null::NullCore;
+pub use playlist::Playlist;
 pub use rom::Rom;
 use serde::Serialize;
 
-use crate::inputs::{gamepad, keyboard};
+use crate::inputs::{gamepad, keyboard, mouse};
 
 pub mod bios;
+pub mod capabilities;
+pub mod events;
+pub mod handle;
 pub mod null;
+pub mod playlist;
 pub mod rom;
 
 /// An ID that is given by the core implementation for a config menu. This
@@ -86,6 +96,10 @@ impl CoreSettings {
     pub fn items(&self) -> &[CoreSettingItem] {
         &self.items
     }
+
+    pub fn add_item(&mut self, item: CoreSettingItem) {
+        self.items.push(item);
+    }
 }
 
 /// A core setting item that can be displayed in the core's setting menu.
@@ -286,6 +300,17 @@ pub trait SaveState {
     /// Save the state of the core to a buffer.
     fn save(&mut self, writer: &mut dyn Write) -> Result<(), Error>;
 
+    /// Save the state of the core to a buffer, with a screenshot of the core
+    /// at the time of the save if the format of the save state supports it.
+    fn save_with_screenshot(
+        &mut self,
+        writer: &mut dyn Write,
+        screenshot: Option<&DynamicImage>,
+    ) -> Result<(), Error> {
+        let _ = screenshot;
+        self.save(writer)
+    }
+
     /// Load the state of the core from a buffer.
     fn load(&mut self, reader: &mut dyn Read) -> Result<(), Error>;
 }
@@ -307,7 +332,55 @@ pub enum Error {
     Message(String),
 
     #[error("An error occurred: {0}")]
-    AnyError(#[from] Box<dyn std::error::Error>),
+    AnyError(#[from] Box<dyn std::error::Error + Send + Sync>),
+
+    /// The operation is not supported by this core.
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
+    /// A slot (gamepad, mount, save state, ...) index is out of bound.
+    #[error("Invalid slot: {0}")]
+    InvalidSlot(usize),
+
+    /// The hardware did not respond in time.
+    #[error("Timeout: {0}")]
+    Timeout(String),
+
+    /// The hardware responded with something unexpected.
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+
+    /// The core configuration (e.g. its config string) is invalid.
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    /// The core was closed before a [`CoreHandle`] command could run.
+    #[error("The core is not running anymore")]
+    CoreClosed,
+
+    /// The operation (e.g. sending a file to the core) was cancelled.
+    #[error("The operation was cancelled")]
+    Cancelled,
+
+    /// A save state cannot be loaded in this core (e.g. it was saved from a
+    /// different core or game).
+    #[error("Incompatible save state: {0}")]
+    IncompatibleSaveState(String),
+
+    /// A file needed by the core (e.g. a ROM of an arcade game) was not found.
+    #[error("Missing file: {0}")]
+    MissingFile(String),
+
+    /// A file has the wrong content (e.g. its checksum does not match).
+    #[error("Invalid file: {0}")]
+    InvalidFile(String),
+}
+
+impl Error {
+    /// An error for an operation that the core does not support.
+    pub fn unsupported(operation: impl Into<String>) -> Self {
+        Error::Unsupported(operation.into())
+    }
 }
 
 impl From<String> for Error {
@@ -363,6 +436,9 @@ pub trait Core {
     /// Return a human-readable name for the core.
     fn name(&self) -> &str;
 
+    /// Return the features supported by the core.
+    fn capabilities(&self) -> Result<CoreCapabilities, Error>;
+
     /// Reset the core, restarting the ROM from the beginning.
     fn reset(&mut self) -> Result<(), Error>;
 
@@ -398,6 +474,19 @@ pub trait Core {
     /// return `None`.
     fn mounted_file_mut(&mut self, slot: usize) -> Result<Option<&mut dyn MountedFile>, Error>;
 
+    /// Insert a disk image in a slot. The image already in the slot, if any, is
+    /// ejected first so the core sees the disk change. If the path is an `.m3u`
+    /// playlist, its first disk is inserted (see [`Core::next_disk`]).
+    fn insert_image(&mut self, slot: usize, path: &Path) -> Result<(), Error>;
+
+    /// Eject the disk image in a slot, leaving it empty.
+    fn eject_image(&mut self, slot: usize) -> Result<(), Error>;
+
+    /// Insert the next disk of the playlist of a slot, or the first disk after
+    /// the last one. Returns the path of the disk inserted, or `None` if the
+    /// slot has no playlist.
+    fn next_disk(&mut self, slot: usize) -> Result<Option<PathBuf>, Error>;
+
     /// Load a ROM into the core.
     fn send_rom(&mut self, rom: Rom) -> Result<(), Error>;
 
@@ -434,6 +523,29 @@ pub trait Core {
     /// return `None`.
     fn gamepad_buttons(&self, index: usize) -> Result<Option<gamepad::ButtonSet>, Error>;
 
+    /// Send an analog axis motion event to the core. The value is the raw position
+    /// of the axis, from `i16::MIN` to `i16::MAX` for sticks, and from `0` to
+    /// `i16::MAX` for triggers. Deadzone and range conversion are left to the core.
+    /// Cores that do not support analog inputs should ignore this event.
+    fn gamepad_axis_motion(
+        &mut self,
+        index: usize,
+        axis: gamepad::Axis,
+        value: i16,
+    ) -> Result<(), Error>;
+
+    /// Send a relative mouse motion to the core. Positive `dx` moves right and
+    /// positive `dy` moves down, like SDL. Cores that do not support a mouse should
+    /// ignore this event.
+    fn mouse_move(&mut self, dx: i32, dy: i32) -> Result<(), Error>;
+
+    /// Send a mouse wheel motion to the core. Positive values scroll up (away from
+    /// the user).
+    fn mouse_wheel(&mut self, delta: i32) -> Result<(), Error>;
+
+    fn mouse_button_up(&mut self, button: mouse::MouseButton) -> Result<(), Error>;
+    fn mouse_button_down(&mut self, button: mouse::MouseButton) -> Result<(), Error>;
+
     /// Returns the menu items that the core supports. This would correspond to the
     /// top level page of config items. If the core does not support a menu, this
     /// should return an empty vector.
@@ -461,18 +573,27 @@ pub trait Core {
 
     /// Indicates to the core that it needs to prepare quitting.
     /// This is used to perform any cleanup that the core needs to do before quitting.
-    /// When the core is ready, [should_quit()] should return true.
+    /// When the core is ready, it returns [`CoreEvent::QuitRequested`] from
+    /// [`Self::poll_events`] and [`Self::should_quit`] returns true.
     fn quit(&mut self);
 
     /// Returns true if the core should quit. Some cores might want to quit
     /// back to the main menu by themselves.
     fn should_quit(&self) -> bool;
+
+    /// Poll the core for events that happened since the last call. This should be
+    /// called regularly by the frontend loop. Cores without events return an empty
+    /// list.
+    fn poll_events(&mut self) -> Result<Vec<CoreEvent>, Error>;
 }
 
 /// A core that be used in the `1FPGA` platform. This is a wrapper around a core
 /// that implements the [`Core`] trait. It can be used to pass around a core
 /// without knowing its implementation.
 ///
+/// Other threads must use a [`CoreHandle`] (see [`OneFpgaCore::handle`]) to
+/// talk to the core.
+///
 /// # Safety
 /// We can use UnsafeCell here because we are not sharing the core across threads.
 /// Although this can still lead to undefined behaviour, the underlying core still
@@ -481,19 +602,42 @@ pub trait Core {
 pub struct OneFpgaCore {
     name: String,
     inner: Rc<UnsafeCell<dyn Core + 'static>>,
+    sender: mpsc::Sender<handle::Command>,
+    receiver: Rc<mpsc::Receiver<handle::Command>>,
 }
 
 impl OneFpgaCore {
     pub fn new(core: impl Core + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
         Self {
             name: core.name().to_string(),
             inner: Rc::new(UnsafeCell::new(core)),
+            sender,
+            receiver: Rc::new(receiver),
         }
     }
 
     pub fn null() -> Self {
         Self::new(NullCore)
     }
+
+    /// Create a handle to this core that can be sent to other threads.
+    pub fn handle(&self) -> CoreHandle {
+        CoreHandle::new(self.sender.clone())
+    }
+
+    /// Run the commands sent by [`CoreHandle`]s since the last call, in order.
+    /// This must be called regularly by the thread owning the core, from any of
+    /// its clones (they share the queue), or [`CoreHandle::call`] blocks. Returns
+    /// the number of commands that were run.
+    pub fn process_commands(&mut self) -> usize {
+        let mut count = 0;
+        while let Ok(command) = self.receiver.try_recv() {
+            command(unsafe { &mut *self.inner.get() });
+            count += 1;
+        }
+        count
+    }
 }
 
 impl Core for OneFpgaCore {
@@ -505,6 +649,10 @@ impl Core for OneFpgaCore {
         &self.name
     }
 
+    fn capabilities(&self) -> Result<CoreCapabilities, Error> {
+        unsafe { &mut *self.inner.get() }.capabilities()
+    }
+
     fn reset(&mut self) -> Result<(), Error> {
         unsafe { &mut *self.inner.get() }.reset()
     }
@@ -537,6 +685,18 @@ impl Core for OneFpgaCore {
         unsafe { &mut *self.inner.get() }.mounted_file_mut(slot)
     }
 
+    fn insert_image(&mut self, slot: usize, path: &Path) -> Result<(), Error> {
+        unsafe { &mut *self.inner.get() }.insert_image(slot, path)
+    }
+
+    fn eject_image(&mut self, slot: usize) -> Result<(), Error> {
+        unsafe { &mut *self.inner.get() }.eject_image(slot)
+    }
+
+    fn next_disk(&mut self, slot: usize) -> Result<Option<PathBuf>, Error> {
+        unsafe { &mut *self.inner.get() }.next_disk(slot)
+    }
+
     fn send_rom(&mut self, rom: Rom) -> Result<(), Error> {
         unsafe { &mut *self.inner.get() }.send_rom(rom)
     }
@@ -581,6 +741,31 @@ impl Core for OneFpgaCore {
         unsafe { &mut *self.inner.get() }.gamepad_buttons(index)
     }
 
+    fn gamepad_axis_motion(
+        &mut self,
+        index: usize,
+        axis: gamepad::Axis,
+        value: i16,
+    ) -> Result<(), Error> {
+        unsafe { &mut *self.inner.get() }.gamepad_axis_motion(index, axis, value)
+    }
+
+    fn mouse_move(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        unsafe { &mut *self.inner.get() }.mouse_move(dx, dy)
+    }
+
+    fn mouse_wheel(&mut self, delta: i32) -> Result<(), Error> {
+        unsafe { &mut *self.inner.get() }.mouse_wheel(delta)
+    }
+
+    fn mouse_button_up(&mut self, button: mouse::MouseButton) -> Result<(), Error> {
+        unsafe { &mut *self.inner.get() }.mouse_button_up(button)
+    }
+
+    fn mouse_button_down(&mut self, button: mouse::MouseButton) -> Result<(), Error> {
+        unsafe { &mut *self.inner.get() }.mouse_button_down(button)
+    }
+
     fn settings(&self) -> Result<CoreSettings, Error> {
         unsafe { &mut *self.inner.get() }.settings()
     }
@@ -616,4 +801,8 @@ impl Core for OneFpgaCore {
     fn should_quit(&self) -> bool {
         unsafe { &*self.inner.get() }.should_quit()
     }
+
+    fn poll_events(&mut self) -> Result<Vec<CoreEvent>, Error> {
+        unsafe { &mut *self.inner.get() }.poll_events()
+    }
 }
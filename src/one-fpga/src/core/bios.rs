@@ -1,5 +1,5 @@
 use std::io::{Cursor, Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 /// A BIOS, including any information the core needs to know about the BIOS.
@@ -12,6 +12,17 @@ pub enum Bios {
     File(PathBuf, Arc<std::fs::File>),
 }
 
+impl Bios {
+    /// The path of the BIOS, if known. This is used by cores to select the
+    /// BIOS slot.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Memory(path, _) => path.as_deref(),
+            Self::File(path, _) => Some(path.as_path()),
+        }
+    }
+}
+
 impl Read for Bios {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
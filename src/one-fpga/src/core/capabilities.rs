@@ -0,0 +1,50 @@
+use serde::Serialize;
+
+/// A slot in which the core can load or mount a file (e.g. a disk image or a BIOS).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSlot {
+    /// The index of the slot, as understood by the core.
+    pub index: usize,
+
+    /// A human-readable label for the slot, if the core has one.
+    pub label: Option<String>,
+
+    /// The file extensions (without the leading dot) accepted by the slot.
+    pub extensions: Vec<String>,
+}
+
+/// The features supported by a core. Frontends can use this to show or hide
+/// options without having to probe the core or know its implementation.
+///
+/// Features a core cannot detect are `None` (unknown).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreCapabilities {
+    /// The number of save state slots (0 if save states are not supported).
+    pub save_state_slots: usize,
+
+    /// The slots in which files can be mounted.
+    pub mount_slots: Vec<FileSlot>,
+
+    /// The slots in which a BIOS can be loaded.
+    pub bios_slots: Vec<FileSlot>,
+
+    /// Whether [`super::Core::screenshot`] is supported.
+    pub screenshot: bool,
+
+    /// Whether the core accepts keyboard events.
+    pub keyboard: Option<bool>,
+
+    /// Whether the core accepts mouse events.
+    pub mouse: Option<bool>,
+
+    /// Whether the core accepts analog sticks and triggers events.
+    pub analog: Option<bool>,
+
+    /// The number of gamepads the core accepts events for.
+    pub gamepads: usize,
+
+    /// Whether the core can receive the real time clock.
+    pub rtc: Option<bool>,
+}
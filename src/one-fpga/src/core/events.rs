@@ -0,0 +1,45 @@
+use serde::Serialize;
+
+/// An event produced by a core, returned by [`super::Core::poll_events`].
+/// Frontends should dispatch these without needing to know the core
+/// implementation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum CoreEvent {
+    /// The save state at `slot` changed and should be saved.
+    SaveStateDirty { slot: usize },
+
+    /// The core read from (or wrote to) the file mounted at `slot`.
+    SdServiced { slot: usize, write: bool },
+
+    /// The core changed its own status bits (e.g. a reset or an option
+    /// changed from within the core).
+    StatusBitsChanged,
+
+    /// The core asked to quit back to the menu.
+    QuitRequested,
+
+    /// The video mode output by the core changed.
+    VideoModeChanged {
+        width: u32,
+        height: u32,
+        interlaced: bool,
+    },
+
+    /// An informational message that should be shown to the user.
+    Info { message: String },
+}
+
+impl CoreEvent {
+    /// The name of the event, as used by scripts to register handlers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CoreEvent::SaveStateDirty { .. } => "saveStateDirty",
+            CoreEvent::SdServiced { .. } => "sdServiced",
+            CoreEvent::StatusBitsChanged => "statusBitsChanged",
+            CoreEvent::QuitRequested => "quitRequested",
+            CoreEvent::VideoModeChanged { .. } => "videoModeChanged",
+            CoreEvent::Info { .. } => "info",
+        }
+    }
+}
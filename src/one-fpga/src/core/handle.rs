@@ -0,0 +1,124 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use static_assertions::assert_impl_all;
+
+use crate::core::Error;
+use crate::Core;
+
+/// A command sent from a [`CoreHandle`] to the thread owning the core.
+pub(super) type Command = Box<dyn FnOnce(&mut dyn Core) + Send>;
+
+/// A handle to a core that can be cloned and sent across threads.
+///
+/// Cores (and the FPGA they talk to) are not thread safe, so calls made through
+/// a handle are queued and executed on the thread owning the core, the next time
+/// it calls [`super::OneFpgaCore::process_commands`]. This serialises every
+/// access to the core (including SPI) without slowing down the owner thread.
+#[derive(Clone)]
+pub struct CoreHandle {
+    sender: mpsc::Sender<Command>,
+}
+
+assert_impl_all!(CoreHandle: Send, Sync);
+assert_impl_all!(Error: Send, Sync);
+
+impl CoreHandle {
+    pub(super) fn new(sender: mpsc::Sender<Command>) -> Self {
+        Self { sender }
+    }
+
+    /// Queue a function to run on the core, without waiting for it to run.
+    pub fn send(&self, f: impl FnOnce(&mut dyn Core) + Send + 'static) -> Result<(), Error> {
+        self.sender.send(Box::new(f)).map_err(|_| Error::CoreClosed)
+    }
+
+    /// Run a function on the core and wait for its result.
+    ///
+    /// This blocks until the thread owning the core processes its commands,
+    /// which it does once per iteration of its event loop. It must not be called
+    /// from that thread, as it would never return. Use [`Self::call_timeout`] if
+    /// the owner might be busy for a long time.
+    pub fn call<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut dyn Core) -> R + Send + 'static,
+    ) -> Result<R, Error> {
+        let (tx, rx) = mpsc::sync_channel(1);
+        self.send(move |core| {
+            let _ = tx.send(f(core));
+        })?;
+        rx.recv().map_err(|_| Error::CoreClosed)
+    }
+
+    /// Run a function on the core and wait for its result, up to `timeout`. If the
+    /// timeout expires, the function might still run later.
+    pub fn call_timeout<R: Send + 'static>(
+        &self,
+        timeout: Duration,
+        f: impl FnOnce(&mut dyn Core) -> R + Send + 'static,
+    ) -> Result<R, Error> {
+        let (tx, rx) = mpsc::sync_channel(1);
+        self.send(move |core| {
+            let _ = tx.send(f(core));
+        })?;
+        rx.recv_timeout(timeout).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => {
+                Error::Timeout("The core did not process the command in time".to_string())
+            }
+            mpsc::RecvTimeoutError::Disconnected => Error::CoreClosed,
+        })
+    }
+}
+
+#[test]
+fn handle_call_from_thread() {
+    use crate::OneFpgaCore;
+
+    let mut core = OneFpgaCore::null();
+    let handle = core.handle();
+
+    let thread = std::thread::spawn(move || handle.call(|core| core.name().to_string()));
+    while !thread.is_finished() {
+        core.process_commands();
+    }
+
+    assert_eq!(thread.join().unwrap().unwrap(), "null");
+}
+
+#[test]
+fn handle_processed_by_clone() {
+    use crate::OneFpgaCore;
+
+    // The owner thread can drain the commands from any clone of the core, e.g.
+    // the one kept by its main loop.
+    let core = OneFpgaCore::null();
+    let mut owner = core.clone();
+    let handle = core.handle();
+
+    let other = handle.clone();
+    let thread = std::thread::spawn(move || other.call(|core| core.name().to_string()));
+    while !thread.is_finished() {
+        owner.process_commands();
+    }
+    assert_eq!(thread.join().unwrap().unwrap(), "null");
+
+    // While the owner doesn't process commands, calls time out.
+    let thread = std::thread::spawn(move || handle.call_timeout(Duration::from_millis(10), |_| ()));
+    assert!(matches!(thread.join().unwrap(), Err(Error::Timeout(_))));
+    assert_eq!(owner.process_commands(), 1);
+}
+
+#[test]
+fn handle_closed() {
+    use crate::OneFpgaCore;
+
+    let core = OneFpgaCore::null();
+    let handle = core.handle();
+    drop(core);
+
+    assert!(matches!(handle.send(|_| {}), Err(Error::CoreClosed)));
+    assert!(matches!(
+        handle.call(|core| core.name().to_string()),
+        Err(Error::CoreClosed)
+    ));
+}
@@ -1,12 +1,15 @@
 use std::any::Any;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use image::{ColorType, DynamicImage};
 
-use crate::core::{Bios, CoreSettings, Error, MountedFile, Rom, SaveState, SettingId};
+use crate::core::{
+    Bios, CoreCapabilities, CoreEvent, CoreSettings, Error, MountedFile, Rom, SaveState, SettingId,
+};
 use crate::inputs::gamepad::ButtonSet;
 use crate::inputs::keyboard::ScancodeSet;
-use crate::inputs::{Button, Scancode};
+use crate::inputs::{Axis, Button, MouseButton, Scancode};
 use crate::Core;
 
 /// A Core that does nothing.
@@ -21,6 +24,10 @@ impl Core for NullCore {
         "null"
     }
 
+    fn capabilities(&self) -> Result<CoreCapabilities, Error> {
+        Ok(CoreCapabilities::default())
+    }
+
     fn reset(&mut self) -> Result<(), Error> {
         Ok(())
     }
@@ -53,6 +60,18 @@ impl Core for NullCore {
         Ok(None)
     }
 
+    fn insert_image(&mut self, _slot: usize, _path: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn eject_image(&mut self, _slot: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn next_disk(&mut self, _slot: usize) -> Result<Option<PathBuf>, Error> {
+        Ok(None)
+    }
+
     fn send_rom(&mut self, _rom: Rom) -> Result<(), Error> {
         Ok(())
     }
@@ -93,6 +112,31 @@ impl Core for NullCore {
         Ok(None)
     }
 
+    fn gamepad_axis_motion(
+        &mut self,
+        _index: usize,
+        _axis: Axis,
+        _value: i16,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_move(&mut self, _dx: i32, _dy: i32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_wheel(&mut self, _delta: i32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_button_up(&mut self, _button: MouseButton) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn mouse_button_down(&mut self, _button: MouseButton) -> Result<(), Error> {
+        Ok(())
+    }
+
     fn settings(&self) -> Result<CoreSettings, Error> {
         // TODO: add some basic items.
         Ok(CoreSettings::new("null".to_string(), vec![]))
@@ -127,4 +171,8 @@ impl Core for NullCore {
     fn should_quit(&self) -> bool {
         false
     }
+
+    fn poll_events(&mut self) -> Result<Vec<CoreEvent>, Error> {
+        Ok(Vec::new())
+    }
 }
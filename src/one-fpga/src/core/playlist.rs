@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+/// A list of disk images for a game on multiple disks, which are inserted one
+/// at a time in a slot of the core (see [`super::Core::next_disk`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playlist {
+    disks: Vec<PathBuf>,
+    current: usize,
+}
+
+impl Playlist {
+    /// Whether a path is an `.m3u` playlist.
+    pub fn is_playlist(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("m3u"))
+    }
+
+    /// Parse an `.m3u` playlist. Each line that is not empty or a comment is the
+    /// path of a disk, relative to `dir` if it isn't absolute.
+    pub fn parse(text: &str, dir: &Path) -> Result<Self, String> {
+        let disks = text
+            .lines()
+            .map(|line| line.trim_start_matches('\u{feff}').trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| dir.join(line.replace('\\', "/")))
+            .collect::<Vec<_>>();
+
+        if disks.is_empty() {
+            return Err("Playlist has no disks".to_string());
+        }
+        Ok(Self { disks, current: 0 })
+    }
+
+    /// Read an `.m3u` playlist file.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read playlist {path:?}: {e}"))?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new("")))
+    }
+
+    pub fn disks(&self) -> &[PathBuf] {
+        &self.disks
+    }
+
+    /// The index of the current disk.
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    /// The current disk.
+    pub fn current(&self) -> &Path {
+        &self.disks[self.current]
+    }
+
+    /// Move to the next disk, or the first one after the last, and return it.
+    pub fn next_disk(&mut self) -> &Path {
+        self.current = (self.current + 1) % self.disks.len();
+        self.current()
+    }
+
+    /// Move to a disk by its index. Returns `None` if it is out of bounds.
+    pub fn select(&mut self, index: usize) -> Option<&Path> {
+        if index >= self.disks.len() {
+            return None;
+        }
+        self.current = index;
+        Some(self.current())
+    }
+}
+
+#[test]
+fn playlist() {
+    let mut playlist = Playlist::parse(
+        "\u{feff}#EXTM3U\n\
+         Game (Disk 1).d64\n\
+         \n\
+         # Comment\n\
+         Disks\\Game (Disk 2).d64\r\n\
+         /media/fat/games/C64/Game (Disk 3).d64\n",
+        Path::new("/media/fat/games/C64"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        playlist.disks(),
+        [
+            PathBuf::from("/media/fat/games/C64/Game (Disk 1).d64"),
+            PathBuf::from("/media/fat/games/C64/Disks/Game (Disk 2).d64"),
+            PathBuf::from("/media/fat/games/C64/Game (Disk 3).d64"),
+        ]
+    );
+    assert_eq!(playlist.current(), playlist.disks()[0]);
+    assert_eq!(
+        playlist.next_disk(),
+        Path::new("/media/fat/games/C64/Disks/Game (Disk 2).d64")
+    );
+    playlist.next_disk();
+    assert_eq!(playlist.index(), 2);
+    assert_eq!(
+        playlist.next_disk(),
+        Path::new("/media/fat/games/C64/Game (Disk 1).d64")
+    );
+    assert_eq!(playlist.select(3), None);
+    assert_eq!(
+        playlist.select(1),
+        Some(Path::new("/media/fat/games/C64/Disks/Game (Disk 2).d64"))
+    );
+
+    assert!(Playlist::parse("#EXTM3U\n", Path::new("")).is_err());
+    assert!(Playlist::is_playlist(Path::new("Game.M3U")));
+    assert!(!Playlist::is_playlist(Path::new("Game.d64")));
+}
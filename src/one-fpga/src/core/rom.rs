@@ -1,5 +1,8 @@
 use std::io::Cursor;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// The extensions of the archives ROMs can be loaded from.
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "7z"];
 
 /// A ROM, including any information the core needs to know about the ROM.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,4 +12,41 @@ pub enum Rom {
 
     /// A ROM that is stored in a file on the file system.
     File(PathBuf),
+
+    /// A ROM that is a file inside an archive, with the path of the archive and
+    /// the path of the file in the archive (separated by `/`).
+    Archive(PathBuf, String),
+}
+
+impl Rom {
+    /// Create a ROM from a path. A path that goes through an archive (e.g.
+    /// `SNES/Games.zip/Game.sfc`) refers to a file inside that archive.
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        match split_archive_path(&path) {
+            Some((archive, entry)) => Self::Archive(archive, entry),
+            None => Self::File(path),
+        }
+    }
+}
+
+/// Split a path to a file inside an archive into the path of the archive and the
+/// path of the file in the archive. Returns `None` if none of the parents of the
+/// path is an archive file.
+pub fn split_archive_path(path: &Path) -> Option<(PathBuf, String)> {
+    let archive = path.ancestors().skip(1).find(|p| {
+        p.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| ARCHIVE_EXTENSIONS.iter().any(|x| x.eq_ignore_ascii_case(e)))
+            && p.is_file()
+    })?;
+
+    let entry = path
+        .strip_prefix(archive)
+        .ok()?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some((archive.to_path_buf(), entry))
 }
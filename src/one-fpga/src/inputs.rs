@@ -1,5 +1,7 @@
 pub mod gamepad;
 pub mod keyboard;
+pub mod mouse;
 
 pub use gamepad::{Axis, Button};
 pub use keyboard::Scancode;
+pub use mouse::MouseButton;
@@ -149,6 +149,10 @@ impl ButtonSet {
     pub fn remove(&mut self, button: Button) {
         self.0 &= !(1 << button.as_repr());
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = Button> + '_ {
+        Button::iter().filter(|b| self.contains(*b))
+    }
 }
 
 impl std::fmt::Debug for ButtonSet {
@@ -171,6 +175,23 @@ impl std::fmt::Debug for ButtonSet {
 pub struct Axis(sdl3::gamepad::Axis);
 
 impl Axis {
+    pub const LEFT_X: Axis = Axis(sdl3::gamepad::Axis::LeftX);
+    pub const LEFT_Y: Axis = Axis(sdl3::gamepad::Axis::LeftY);
+    pub const RIGHT_X: Axis = Axis(sdl3::gamepad::Axis::RightX);
+    pub const RIGHT_Y: Axis = Axis(sdl3::gamepad::Axis::RightY);
+    pub const TRIGGER_LEFT: Axis = Axis(sdl3::gamepad::Axis::TriggerLeft);
+    pub const TRIGGER_RIGHT: Axis = Axis(sdl3::gamepad::Axis::TriggerRight);
+
+    pub fn as_sdl(&self) -> sdl3::gamepad::Axis {
+        self.0
+    }
+
+    /// Whether this axis is a trigger (which only has positive values), as opposed
+    /// to a stick axis.
+    pub fn is_trigger(&self) -> bool {
+        *self == Self::TRIGGER_LEFT || *self == Self::TRIGGER_RIGHT
+    }
+
     /// Get a string representation of the axis.
     pub fn name(&self) -> String {
         self.0.string()
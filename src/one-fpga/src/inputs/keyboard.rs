@@ -75,4 +75,8 @@ impl ScancodeSet {
     pub fn remove(&mut self, scancode: Scancode) {
         self.set.remove(&scancode);
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = Scancode> + '_ {
+        self.set.iter().copied()
+    }
 }
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumCount, EnumIter, EnumString, FromRepr};
+
+/// Mouse buttons. The representation is the bit index of the button in a
+/// PS/2 (IntelliMouse Explorer) packet.
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Serialize,
+    Deserialize,
+    EnumIter,
+    EnumString,
+    Display,
+    EnumCount,
+    FromRepr,
+)]
+#[repr(u8)]
+pub enum MouseButton {
+    Left = 0,
+    Right = 1,
+    Middle = 2,
+    X1 = 3,
+    X2 = 4,
+}
+
+impl MouseButton {
+    pub fn as_repr(&self) -> u8 {
+        *self as u8
+    }
+
+    /// The mask of this button in a button state byte.
+    pub fn mask(&self) -> u8 {
+        1 << self.as_repr()
+    }
+}
+
+impl TryFrom<sdl3::mouse::MouseButton> for MouseButton {
+    type Error = ();
+
+    fn try_from(button: sdl3::mouse::MouseButton) -> Result<Self, Self::Error> {
+        match button {
+            sdl3::mouse::MouseButton::Left => Ok(MouseButton::Left),
+            sdl3::mouse::MouseButton::Right => Ok(MouseButton::Right),
+            sdl3::mouse::MouseButton::Middle => Ok(MouseButton::Middle),
+            sdl3::mouse::MouseButton::X1 => Ok(MouseButton::X1),
+            sdl3::mouse::MouseButton::X2 => Ok(MouseButton::X2),
+            sdl3::mouse::MouseButton::Unknown => Err(()),
+        }
+    }
+}
@@ -12,6 +12,10 @@ pub enum CoreType {
     /// Launch a core from an RBF file.
     RbfFile(PathBuf),
 
+    /// Launch an arcade game from an MRA file. The core is found from the MRA,
+    /// and the ROMs it describes are assembled and sent to the core.
+    Mra(PathBuf),
+
     /// Launch the menu core.
     Menu,
 }
@@ -30,6 +34,23 @@ pub struct CoreLaunchInfo<T> {
     pub files: BTreeMap<usize, Slot>,
     pub save_state: Vec<Slot>,
 
+    /// Files to restore the core options from, most specific first (e.g. a
+    /// per-game file, then a per-core file). The first file that exists is
+    /// loaded, and options are saved to the first file when they change.
+    pub status_files: Vec<PathBuf>,
+
+    /// The file to restore the DIP switches from, and to save them to when they
+    /// change. Only used by arcade games.
+    pub dip_file: Option<PathBuf>,
+
+    /// The file to restore the files remembered by the core from (e.g. a palette
+    /// or a boot ROM chosen by the user), and to save them to when they change.
+    /// Frontends use a file per core when this is not set.
+    pub remember_file: Option<PathBuf>,
+
+    /// The number of save state slots, if not the default of the core.
+    pub save_state_slots: Option<usize>,
+
     pub data: T,
 }
 
@@ -41,6 +62,10 @@ impl CoreLaunchInfo<()> {
             bios: Default::default(),
             files: Default::default(),
             save_state: Default::default(),
+            status_files: Default::default(),
+            dip_file: None,
+            remember_file: None,
+            save_state_slots: None,
             data: (),
         }
     }
@@ -49,6 +74,10 @@ impl CoreLaunchInfo<()> {
         Self::new(CoreType::RbfFile(rbf_path))
     }
 
+    pub fn mra(mra_path: PathBuf) -> Self {
+        Self::new(CoreType::Mra(mra_path))
+    }
+
     pub fn menu() -> Self {
         Self::new(CoreType::Menu)
     }
@@ -64,6 +93,11 @@ impl<T> CoreLaunchInfo<T> {
         self
     }
 
+    pub fn with_bios(mut self, bios: Bios) -> Self {
+        self.bios.push(bios);
+        self
+    }
+
     pub fn with_file(mut self, slot: usize, content: Slot) -> Self {
         self.files.insert(slot, content);
         self
@@ -74,6 +108,26 @@ impl<T> CoreLaunchInfo<T> {
         self
     }
 
+    pub fn with_status_file(mut self, path: PathBuf) -> Self {
+        self.status_files.push(path);
+        self
+    }
+
+    pub fn with_dip_file(mut self, path: PathBuf) -> Self {
+        self.dip_file = Some(path);
+        self
+    }
+
+    pub fn with_remember_file(mut self, path: PathBuf) -> Self {
+        self.remember_file = Some(path);
+        self
+    }
+
+    pub fn with_save_state_slots(mut self, slots: usize) -> Self {
+        self.save_state_slots = Some(slots);
+        self
+    }
+
     pub fn with_data<U>(self, data: U) -> CoreLaunchInfo<U> {
         CoreLaunchInfo {
             core: self.core,
@@ -81,6 +135,10 @@ impl<T> CoreLaunchInfo<T> {
             bios: self.bios,
             files: self.files,
             save_state: self.save_state,
+            status_files: self.status_files,
+            dip_file: self.dip_file,
+            remember_file: self.remember_file,
+            save_state_slots: self.save_state_slots,
             data,
         }
     }
@@ -105,7 +105,7 @@ fn main() {
 
     if let Some(tas) = opts.tas {
         // Showtime!
-        core.soft_reset();
+        core.soft_reset().expect("Could not reset the core");
 
         let port0 = *core.gamepad(0).unwrap();
         let frames = read_frames(&tas, port0).expect("Could not read TAS file.");
@@ -169,10 +169,10 @@ fn main() {
             }
 
             if let Some(p0) = p0 {
-                core.send_gamepad(0, p0);
+                core.send_gamepad(0, p0).expect("Could not send gamepad");
             }
             if let Some(p1) = p1 {
-                core.send_gamepad(1, p1);
+                core.send_gamepad(1, p1).expect("Could not send gamepad");
             }
         }
     } else {